@@ -0,0 +1,102 @@
+// Compares chunk meshing with and without greedy meshing: number of instances drawn per frame,
+// bytes uploaded to the GPU, time spent meshing the initially visible world and frame times over a
+// fixed camera path, see frame_timing.rs.
+//
+// Run with `cargo bench --bench meshing`. Frames are rendered offscreen, on the adapter
+// `WGPU_ADAPTER_NAME` names or the first one wgpu finds. For the wasm build, open the page with
+// `?timeFrames` and the frame times show up in the console. `RUST_LOG=info` shows progress.
+use cgmath::Point3;
+use minecrust::camera::Camera;
+use minecrust::frame_timing;
+use minecrust::instance::InstanceRaw;
+use minecrust::world::{self, ChunkDataType, WorldState};
+use std::time::{Duration, Instant};
+
+const NUM_RUNS: u32 = 5;
+const FRAME_WIDTH: u32 = 1280;
+const FRAME_HEIGHT: u32 = 720;
+
+struct MeshingStats {
+    instances: [usize; 3],
+    mesh_time: Duration,
+}
+
+fn mesh_world(greedy_meshing: bool) -> MeshingStats {
+    let center = world::get_world_center();
+    let camera = Camera::new(
+        Point3::<f32>::new(center.x as f32, center.y as f32, center.z as f32),
+        (0.0, 0.0, 0.0).into(),
+        cgmath::Vector3::unit_y(),
+        cgmath::Vector3::unit_y(),
+        16.0 / 9.0,
+        70.0,
+        0.1,
+        250.0,
+    );
+
+    let mut world_state = WorldState::new();
+    world_state.greedy_meshing = greedy_meshing;
+    world_state.initial_setup(&camera);
+
+    let mut instances = [0; 3];
+    let mut mesh_time = Duration::ZERO;
+    for _ in 0..NUM_RUNS {
+        let start = Instant::now();
+        let (all_chunk_data, _) = world_state.generate_world_data(&camera);
+        mesh_time += start.elapsed();
+
+        instances = [0; 3];
+        for chunk_data in all_chunk_data.vec.iter() {
+            for typed_instances in &chunk_data.typed_instances_vec {
+                let idx = match typed_instances.data_type {
+                    ChunkDataType::Opaque => 0,
+                    ChunkDataType::Translucent => 1,
+                    ChunkDataType::SemiTranslucent
+                    | ChunkDataType::TranslucentAndSemiTranslucent => 2,
+                };
+                instances[idx] += typed_instances.instance_data.len();
+            }
+        }
+    }
+
+    MeshingStats {
+        instances,
+        mesh_time: mesh_time / NUM_RUNS,
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let per_face = mesh_world(false);
+    let greedy = mesh_world(true);
+
+    println!();
+    println!(
+        "{:<12} {:>10} {:>12} {:>16} {:>10} {:>10}",
+        "meshing", "opaque", "translucent", "semi-translucent", "MiB", "mesh ms"
+    );
+    for (name, stats) in [("per-face", &per_face), ("greedy", &greedy)] {
+        let total: usize = stats.instances.iter().sum();
+        println!(
+            "{:<12} {:>10} {:>12} {:>16} {:>10.2} {:>10.1}",
+            name,
+            stats.instances[0],
+            stats.instances[1],
+            stats.instances[2],
+            (total * InstanceRaw::size()) as f64 / (1024.0 * 1024.0),
+            stats.mesh_time.as_secs_f64() * 1000.0,
+        );
+    }
+    println!(
+        "greedy meshing draws {:.1}x fewer opaque instances",
+        per_face.instances[0] as f64 / greedy.instances[0].max(1) as f64
+    );
+
+    println!();
+    let frame_times = futures::executor::block_on(frame_timing::time_frames_headless(
+        FRAME_WIDTH,
+        FRAME_HEIGHT,
+    ));
+    print!("{}", frame_timing::report(&frame_times));
+}
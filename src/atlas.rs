@@ -0,0 +1,299 @@
+use image::{Rgba, RgbaImage};
+
+// Pixels along each side of a block texture
+pub const TILE_SIZE: u32 = 16;
+// Each tile is surrounded by its own pixels wrapped around from the opposite edge, the same way
+// merged faces repeat it. Filtering and smaller mip levels then never reach into the neighboring
+// tiles of the atlas.
+pub const TILE_PADDING: u32 = 8;
+// Pixels along each side of a tile's cell in the atlas
+pub const CELL_SIZE: u32 = TILE_SIZE + 2 * TILE_PADDING;
+// Down to 2x2 pixel tiles, the smallest size where the padding is still a whole pixel
+pub const MIP_LEVEL_COUNT: u32 = 4;
+// Cells along each side of the atlas. A power of two, so every mip level halves evenly.
+pub const TILES_PER_ROW: u32 = tiles_per_row(Tile::first_cell(Tile::ALL.len()));
+pub const ATLAS_SIZE: u32 = TILES_PER_ROW * CELL_SIZE;
+
+const fn tiles_per_row(num_cells: u32) -> u32 {
+    let mut tiles_per_row = 1;
+    while tiles_per_row * tiles_per_row < num_cells {
+        tiles_per_row *= 2;
+    }
+    tiles_per_row
+}
+
+// Frames of an animated tile are stacked top to bottom in its PNG and take up consecutive cells of
+// the atlas. The shaders pick the frame from the time in `CameraUniform`, so a full loop should
+// divide `camera::ANIMATION_TIME_WRAP_SECS` to not skip when it wraps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileAnimation {
+    pub frame_count: u32,
+    pub frame_time_secs: f32,
+}
+
+// Block textures, one PNG each in assets/blocks. They're packed into the atlas in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Tile {
+    Debug,
+    GrassBlockTop,
+    GrassBlockSide,
+    Dirt,
+    Sand,
+    Stone,
+    Water,
+    Glass,
+    OakLog,
+    OakLogTop,
+    OakLeaves1,
+    OakLeaves2,
+    OakLeaves3,
+    OakPlanks,
+    RedFlower,
+    Torch,
+    Glowstone,
+    Lantern,
+    Pig,
+    PigSnout,
+    ZombieSkin,
+    ZombieShirt,
+    ZombiePants,
+    // Cracks drawn over a block while it's mined, one frame per `mining::BREAK_STAGE_COUNT`
+    DestroyStages,
+}
+
+impl Tile {
+    pub const ALL: [Tile; 24] = [
+        Tile::Debug,
+        Tile::GrassBlockTop,
+        Tile::GrassBlockSide,
+        Tile::Dirt,
+        Tile::Sand,
+        Tile::Stone,
+        Tile::Water,
+        Tile::Glass,
+        Tile::OakLog,
+        Tile::OakLogTop,
+        Tile::OakLeaves1,
+        Tile::OakLeaves2,
+        Tile::OakLeaves3,
+        Tile::OakPlanks,
+        Tile::RedFlower,
+        Tile::Torch,
+        Tile::Glowstone,
+        Tile::Lantern,
+        Tile::Pig,
+        Tile::PigSnout,
+        Tile::ZombieSkin,
+        Tile::ZombieShirt,
+        Tile::ZombiePants,
+        Tile::DestroyStages,
+    ];
+
+    // File name of the tile's PNG, without the extension
+    pub fn name(self) -> &'static str {
+        match self {
+            Tile::Debug => "debug",
+            Tile::GrassBlockTop => "grass_block_top",
+            Tile::GrassBlockSide => "grass_block_side",
+            Tile::Dirt => "dirt",
+            Tile::Sand => "sand",
+            Tile::Stone => "stone",
+            Tile::Water => "water",
+            Tile::Glass => "glass",
+            Tile::OakLog => "oak_log",
+            Tile::OakLogTop => "oak_log_top",
+            Tile::OakLeaves1 => "oak_leaves_1",
+            Tile::OakLeaves2 => "oak_leaves_2",
+            Tile::OakLeaves3 => "oak_leaves_3",
+            Tile::OakPlanks => "oak_planks",
+            Tile::RedFlower => "red_flower",
+            Tile::Torch => "torch",
+            Tile::Glowstone => "glowstone",
+            Tile::Lantern => "lantern",
+            Tile::Pig => "pig",
+            Tile::PigSnout => "pig_snout",
+            Tile::ZombieSkin => "zombie_skin",
+            Tile::ZombieShirt => "zombie_shirt",
+            Tile::ZombiePants => "zombie_pants",
+            Tile::DestroyStages => "destroy_stages",
+        }
+    }
+
+    pub const fn animation(self) -> Option<TileAnimation> {
+        match self {
+            Tile::Water => Some(TileAnimation {
+                frame_count: 8,
+                frame_time_secs: 0.25,
+            }),
+            _ => None,
+        }
+    }
+
+    pub const fn frame_count(self) -> u32 {
+        match (self, self.animation()) {
+            (Tile::DestroyStages, _) => crate::mining::BREAK_STAGE_COUNT,
+            (_, Some(animation)) => animation.frame_count,
+            (_, None) => 1,
+        }
+    }
+
+    // Cell index of the first frame of the tile at `idx` in `Tile::ALL`, or the number of cells
+    // taken up by all tiles when `idx` is past the end
+    const fn first_cell(idx: usize) -> u32 {
+        let mut cell = 0;
+        let mut i = 0;
+        while i < idx {
+            cell += Tile::ALL[i].frame_count();
+            i += 1;
+        }
+        cell
+    }
+
+    pub fn from_name(name: &str) -> Option<Tile> {
+        Tile::ALL.into_iter().find(|tile| tile.name() == name)
+    }
+
+    // The PNG shipped with the game
+    pub fn builtin_png(self) -> &'static [u8] {
+        match self {
+            Tile::Debug => include_bytes!("../assets/blocks/debug.png"),
+            Tile::GrassBlockTop => include_bytes!("../assets/blocks/grass_block_top.png"),
+            Tile::GrassBlockSide => include_bytes!("../assets/blocks/grass_block_side.png"),
+            Tile::Dirt => include_bytes!("../assets/blocks/dirt.png"),
+            Tile::Sand => include_bytes!("../assets/blocks/sand.png"),
+            Tile::Stone => include_bytes!("../assets/blocks/stone.png"),
+            Tile::Water => include_bytes!("../assets/blocks/water.png"),
+            Tile::Glass => include_bytes!("../assets/blocks/glass.png"),
+            Tile::OakLog => include_bytes!("../assets/blocks/oak_log.png"),
+            Tile::OakLogTop => include_bytes!("../assets/blocks/oak_log_top.png"),
+            Tile::OakLeaves1 => include_bytes!("../assets/blocks/oak_leaves_1.png"),
+            Tile::OakLeaves2 => include_bytes!("../assets/blocks/oak_leaves_2.png"),
+            Tile::OakLeaves3 => include_bytes!("../assets/blocks/oak_leaves_3.png"),
+            Tile::OakPlanks => include_bytes!("../assets/blocks/oak_planks.png"),
+            Tile::RedFlower => include_bytes!("../assets/blocks/red_flower.png"),
+            Tile::Torch => include_bytes!("../assets/blocks/torch.png"),
+            Tile::Glowstone => include_bytes!("../assets/blocks/glowstone.png"),
+            Tile::Lantern => include_bytes!("../assets/blocks/lantern.png"),
+            Tile::Pig => include_bytes!("../assets/blocks/pig.png"),
+            Tile::PigSnout => include_bytes!("../assets/blocks/pig_snout.png"),
+            Tile::ZombieSkin => include_bytes!("../assets/blocks/zombie_skin.png"),
+            Tile::ZombieShirt => include_bytes!("../assets/blocks/zombie_shirt.png"),
+            Tile::ZombiePants => include_bytes!("../assets/blocks/zombie_pants.png"),
+            Tile::DestroyStages => include_bytes!("../assets/blocks/destroy_stages.png"),
+        }
+    }
+
+    // Cell of the tile's first frame in the atlas, in cells. Handed to the shaders as
+    // `InstanceRaw::texture_atlas_offset`.
+    pub fn atlas_offset(self) -> [f32; 2] {
+        cell_offset(Tile::first_cell(self as usize))
+    }
+
+    // Cell of one of the tile's frames, for tiles whose frames are picked by the game instead of
+    // the time
+    pub fn frame_atlas_offset(self, frame: u32) -> [f32; 2] {
+        cell_offset(Tile::first_cell(self as usize) + frame % self.frame_count())
+    }
+}
+
+fn cell_offset(cell: u32) -> [f32; 2] {
+    [(cell % TILES_PER_ROW) as f32, (cell / TILES_PER_ROW) as f32]
+}
+
+// Decodes a tile's PNG into `frame_count` frames, scaling them to `TILE_SIZE` if they have a
+// different size. Frames are square, so a PNG with fewer frames than the tile has, like a
+// single-frame texture for an animated tile, repeats them.
+pub fn decode_tile(png_bytes: &[u8], frame_count: u32) -> image::ImageResult<Vec<RgbaImage>> {
+    let image = image::load_from_memory(png_bytes)?.to_rgba8();
+    let frame_size = image.width().max(1);
+    let image_frame_count = (image.height() / frame_size).max(1);
+    let image_frames: Vec<RgbaImage> = (0..image_frame_count)
+        .map(|frame| {
+            let frame_height = frame_size.min(image.height());
+            let frame_image =
+                image::imageops::crop_imm(&image, 0, frame * frame_size, frame_size, frame_height)
+                    .to_image();
+            if frame_image.dimensions() == (TILE_SIZE, TILE_SIZE) {
+                frame_image
+            } else {
+                image::imageops::resize(
+                    &frame_image,
+                    TILE_SIZE,
+                    TILE_SIZE,
+                    image::imageops::FilterType::Nearest,
+                )
+            }
+        })
+        .collect();
+    Ok((0..frame_count)
+        .map(|frame| image_frames[(frame % image_frame_count) as usize].clone())
+        .collect())
+}
+
+// Packs every frame of every tile into a padded atlas, followed by its smaller mip levels
+pub fn build_atlas(tile_frames: impl Fn(Tile) -> Vec<RgbaImage>) -> Vec<RgbaImage> {
+    let mut atlas = RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE);
+    for (tile_idx, tile) in Tile::ALL.into_iter().enumerate() {
+        let first_cell = Tile::first_cell(tile_idx);
+        for (cell, image) in (first_cell..).zip(tile_frames(tile)) {
+            let [cell_x, cell_y] = cell_offset(cell).map(|offset| offset as u32 * CELL_SIZE);
+            for (y, x) in iproduct!(0..CELL_SIZE, 0..CELL_SIZE) {
+                let tile_x = (x + TILE_SIZE * 2 - TILE_PADDING) % TILE_SIZE;
+                let tile_y = (y + TILE_SIZE * 2 - TILE_PADDING) % TILE_SIZE;
+                atlas.put_pixel(cell_x + x, cell_y + y, *image.get_pixel(tile_x, tile_y));
+            }
+        }
+    }
+
+    let mut mip_levels = vec![atlas];
+    while mip_levels.len() < MIP_LEVEL_COUNT as usize {
+        let next_level = downsample(mip_levels.last().unwrap());
+        mip_levels.push(next_level);
+    }
+    mip_levels
+}
+
+// Averages each 2x2 block of pixels. Colors are weighted by alpha, so the color of fully
+// transparent pixels doesn't darken the edges of leaves and flowers.
+fn downsample(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    RgbaImage::from_fn(width / 2, height / 2, |x, y| {
+        let pixels = [[0, 0], [1, 0], [0, 1], [1, 1]]
+            .map(|[offset_x, offset_y]| *image.get_pixel(x * 2 + offset_x, y * 2 + offset_y));
+        let alpha_sum: u32 = pixels.iter().map(|pixel| pixel[3] as u32).sum();
+        if alpha_sum == 0 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let channel = |c: usize| {
+            let weighted_sum: u32 = pixels
+                .iter()
+                .map(|pixel| pixel[c] as u32 * pixel[3] as u32)
+                .sum();
+            (weighted_sum / alpha_sum) as u8
+        };
+        Rgba([channel(0), channel(1), channel(2), (alpha_sum / 4) as u8])
+    })
+}
+
+// Values for the atlas layout constants and the animated tiles in the shader templates
+pub fn shader_template_data() -> serde_json::Value {
+    let animations: Vec<serde_json::Value> = Tile::ALL
+        .into_iter()
+        .filter_map(|tile| {
+            let animation = tile.animation()?;
+            let [offset_x, offset_y] = tile.atlas_offset();
+            // Debug formatting keeps the decimal point WGSL needs for floats
+            Some(serde_json::json!({
+                "offset": format!("{:?}, {:?}", offset_x, offset_y),
+                "frame_count": format!("{:?}", animation.frame_count as f32),
+                "frame_time_secs": format!("{:?}", animation.frame_time_secs),
+            }))
+        })
+        .collect();
+    serde_json::json!({
+        "tiles_per_row": TILES_PER_ROW,
+        "tile_size": TILE_SIZE,
+        "tile_padding": TILE_PADDING,
+        "animations": animations,
+    })
+}
@@ -0,0 +1,169 @@
+// Plain binary encoding for chunk jobs and their results, which are sent to and from Web Workers
+// as bytes, see `jobs::run_encoded_chunk_job`. Both ends are always the same build, so there's no
+// versioning. Numbers are little endian, sizes and indices are written as u64.
+
+pub struct ByteWriter {
+    bytes: Vec<u8>,
+}
+
+impl ByteWriter {
+    pub fn new() -> Self {
+        Self { bytes: vec![] }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_usizes<const N: usize>(&mut self, values: [usize; N]) {
+        for value in values {
+            self.write_usize(value);
+        }
+    }
+
+    pub fn write_f32s<const N: usize>(&mut self, values: [f32; N]) {
+        for value in values {
+            self.write_f32(value);
+        }
+    }
+
+    // Length first, so `ByteReader::read_bytes` knows where they end
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_usize(bytes.len());
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+impl Default for ByteWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Reads values back in the order they were written. Returns `None` once the bytes run out.
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (taken, rest) = (self.bytes.get(..N)?, &self.bytes[N..]);
+        self.bytes = rest;
+        taken.try_into().ok()
+    }
+
+    pub fn read_u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[value]| value)
+    }
+
+    pub fn read_bool(&mut self) -> Option<bool> {
+        self.read_u8().map(|value| value != 0)
+    }
+
+    pub fn read_u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub fn read_usize(&mut self) -> Option<usize> {
+        self.read_u64()?.try_into().ok()
+    }
+
+    pub fn read_f32(&mut self) -> Option<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    pub fn read_usizes<const N: usize>(&mut self) -> Option<[usize; N]> {
+        let mut values = [0; N];
+        for value in values.iter_mut() {
+            *value = self.read_usize()?;
+        }
+        Some(values)
+    }
+
+    pub fn read_f32s<const N: usize>(&mut self) -> Option<[f32; N]> {
+        let mut values = [0.0; N];
+        for value in values.iter_mut() {
+            *value = self.read_f32()?;
+        }
+        Some(values)
+    }
+
+    pub fn read_bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.read_usize()?;
+        let bytes = self.bytes.get(..len)?;
+        self.bytes = &self.bytes[len..];
+        Some(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_read_back_in_order() {
+        let mut writer = ByteWriter::new();
+        writer.write_u8(7);
+        writer.write_bool(true);
+        writer.write_usize(123_456);
+        writer.write_f32(-1.5);
+        writer.write_bytes(&[1, 2, 3]);
+        writer.write_u64(u64::MAX);
+        writer.write_usizes([4, 5]);
+        writer.write_f32s([0.25, 8.0, -0.0]);
+        let bytes = writer.into_bytes();
+
+        let mut reader = ByteReader::new(&bytes);
+        assert_eq!(reader.read_u8(), Some(7));
+        assert_eq!(reader.read_bool(), Some(true));
+        assert_eq!(reader.read_usize(), Some(123_456));
+        assert_eq!(reader.read_f32(), Some(-1.5));
+        assert_eq!(reader.read_bytes(), Some(&[1, 2, 3][..]));
+        assert_eq!(reader.read_u64(), Some(u64::MAX));
+        assert_eq!(reader.read_usizes(), Some([4, 5]));
+        assert_eq!(reader.read_f32s(), Some([0.25, 8.0, -0.0]));
+        assert!(reader.is_empty());
+        assert_eq!(reader.read_u8(), None);
+    }
+
+    #[test]
+    fn truncated_bytes_read_as_none() {
+        let mut writer = ByteWriter::new();
+        writer.write_bytes(&[1, 2, 3]);
+        let bytes = writer.into_bytes();
+
+        assert_eq!(
+            ByteReader::new(&bytes[..bytes.len() - 1]).read_bytes(),
+            None
+        );
+        assert_eq!(ByteReader::new(&bytes[..4]).read_u64(), None);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{
+    fog::Fog,
     world::{CHUNK_XZ_SIZE},
     DomControlsUserEvent,
 };
@@ -21,6 +22,9 @@ pub struct Camera {
     pub frustum: collision::Frustum<f32>,
 }
 
+// Shader animations loop within this many seconds, so they don't jump when the time wraps around
+const ANIMATION_TIME_WRAP_SECS: f64 = 3600.0;
+
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
@@ -125,7 +129,8 @@ impl Camera {
     }
 
     pub fn update_frustum(&mut self) {
-        let half_v_side = self.zfar * (self.fovy * 0.5).tan();
+        // fovy is in degrees
+        let half_v_side = self.zfar * (self.fovy.to_radians() * 0.5).tan();
         let half_h_side = half_v_side * self.aspect;
 
         let forward_norm = (self.target - self.eye).normalize();
@@ -173,6 +178,13 @@ pub struct CameraUniform {
     // to convert the Matrix4 into a 4x4 f32 array
     view_proj: [[f32; 4]; 4],
     eye_pos: [f32; 4],
+    // See `Fog::params`
+    fog: [f32; 4],
+    // Linear RGB, w is padding
+    fog_color: [f32; 4],
+    // x: seconds the game has been running for, wrapped around every now and then to keep its
+    // precision, the rest is padding
+    time: [f32; 4],
 }
 
 impl CameraUniform {
@@ -180,6 +192,9 @@ impl CameraUniform {
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
             eye_pos: [0.0, 0.0, 0.0, 0.0],
+            fog: [0.0, 0.0, 0.0, 0.0],
+            fog_color: [0.0, 0.0, 0.0, 0.0],
+            time: [0.0, 0.0, 0.0, 0.0],
         }
     }
 
@@ -187,6 +202,16 @@ impl CameraUniform {
         self.view_proj = camera.build_view_projection_matrix().into();
         self.eye_pos = [camera.eye.x, camera.eye.y, camera.eye.z, 1.0];
     }
+
+    pub fn update_fog(&mut self, fog: &Fog) {
+        self.fog = fog.params();
+        self.fog_color = fog.color.extend(1.0).into();
+    }
+
+    // Drives animations in the shaders, like the water surface
+    pub fn update_time(&mut self, running_time_secs: f64) {
+        self.time[0] = (running_time_secs % ANIMATION_TIME_WRAP_SECS) as f32;
+    }
 }
 
 pub struct CameraController {
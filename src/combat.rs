@@ -0,0 +1,117 @@
+use crate::entity::Entities;
+
+// After being hurt, entities and the character can't be hurt again for this long. Entities flash
+// red and are knocked back meanwhile.
+pub const HURT_SECS: f32 = 0.5;
+// Multiplied with the colors of an entity's model while it's hurt
+pub const HURT_COLOR_ADJUST: [f32; 4] = [1.0, 0.45, 0.45, 1.0];
+// In blocks per second
+const KNOCKBACK_SPEED: f32 = 6.0;
+const KNOCKBACK_UP_SPEED: f32 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub points: f32,
+    pub max_points: f32,
+    // Counts down after being hurt, see `HURT_SECS`
+    pub hurt_secs_left: f32,
+}
+
+impl Health {
+    pub fn new(max_points: f32) -> Self {
+        Health {
+            points: max_points,
+            max_points,
+            hurt_secs_left: 0.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.points <= 0.0
+    }
+
+    pub fn is_hurt(&self) -> bool {
+        self.hurt_secs_left > 0.0
+    }
+
+    // Takes `damage` points, unless it's dead or was hurt too recently. Returns whether it was
+    // hurt.
+    pub fn damage(&mut self, damage: f32) -> bool {
+        if self.is_dead() || self.is_hurt() {
+            return false;
+        }
+        self.points = (self.points - damage).max(0.0);
+        self.hurt_secs_left = HURT_SECS;
+        true
+    }
+
+    pub fn heal(&mut self, points: f32) {
+        if !self.is_dead() {
+            self.points = (self.points + points).min(self.max_points);
+        }
+    }
+
+    pub fn tick(&mut self, tick_secs: f32) {
+        self.hurt_secs_left = (self.hurt_secs_left - tick_secs).max(0.0);
+    }
+}
+
+// Velocity in blocks per second that knocks something at `target_position` up and away from
+// `attacker_position`
+pub fn knockback_velocity(
+    attacker_position: glam::Vec3,
+    target_position: glam::Vec3,
+) -> glam::Vec3 {
+    let away = target_position - attacker_position;
+    let away = glam::Vec2::new(away.x, away.z).normalize_or_zero() * KNOCKBACK_SPEED;
+    glam::Vec3::new(away.x, KNOCKBACK_UP_SPEED, away.y)
+}
+
+// An entity's attack on the character up close, see `melee_tick`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Melee {
+    pub damage: f32,
+    // Blocks between the entity's collider and the character's that it can hit across
+    pub reach: f32,
+    pub cooldown_secs: f32,
+    pub cooldown_secs_left: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeleeHit {
+    pub attacker_position: glam::Vec3,
+    pub damage: f32,
+}
+
+// Entities with a `Melee` attack the character's cylinder when it's in reach and they've cooled
+// down from their last attack. Returns the hits on the character.
+pub fn melee_tick(
+    entities: &mut Entities,
+    character_position: glam::Vec3,
+    character_radius: f32,
+    character_half_height: f32,
+    tick_secs: f32,
+) -> Vec<MeleeHit> {
+    let mut hits = vec![];
+    for (_, entity) in entities.iter_mut() {
+        let is_alive = entity.is_alive();
+        let half_extents = entity.collider.half_extents();
+        let Some(melee) = &mut entity.melee else {
+            continue;
+        };
+        melee.cooldown_secs_left = (melee.cooldown_secs_left - tick_secs).max(0.0);
+
+        let offset = character_position - entity.position;
+        let is_in_reach = glam::Vec2::new(offset.x, offset.z).length()
+            <= half_extents.x + character_radius + melee.reach
+            && offset.y.abs() <= half_extents.y + character_half_height + melee.reach;
+        if is_alive && is_in_reach && melee.cooldown_secs_left == 0.0 {
+            melee.cooldown_secs_left = melee.cooldown_secs;
+            hits.push(MeleeHit {
+                attacker_position: entity.position,
+                damage: melee.damage,
+            });
+        }
+    }
+    hits
+}
@@ -0,0 +1,415 @@
+use crate::inventory::{Inventory, ItemStack};
+use crate::item::Item;
+
+// Recipes span at most a 3x3 crafting grid
+pub const MAX_GRID_SIZE: usize = 3;
+
+#[derive(Debug)]
+pub enum RecipeError {
+    Json(serde_json::Error),
+    InvalidRecipe { recipe_idx: usize, reason: String },
+}
+
+impl std::fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecipeError::Json(err) => write!(f, "{}", err),
+            RecipeError::InvalidRecipe { recipe_idx, reason } => {
+                write!(f, "recipe {}: {}", recipe_idx, reason)
+            }
+        }
+    }
+}
+
+// Items laid out on a crafting grid, row by row from the top left
+#[derive(Clone, Debug, PartialEq)]
+pub struct CraftingGrid {
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<Option<Item>>,
+}
+
+impl CraftingGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        CraftingGrid {
+            width,
+            height,
+            cells: vec![None; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Item> {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, item: Option<Item>) {
+        self.cells[y * self.width + x] = item;
+    }
+
+    // The smallest part of the grid holding all of its items, so a shaped recipe matches
+    // anywhere on a larger grid
+    fn trimmed(&self) -> CraftingGrid {
+        let is_row_empty = |y: usize| (0..self.width).all(|x| self.get(x, y).is_none());
+        let is_column_empty = |x: usize| (0..self.height).all(|y| self.get(x, y).is_none());
+        let (Some(min_y), Some(max_y)) = (
+            (0..self.height).find(|&y| !is_row_empty(y)),
+            (0..self.height).rfind(|&y| !is_row_empty(y)),
+        ) else {
+            return CraftingGrid::new(0, 0);
+        };
+        let min_x = (0..self.width).find(|&x| !is_column_empty(x)).unwrap();
+        let max_x = (0..self.width).rfind(|&x| !is_column_empty(x)).unwrap();
+
+        let mut trimmed = CraftingGrid::new(max_x - min_x + 1, max_y - min_y + 1);
+        for (y, x) in iproduct!(0..trimmed.height, 0..trimmed.width) {
+            trimmed.set(x, y, self.get(min_x + x, min_y + y));
+        }
+        trimmed
+    }
+
+    fn mirrored(&self) -> CraftingGrid {
+        let mut mirrored = CraftingGrid::new(self.width, self.height);
+        for (y, x) in iproduct!(0..self.height, 0..self.width) {
+            mirrored.set(x, y, self.get(self.width - 1 - x, y));
+        }
+        mirrored
+    }
+
+    fn items(&self) -> impl Iterator<Item = Item> + '_ {
+        self.cells.iter().flatten().copied()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecipeShape {
+    // Items have to be laid out in this pattern, or its mirror image, anywhere on the grid
+    Shaped(CraftingGrid),
+    // Items can be anywhere on the grid
+    Shapeless(Vec<Item>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipe {
+    pub shape: RecipeShape,
+    pub result: ItemStack,
+}
+
+impl Recipe {
+    pub fn matches(&self, grid: &CraftingGrid) -> bool {
+        match &self.shape {
+            RecipeShape::Shaped(pattern) => {
+                let trimmed = grid.trimmed();
+                trimmed == *pattern || trimmed.mirrored() == *pattern
+            }
+            RecipeShape::Shapeless(ingredients) => {
+                let mut remaining: Vec<Item> = grid.items().collect();
+                for ingredient in ingredients {
+                    match remaining.iter().position(|item| item == ingredient) {
+                        Some(idx) => remaining.swap_remove(idx),
+                        None => return false,
+                    };
+                }
+                remaining.is_empty()
+            }
+        }
+    }
+
+    // How many of each item the recipe uses up
+    pub fn ingredient_counts(&self) -> Vec<(Item, u32)> {
+        let items: Vec<Item> = match &self.shape {
+            RecipeShape::Shaped(pattern) => pattern.items().collect(),
+            RecipeShape::Shapeless(ingredients) => ingredients.clone(),
+        };
+        let mut counts: Vec<(Item, u32)> = vec![];
+        for item in items {
+            match counts
+                .iter_mut()
+                .find(|(counted_item, _)| *counted_item == item)
+            {
+                Some((_, count)) => *count += 1,
+                None => counts.push((item, 1)),
+            }
+        }
+        counts
+    }
+
+    pub fn uses(&self, item: Item) -> bool {
+        self.ingredient_counts()
+            .iter()
+            .any(|&(ingredient, _)| ingredient == item)
+    }
+
+    // Crafting straight from the inventory, wherever the ingredients are. Leaves the inventory
+    // untouched and returns false if an ingredient is missing or the result doesn't fit.
+    pub fn craft(&self, inventory: &mut Inventory) -> bool {
+        let mut crafted = inventory.clone();
+        for (item, count) in self.ingredient_counts() {
+            if !crafted.remove(item, count) {
+                return false;
+            }
+        }
+        if crafted.add(self.result.item, self.result.count) > 0 {
+            return false;
+        }
+        *inventory = crafted;
+        true
+    }
+
+    pub fn can_craft(&self, inventory: &Inventory) -> bool {
+        self.craft(&mut inventory.clone())
+    }
+
+    // See assets/recipes.json
+    fn from_json(json: &serde_json::Value) -> Result<Self, String> {
+        let item = |name: &serde_json::Value| {
+            let name = name.as_str().ok_or("expected an item name")?;
+            Item::from_name(name).ok_or(format!("unknown item {}", name))
+        };
+
+        let shape = match json["type"].as_str() {
+            Some("shaped") => {
+                let rows: Vec<&str> = json["pattern"]
+                    .as_array()
+                    .ok_or("expected a \"pattern\" array")?
+                    .iter()
+                    .map(|row| row.as_str().ok_or("expected a pattern row"))
+                    .collect::<Result<_, _>>()?;
+                let width = rows.first().map_or(0, |row| row.chars().count());
+                if !(1..=MAX_GRID_SIZE).contains(&rows.len())
+                    || !(1..=MAX_GRID_SIZE).contains(&width)
+                    || rows.iter().any(|row| row.chars().count() != width)
+                {
+                    return Err(format!(
+                        "expected a pattern of rows of equal length, up to {}x{}",
+                        MAX_GRID_SIZE, MAX_GRID_SIZE
+                    ));
+                }
+
+                let mut pattern = CraftingGrid::new(width, rows.len());
+                for (y, row) in rows.iter().enumerate() {
+                    for (x, symbol) in row.chars().enumerate() {
+                        if symbol == ' ' {
+                            continue;
+                        }
+                        let key = &json["key"][symbol.to_string()];
+                        if key.is_null() {
+                            return Err(format!("no key for {:?} in the pattern", symbol));
+                        }
+                        pattern.set(x, y, Some(item(key)?));
+                    }
+                }
+                if pattern.trimmed() != pattern {
+                    return Err("pattern has empty rows or columns at its edges".to_string());
+                }
+                RecipeShape::Shaped(pattern)
+            }
+            Some("shapeless") => {
+                let ingredients: Vec<Item> = json["ingredients"]
+                    .as_array()
+                    .ok_or("expected an \"ingredients\" array")?
+                    .iter()
+                    .map(item)
+                    .collect::<Result<_, _>>()?;
+                if !(1..=MAX_GRID_SIZE * MAX_GRID_SIZE).contains(&ingredients.len()) {
+                    return Err("expected at least one ingredient that fits the grid".to_string());
+                }
+                RecipeShape::Shapeless(ingredients)
+            }
+            _ => return Err("expected a \"type\" of shaped or shapeless".to_string()),
+        };
+
+        let result_item = item(&json["result"])?;
+        let count = match &json["count"] {
+            serde_json::Value::Null => 1,
+            count => count
+                .as_u64()
+                .filter(|count| (1..=result_item.max_stack_size() as u64).contains(count))
+                .ok_or("expected a count that fits a stack")? as u32,
+        };
+        Ok(Recipe {
+            shape,
+            result: ItemStack {
+                item: result_item,
+                count,
+            },
+        })
+    }
+}
+
+// All recipes, in the order they're tried
+pub struct RecipeBook {
+    recipes: Vec<Recipe>,
+}
+
+impl RecipeBook {
+    pub fn builtin() -> Self {
+        RecipeBook::from_json(include_str!("../assets/recipes.json"))
+            .expect("Failed to load built-in recipes")
+    }
+
+    // A JSON array of recipes, see assets/recipes.json
+    pub fn from_json(json: &str) -> Result<Self, RecipeError> {
+        let json: serde_json::Value = serde_json::from_str(json).map_err(RecipeError::Json)?;
+        let recipes = json
+            .as_array()
+            .ok_or(RecipeError::InvalidRecipe {
+                recipe_idx: 0,
+                reason: "expected an array of recipes".to_string(),
+            })?
+            .iter()
+            .enumerate()
+            .map(|(recipe_idx, recipe_json)| {
+                Recipe::from_json(recipe_json)
+                    .map_err(|reason| RecipeError::InvalidRecipe { recipe_idx, reason })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(RecipeBook { recipes })
+    }
+
+    pub fn recipes(&self) -> &[Recipe] {
+        &self.recipes
+    }
+
+    // The recipe for the items laid out on `grid`
+    pub fn find_match(&self, grid: &CraftingGrid) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.matches(grid))
+    }
+
+    // The first recipe that can be crafted from the inventory, using `item` if given
+    pub fn first_craftable(&self, inventory: &Inventory, item: Option<Item>) -> Option<&Recipe> {
+        self.recipes
+            .iter()
+            .find(|recipe| item.is_none_or(|item| recipe.uses(item)) && recipe.can_craft(inventory))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::BlockType;
+
+    const RECIPES_JSON: &str = r#"[
+        {
+            "type": "shaped",
+            "pattern": ["PP", "PS", " S"],
+            "key": { "P": "OakPlank", "S": "Stick" },
+            "result": "WoodenAxe"
+        },
+        {
+            "type": "shaped",
+            "pattern": ["P", "P"],
+            "key": { "P": "OakPlank" },
+            "result": "Stick",
+            "count": 4
+        },
+        {
+            "type": "shapeless",
+            "ingredients": ["Sand", "Stone", "Stone"],
+            "result": "Glass",
+            "count": 2
+        }
+    ]"#;
+
+    // A 3x3 grid, with P for oak planks, S for sticks, C for stone and A for sand
+    fn grid(rows: [&str; 3]) -> CraftingGrid {
+        let mut grid = CraftingGrid::new(MAX_GRID_SIZE, MAX_GRID_SIZE);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, symbol) in row.chars().enumerate() {
+                let item = match symbol {
+                    'P' => Item::Block(BlockType::OakPlank),
+                    'S' => Item::Stick,
+                    'C' => Item::Block(BlockType::Stone),
+                    'A' => Item::Block(BlockType::Sand),
+                    _ => continue,
+                };
+                grid.set(x, y, Some(item));
+            }
+        }
+        grid
+    }
+
+    fn crafted_item(grid: &CraftingGrid) -> Option<Item> {
+        let recipe_book = RecipeBook::from_json(RECIPES_JSON).unwrap();
+        recipe_book
+            .find_match(grid)
+            .map(|recipe| recipe.result.item)
+    }
+
+    #[test]
+    fn shaped_recipe_matches_its_pattern() {
+        assert_eq!(
+            crafted_item(&grid(["PP ", "PS ", " S "])),
+            Some(Item::WoodenAxe)
+        );
+        // Mirror image
+        assert_eq!(
+            crafted_item(&grid([" PP", " SP", " S "])),
+            Some(Item::WoodenAxe)
+        );
+    }
+
+    #[test]
+    fn shaped_recipe_matches_anywhere_on_the_grid() {
+        assert_eq!(
+            crafted_item(&grid([" PP", " PS", "  S"])),
+            Some(Item::WoodenAxe)
+        );
+        for rows in [
+            ["P  ", "P  ", "   "],
+            ["   ", "  P", "  P"],
+            ["   ", " P ", " P "],
+        ] {
+            assert_eq!(crafted_item(&grid(rows)), Some(Item::Stick), "{:?}", rows);
+        }
+    }
+
+    #[test]
+    fn shapeless_recipe_matches_in_any_order() {
+        for rows in [
+            ["ACC", "   ", "   "],
+            ["C  ", " A ", "  C"],
+            ["   ", "C C", " A "],
+        ] {
+            assert_eq!(
+                crafted_item(&grid(rows)),
+                Some(Item::Block(BlockType::Glass)),
+                "{:?}",
+                rows
+            );
+        }
+    }
+
+    #[test]
+    fn extra_items_dont_match() {
+        assert_eq!(crafted_item(&grid(["PP ", "PS ", " SS"])), None);
+        assert_eq!(crafted_item(&grid(["P  ", "P  ", "P  "])), None);
+        assert_eq!(crafted_item(&grid(["ACC", "C  ", "   "])), None);
+    }
+
+    #[test]
+    fn wrong_items_dont_match() {
+        assert_eq!(crafted_item(&grid(["PP ", "CS ", " S "])), None);
+        assert_eq!(crafted_item(&grid(["P  ", "S  ", "   "])), None);
+        assert_eq!(crafted_item(&grid(["ACA", "   ", "   "])), None);
+        // Missing an ingredient
+        assert_eq!(crafted_item(&grid(["AC ", "   ", "   "])), None);
+        assert_eq!(crafted_item(&grid(["   ", "   ", "   "])), None);
+    }
+
+    #[test]
+    fn builtin_recipes_load() {
+        assert!(!RecipeBook::builtin().recipes().is_empty());
+    }
+
+    #[test]
+    fn invalid_recipes_are_reported_by_index() {
+        let json = r#"[
+            { "type": "shapeless", "ingredients": ["Tree"], "result": "OakPlank" },
+            { "type": "shaped", "pattern": ["X"], "key": { "X": "Bedrock" }, "result": "Stick" }
+        ]"#;
+        assert!(matches!(
+            RecipeBook::from_json(json),
+            Err(RecipeError::InvalidRecipe { recipe_idx: 1, .. })
+        ));
+    }
+}
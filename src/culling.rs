@@ -0,0 +1,68 @@
+use crate::camera::Camera;
+use crate::lod::LodTileIdx;
+use crate::world::CHUNK_XZ_SIZE;
+use cgmath::Point3;
+use collision::{Aabb3, Relation};
+use glam::{Mat4, Vec3};
+
+// Box around the visible blocks of a chunk, in world coordinates
+pub fn chunk_aabb([chunk_x, chunk_z]: [usize; 2], [min_y, max_y]: [f32; 2]) -> Aabb3<f32> {
+    let min_x = (chunk_x * CHUNK_XZ_SIZE) as f32;
+    let min_z = (chunk_z * CHUNK_XZ_SIZE) as f32;
+    Aabb3::new(
+        Point3::new(min_x, min_y, min_z),
+        Point3::new(
+            min_x + CHUNK_XZ_SIZE as f32,
+            max_y,
+            min_z + CHUNK_XZ_SIZE as f32,
+        ),
+    )
+}
+
+pub fn lod_tile_aabb(idx: LodTileIdx, [min_y, max_y]: [f32; 2]) -> Aabb3<f32> {
+    let [first_chunk_x, first_chunk_z] = idx.first_chunk();
+    let width = (LodTileIdx::chunk_width(idx.cell_size) * CHUNK_XZ_SIZE) as f32;
+    let min_x = (first_chunk_x * CHUNK_XZ_SIZE) as f32;
+    let min_z = (first_chunk_z * CHUNK_XZ_SIZE) as f32;
+    Aabb3::new(
+        Point3::new(min_x, min_y, min_z),
+        Point3::new(min_x + width, max_y, min_z + width),
+    )
+}
+
+pub fn is_in_camera_frustum(camera: &Camera, aabb: &Aabb3<f32>) -> bool {
+    camera.frustum.contains(aabb) != Relation::Out
+}
+
+// `light_space_matrix` works on positions relative to the camera eye, like the shadow map shader
+pub fn is_in_light_volume(light_space_matrix: &Mat4, camera: &Camera, aabb: &Aabb3<f32>) -> bool {
+    let eye = Vec3::new(camera.eye.x, camera.eye.y, camera.eye.z);
+    let clip_corners = aabb.to_corners().map(|corner| {
+        light_space_matrix.project_point3(Vec3::new(corner.x, corner.y, corner.z) - eye)
+    });
+
+    // The light volume is a box, so the chunk is outside if all of its corners are past one of
+    // the sides. The shadow map shader remaps z from [-1, 1] to [0, 1].
+    let is_outside = |is_past_side: fn(&Vec3) -> bool| clip_corners.iter().all(is_past_side);
+    !(is_outside(|c| c.x < -1.0)
+        || is_outside(|c| c.x > 1.0)
+        || is_outside(|c| c.y < -1.0)
+        || is_outside(|c| c.y > 1.0)
+        || is_outside(|c| c.z < -1.0)
+        || is_outside(|c| c.z > 1.0))
+}
+
+// Number of chunks drawn and skipped by culling in the last frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullingStats {
+    pub camera_drawn: usize,
+    // Outside of the view frustum
+    pub camera_culled: usize,
+    // In the view frustum but hidden behind terrain, see occlusion.rs
+    pub camera_occluded: usize,
+    pub shadow_drawn: usize,
+    pub shadow_culled: usize,
+    // LOD tiles, see lod.rs
+    pub lod_drawn: usize,
+    pub lod_culled: usize,
+}
@@ -1,3 +1,5 @@
+use crate::light::ShadowQuality;
+use crate::world_clock::TimeOfDay;
 use winit::event_loop::{EventLoop, EventLoopProxy};
 
 #[cfg(target_arch = "wasm32")]
@@ -19,6 +21,13 @@ pub enum DomControlsUserEvent {
     TranslationJoystickReleased,
     WindowResized { size: winit::dpi::LogicalSize<u32> },
     WebPointerLockLost,
+    RenderDistanceChanged { visible_chunk_width: usize },
+    TimeOfDayChanged { time_of_day: f32 },
+    ShadowQualityChanged { shadow_quality: ShadowQuality },
+    ResourcePackLoaded { zip_bytes: Vec<u8> },
+    ResourcePackReset,
+    InventoryLoaded { json: String },
+    TimeFramesRequested,
 }
 
 struct EventLoopGlobalState {
@@ -103,7 +112,62 @@ pub fn web_window_resized(width: u32, height: u32) {
 pub fn web_pointer_lock_lost() {
     send_dom_controls_user_event(DomControlsUserEvent::WebPointerLockLost);
 }
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn set_render_distance(visible_chunk_width: u32) {
+    send_dom_controls_user_event(DomControlsUserEvent::RenderDistanceChanged {
+        visible_chunk_width: visible_chunk_width as usize,
+    });
+}
+// `time_of_day` is a fraction of the day, 0 is midnight and 0.5 is noon
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn set_time_of_day(time_of_day: f32) {
+    send_dom_controls_user_event(DomControlsUserEvent::TimeOfDayChanged { time_of_day });
+}
+// E.g. "dusk", see `TimeOfDay::from_name`
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn set_time_of_day_by_name(name: &str) {
+    match TimeOfDay::from_name(name) {
+        Some(preset) => set_time_of_day(preset.time_of_day()),
+        None => log::warn!("Unknown time of day: {}", name),
+    }
+}
+// "low", "medium" or "high", see `ShadowQuality::from_name`
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn set_shadow_quality(name: &str) {
+    match ShadowQuality::from_name(name) {
+        Some(shadow_quality) => {
+            send_dom_controls_user_event(DomControlsUserEvent::ShadowQualityChanged {
+                shadow_quality,
+            })
+        }
+        None => log::warn!("Unknown shadow quality: {}", name),
+    }
+}
+// Switches to the resource pack in a zip archive, see resource_pack.rs
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn load_resource_pack(zip_bytes: Vec<u8>) {
+    send_dom_controls_user_event(DomControlsUserEvent::ResourcePackLoaded { zip_bytes });
+}
+// Switches back to the built-in textures
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn reset_resource_pack() {
+    send_dom_controls_user_event(DomControlsUserEvent::ResourcePackReset);
+}
+// Restores an inventory the page saved, see `Inventory::to_json`
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn set_inventory(json: &str) {
+    send_dom_controls_user_event(DomControlsUserEvent::InventoryLoaded {
+        json: json.to_string(),
+    });
+}
+// Times frames with and without greedy meshing and logs the results, see frame_timing.rs
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn time_frames() {
+    send_dom_controls_user_event(DomControlsUserEvent::TimeFramesRequested);
+}
 
+#[cfg(target_arch = "wasm32")]
+use crate::inventory::ItemStack;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::JsValue;
 
@@ -112,10 +176,16 @@ extern "C" {
     // Define the JavaScript function signature
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen(js_namespace = window)]
-    fn handlePlaceBlockTypeChanged(eventData: &JsValue);
+    fn handlePlaceBlockTypeChanged(eventData: &JsValue, count: &JsValue);
 }
 
+// Shows the selected hotbar slot in the block preview by item name, "Empty" with a count of 0 if
+// it's empty
 #[cfg(target_arch = "wasm32")]
-pub fn place_block_type_changed(block_type: &str) {
-    handlePlaceBlockTypeChanged(&JsValue::from_str(block_type));
+pub fn place_block_type_changed(item_stack: Option<ItemStack>) {
+    let (item_name, count) = match item_stack {
+        Some(item_stack) => (item_stack.item.to_string(), item_stack.count),
+        None => (crate::world::BlockType::Empty.to_string(), 0),
+    };
+    handlePlaceBlockTypeChanged(&JsValue::from_str(&item_name), &JsValue::from(count));
 }
\ No newline at end of file
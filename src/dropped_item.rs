@@ -0,0 +1,138 @@
+use crate::entity::{Entities, Entity, EntityId, Physics, Renderable};
+use crate::inventory::{Inventory, MAX_STACK_SIZE};
+use crate::item::Item;
+use crate::physics::Collider;
+use crate::world::BlockType;
+
+// Oldest items despawn first once there are more
+pub const MAX_DROPPED_ITEMS: usize = 256;
+// Blocks along each side of an item's cube
+const ITEM_SIZE: f32 = 0.25;
+const ITEM_PHYSICS: Physics = Physics {
+    // Items fall a bit faster than the character
+    gravity: 16.0,
+    terminal_velocity: 30.0,
+    ground_friction: 0.002,
+};
+// Items pop up and out of the block they were broken from
+const SPAWN_UP_VELOCITY: f32 = 4.0;
+const SPAWN_SIDE_VELOCITY: f32 = 1.5;
+// Items can't be picked up right after they drop, so they can be seen popping out of the block
+const PICKUP_DELAY_SECS: f32 = 0.5;
+// Items are picked up once they're this close to the character's cylinder
+const PICKUP_RANGE: f32 = 1.0;
+// Items of the same block closer than this merge into one stack
+const MERGE_RANGE: f32 = 0.75;
+const DESPAWN_SECS: f32 = 300.0;
+
+// A stack of blocks lying in the world, waiting to be picked up. The entity spins it, see
+// `Renderable::SpinningBlock`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DroppedItem {
+    pub block_type: BlockType,
+    pub count: u32,
+}
+
+// Drops `count` blocks from the block at `block_pos`, flying off in a random direction
+pub fn spawn(
+    entities: &mut Entities,
+    block_type: BlockType,
+    count: u32,
+    block_pos: [usize; 3],
+) -> EntityId {
+    let [x, y, z] = block_pos.map(|n| n as f32 + 0.5);
+    let mut entity = Entity::new(
+        glam::Vec3::new(x, y, z),
+        Collider::Cuboid {
+            half_extents: glam::Vec3::splat(ITEM_SIZE / 2.0),
+        },
+    );
+    let random_side_velocity = || (rand::random::<f32>() * 2.0 - 1.0) * SPAWN_SIDE_VELOCITY;
+    entity.velocity = glam::Vec3::new(
+        random_side_velocity(),
+        SPAWN_UP_VELOCITY,
+        random_side_velocity(),
+    );
+    entity.physics = Some(ITEM_PHYSICS);
+    entity.lifetime_secs = Some(DESPAWN_SECS);
+    entity.renderable = Some(Renderable::SpinningBlock(block_type));
+    entity.dropped_item = Some(DroppedItem { block_type, count });
+    let id = entities.spawn(entity);
+
+    let oldest_items: Vec<EntityId> = {
+        let mut dropped_items: Vec<(EntityId, f32)> = entities
+            .iter()
+            .filter(|(_, entity)| entity.dropped_item.is_some())
+            .map(|(id, entity)| (id, entity.age_secs))
+            .collect();
+        dropped_items.sort_by(|(_, age_a), (_, age_b)| age_b.total_cmp(age_a));
+        let excess = dropped_items.len().saturating_sub(MAX_DROPPED_ITEMS);
+        dropped_items[..excess].iter().map(|&(id, _)| id).collect()
+    };
+    for oldest_id in oldest_items {
+        entities.despawn(oldest_id);
+    }
+    id
+}
+
+// Merges items of the same block that lie next to each other into one stack
+pub fn merge(entities: &mut Entities) {
+    let dropped_items: Vec<(EntityId, glam::Vec3)> = entities
+        .iter()
+        .filter(|(_, entity)| entity.dropped_item.is_some())
+        .map(|(id, entity)| (id, entity.position))
+        .collect();
+    for (idx, &(id, position)) in dropped_items.iter().enumerate() {
+        for &(other_id, other_position) in &dropped_items[idx + 1..] {
+            if other_position.distance(position) >= MERGE_RANGE {
+                continue;
+            }
+            // Either one may have been merged into another already
+            let (item, other) = match (entities.get(id), entities.get(other_id)) {
+                (Some(entity), Some(other_entity)) => (
+                    entity.dropped_item.unwrap(),
+                    other_entity.dropped_item.unwrap(),
+                ),
+                _ => continue,
+            };
+            if other.block_type != item.block_type || other.count + item.count > MAX_STACK_SIZE {
+                continue;
+            }
+            let other_entity = entities.despawn(other_id).unwrap();
+            let entity = entities.get_mut(id).unwrap();
+            entity.dropped_item = Some(DroppedItem {
+                count: item.count + other.count,
+                ..item
+            });
+            // The merged stack stays around as long as the newer item would have
+            entity.age_secs = entity.age_secs.min(other_entity.age_secs);
+        }
+    }
+}
+
+// Moves the items that are in range of the character's cylinder into `inventory`, as much of
+// them as fits. Returns whether anything was picked up.
+pub fn pick_up(
+    entities: &mut Entities,
+    character_position: glam::Vec3,
+    character_radius: f32,
+    character_half_height: f32,
+    inventory: &mut Inventory,
+) -> bool {
+    let mut did_pick_up = false;
+    for (_, entity) in entities.iter_mut() {
+        let offset = entity.position - character_position;
+        let is_in_range = entity.age_secs >= PICKUP_DELAY_SECS
+            && glam::Vec2::new(offset.x, offset.z).length() <= character_radius + PICKUP_RANGE
+            && offset.y.abs() <= character_half_height + PICKUP_RANGE;
+        let dropped_item = match &mut entity.dropped_item {
+            Some(dropped_item) if is_in_range => dropped_item,
+            _ => continue,
+        };
+        let leftover = inventory.add(Item::Block(dropped_item.block_type), dropped_item.count);
+        did_pick_up |= leftover != dropped_item.count;
+        dropped_item.count = leftover;
+    }
+    entities.retain(|entity| entity.dropped_item.is_none_or(|item| item.count > 0));
+    did_pick_up
+}
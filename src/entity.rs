@@ -0,0 +1,453 @@
+use crate::atlas::Tile;
+use crate::combat::{self, Health, Melee};
+use crate::dropped_item::DroppedItem;
+use crate::instance::{InstanceRaw, INSTANCE_FLAG_EMISSIVE};
+use crate::mob::MobKind;
+use crate::physics::{self, Collider};
+use crate::world::{BlockType, Face, WorldState, NO_AMBIENT_OCCLUSION};
+use cgmath::Rotation3;
+
+// What the instance buffer is sized for, faces past this many aren't drawn
+pub const MAX_ENTITY_INSTANCES: usize = 4096;
+const SPIN_DEGREES_PER_SEC: f32 = 90.0;
+const BOB_HEIGHT: f32 = 0.1;
+const BOB_PERIOD_SECS: f32 = 2.5;
+
+// Refers to an entity in `Entities`. Ids aren't reused, so an id of a despawned entity never
+// refers to a newer one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntityId {
+    idx: u32,
+    generation: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Physics {
+    // In blocks per second squared, 0 for things that fly straight
+    pub gravity: f32,
+    pub terminal_velocity: f32,
+    // Fraction of horizontal velocity kept after sliding along the ground for a second
+    pub ground_friction: f32,
+}
+
+// A cuboid of an entity's model, see `Renderable::Model`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelPart {
+    // Center of the part relative to the entity's position, before turning with its yaw
+    pub offset: glam::Vec3,
+    pub size: glam::Vec3,
+    // top, bottom, sides
+    pub tiles: [Tile; 3],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Renderable {
+    // A block filling the collider's width, spinning and bobbing in place
+    SpinningBlock(BlockType),
+    // Cuboids turned with the entity's yaw
+    Model(&'static [ModelPart]),
+}
+
+// Decides what an entity does, run every tick before it moves. Mobs steer by setting their
+// velocity and can look at the rest of the world.
+pub trait Ai: std::fmt::Debug {
+    fn tick(&mut self, entity: &mut Entity, world: &WorldState, tick_secs: f32);
+
+    // Called when the entity is hurt by something at `attacker_position`
+    fn on_hit(&mut self, _entity: &mut Entity, _attacker_position: glam::Vec3) {}
+}
+
+// Anything that moves around the world on its own. Entities only have the components they need.
+#[derive(Debug)]
+pub struct Entity {
+    // Center of the collider
+    pub position: glam::Vec3,
+    // In blocks per second
+    pub velocity: glam::Vec3,
+    // Radians counterclockwise around the Y axis, looking from above
+    pub yaw: f32,
+    pub collider: Collider,
+    // `None` for entities that don't move on their own
+    pub physics: Option<Physics>,
+    pub is_on_ground: bool,
+    pub age_secs: f32,
+    // Despawns once this old
+    pub lifetime_secs: Option<f32>,
+    pub renderable: Option<Renderable>,
+    pub dropped_item: Option<DroppedItem>,
+    pub mob: Option<MobKind>,
+    // `None` for entities that can't be hurt. Dies once it runs out.
+    pub health: Option<Health>,
+    pub melee: Option<Melee>,
+    pub ai: Option<Box<dyn Ai>>,
+}
+
+impl Entity {
+    pub fn new(position: glam::Vec3, collider: Collider) -> Self {
+        Entity {
+            position,
+            velocity: glam::Vec3::ZERO,
+            yaw: 0.0,
+            collider,
+            physics: None,
+            is_on_ground: false,
+            age_secs: 0.0,
+            lifetime_secs: None,
+            renderable: None,
+            dropped_item: None,
+            mob: None,
+            health: None,
+            melee: None,
+            ai: None,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.health.is_none_or(|health| !health.is_dead())
+    }
+
+    // Bounding box of the collider, min and max corners
+    pub fn aabb(&self) -> (glam::Vec3, glam::Vec3) {
+        let half_extents = self.collider.half_extents();
+        (self.position - half_extents, self.position + half_extents)
+    }
+
+    // Falls, slides and comes to rest against the blocks that are `is_collidable`
+    fn physics_tick(&mut self, is_collidable: &impl Fn([usize; 3]) -> bool, tick_secs: f32) {
+        let physics = match self.physics {
+            Some(physics) => physics,
+            None => return,
+        };
+
+        // Pushed out the top of blocks placed on it
+        let [x, y, z] = self.position.to_array().map(|n| n.max(0.0) as usize);
+        if is_collidable([x, y, z]) {
+            self.position.y = y as f32 + 1.0 + self.collider.half_extents().y;
+            self.velocity = glam::Vec3::ZERO;
+            return;
+        }
+
+        self.is_on_ground = physics::is_on_floor(is_collidable, &self.collider, self.position);
+        if self.is_on_ground {
+            self.velocity.y = self.velocity.y.max(0.0);
+            let friction = physics.ground_friction.powf(tick_secs);
+            self.velocity.x *= friction;
+            self.velocity.z *= friction;
+        } else {
+            self.velocity.y =
+                (self.velocity.y - physics.gravity * tick_secs).max(-physics.terminal_velocity);
+        }
+
+        let move_result = physics::move_and_collide(
+            is_collidable,
+            &self.collider,
+            self.position,
+            self.velocity * tick_secs,
+        );
+        self.position = move_result.position;
+        for (axis, is_blocked) in move_result.blocked.into_iter().enumerate() {
+            if is_blocked {
+                self.velocity[axis] = 0.0;
+            }
+        }
+    }
+
+    fn instances(&self) -> Vec<InstanceRaw> {
+        let color_adjust = if self.health.is_some_and(|health| health.is_hurt()) {
+            combat::HURT_COLOR_ADJUST
+        } else {
+            [1.0, 1.0, 1.0, 1.0]
+        };
+        let center = cgmath::Vector3::new(self.position.x, self.position.y, self.position.z);
+        match self.renderable {
+            Some(Renderable::SpinningBlock(block_type)) => {
+                let spin = cgmath::Quaternion::from_angle_y(cgmath::Deg(
+                    self.age_secs * SPIN_DEGREES_PER_SEC,
+                ));
+                // Bobs above its resting position, so it never sinks into the ground
+                let bob = (self.age_secs / BOB_PERIOD_SECS * std::f32::consts::TAU).sin();
+                let center = center + cgmath::Vector3::unit_y() * BOB_HEIGHT * (bob + 1.0) / 2.0;
+                spinning_block_instances(
+                    block_type,
+                    center,
+                    self.collider.half_extents().x * 2.0,
+                    spin,
+                )
+            }
+            Some(Renderable::Model(parts)) => {
+                let rotation = cgmath::Quaternion::from_angle_y(cgmath::Rad(self.yaw));
+                parts
+                    .iter()
+                    .flat_map(|part| {
+                        let offset =
+                            cgmath::Vector3::new(part.offset.x, part.offset.y, part.offset.z);
+                        cuboid_instances(
+                            center + rotation * offset,
+                            cgmath::Vector3::new(part.size.x, part.size.y, part.size.z),
+                            rotation,
+                            part.tiles.map(Tile::atlas_offset),
+                            color_adjust,
+                            0,
+                        )
+                    })
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
+}
+
+// The faces of a cuboid of `size` blocks, centered on `center` and turned by `rotation`. Faces
+// show as much of their tile as they're wide.
+pub fn cuboid_instances(
+    center: cgmath::Vector3<f32>,
+    size: cgmath::Vector3<f32>,
+    rotation: cgmath::Quaternion<f32>,
+    [top_offset, bottom_offset, side_offset]: [[f32; 2]; 3],
+    color_adjust: [f32; 4],
+    flags: u32,
+) -> Vec<InstanceRaw> {
+    Face::ALL
+        .into_iter()
+        .map(|face| {
+            // The face quad spans its local X and Z axes, which line up with two of the axes
+            // of the cuboid
+            let face_rotation = face.rotation();
+            let extent_along = |axis: cgmath::Vector3<f32>| {
+                let axis = face_rotation * axis;
+                axis.x.abs() * size.x + axis.y.abs() * size.y + axis.z.abs() * size.z
+            };
+            let scale = [
+                extent_along(cgmath::Vector3::unit_x()),
+                extent_along(cgmath::Vector3::unit_z()),
+            ];
+            let corner = face.instance_offset() - cgmath::Vector3::new(0.5, 0.5, 0.5);
+            let corner =
+                cgmath::Vector3::new(corner.x * size.x, corner.y * size.y, corner.z * size.z);
+            let texture_atlas_offset = match face {
+                Face::Top => top_offset,
+                Face::Bottom => bottom_offset,
+                _ => side_offset,
+            };
+            InstanceRaw::new(
+                center + rotation * corner,
+                rotation * face_rotation,
+                texture_atlas_offset,
+                color_adjust,
+                flags,
+                NO_AMBIENT_OCCLUSION,
+            )
+            .with_scale(scale)
+        })
+        .collect()
+}
+
+// A block `size` blocks wide, sprites like flowers and torches as a flat card through the middle
+fn spinning_block_instances(
+    block_type: BlockType,
+    center: cgmath::Vector3<f32>,
+    size: f32,
+    spin: cgmath::Quaternion<f32>,
+) -> Vec<InstanceRaw> {
+    let flags = if block_type.is_emissive() {
+        INSTANCE_FLAG_EMISSIVE
+    } else {
+        0
+    };
+    if !block_type.is_sprite() {
+        return cuboid_instances(
+            center,
+            cgmath::Vector3::new(size, size, size),
+            spin,
+            block_type.texture_atlas_offsets(),
+            [1.0, 1.0, 1.0, 1.0],
+            flags,
+        );
+    }
+    // Front and back faces, moved into the middle
+    [Face::Front, Face::Back]
+        .into_iter()
+        .map(|face| {
+            let mut corner = face.instance_offset() - cgmath::Vector3::new(0.5, 0.5, 0.5);
+            corner.z = 0.0;
+            InstanceRaw::new(
+                center + spin * (corner * size),
+                spin * face.rotation(),
+                block_type.texture_atlas_offsets()[2],
+                [1.0, 1.0, 1.0, 1.0],
+                flags,
+                NO_AMBIENT_OCCLUSION,
+            )
+            .with_scale([size, size])
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct EntitySlot {
+    generation: u32,
+    entity: Option<Entity>,
+}
+
+// Every entity in the world, in slots that are reused once their entity despawns
+#[derive(Debug, Default)]
+pub struct Entities {
+    slots: Vec<EntitySlot>,
+    free_idxs: Vec<u32>,
+}
+
+impl Entities {
+    pub fn spawn(&mut self, entity: Entity) -> EntityId {
+        match self.free_idxs.pop() {
+            Some(idx) => {
+                let slot = &mut self.slots[idx as usize];
+                slot.generation += 1;
+                slot.entity = Some(entity);
+                EntityId {
+                    idx,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(EntitySlot {
+                    generation: 0,
+                    entity: Some(entity),
+                });
+                EntityId {
+                    idx: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn despawn(&mut self, id: EntityId) -> Option<Entity> {
+        let slot = self.slots.get_mut(id.idx as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        let entity = slot.entity.take()?;
+        self.free_idxs.push(id.idx);
+        Some(entity)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        let slot = self.slots.get(id.idx as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.entity.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        let slot = self.slots.get_mut(id.idx as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.entity.as_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free_idxs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.slots.iter().enumerate().filter_map(|(idx, slot)| {
+            let id = EntityId {
+                idx: idx as u32,
+                generation: slot.generation,
+            };
+            Some((id, slot.entity.as_ref()?))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut Entity)> {
+        self.slots.iter_mut().enumerate().filter_map(|(idx, slot)| {
+            let id = EntityId {
+                idx: idx as u32,
+                generation: slot.generation,
+            };
+            Some((id, slot.entity.as_mut()?))
+        })
+    }
+
+    // Despawns the entities that `keep` returns false for
+    pub fn retain(&mut self, mut keep: impl FnMut(&Entity) -> bool) {
+        let ids: Vec<EntityId> = self
+            .iter()
+            .filter(|(_, entity)| !keep(entity))
+            .map(|(id, _)| id)
+            .collect();
+        for id in ids {
+            self.despawn(id);
+        }
+    }
+
+    // Lets every living entity's AI decide what to do this tick
+    pub fn ai_tick(&mut self, world: &WorldState, tick_secs: f32) {
+        for (_, entity) in self.iter_mut().filter(|(_, entity)| entity.is_alive()) {
+            if let Some(mut ai) = entity.ai.take() {
+                ai.tick(entity, world, tick_secs);
+                entity.ai = Some(ai);
+            }
+        }
+    }
+
+    // Hurts the entity for `damage` points and knocks it back away from `attacker_position`.
+    // Returns whether it was hurt, entities without health can't be and ones that were just hurt
+    // can't be again yet.
+    pub fn hit(&mut self, id: EntityId, attacker_position: glam::Vec3, damage: f32) -> bool {
+        let Some(entity) = self.get_mut(id) else {
+            return false;
+        };
+        if !entity
+            .health
+            .as_mut()
+            .is_some_and(|health| health.damage(damage))
+        {
+            return false;
+        }
+        entity.velocity = combat::knockback_velocity(attacker_position, entity.position);
+        if let Some(mut ai) = entity.ai.take() {
+            ai.on_hit(entity, attacker_position);
+            entity.ai = Some(ai);
+        }
+        true
+    }
+
+    // Ages and moves every entity for one fixed tick of `tick_secs`, against the blocks that are
+    // `is_collidable`. Despawns the ones past their lifetime, fallen out of the world or dead.
+    // Dead entities stay around until they stop flashing red.
+    pub fn physics_tick(&mut self, is_collidable: impl Fn([usize; 3]) -> bool, tick_secs: f32) {
+        for (_, entity) in self.iter_mut() {
+            entity.age_secs += tick_secs;
+            if let Some(health) = &mut entity.health {
+                health.tick(tick_secs);
+            }
+            entity.physics_tick(&is_collidable, tick_secs);
+        }
+        self.retain(|entity| {
+            entity
+                .lifetime_secs
+                .is_none_or(|lifetime_secs| entity.age_secs < lifetime_secs)
+                && entity.position.y > 0.0
+                && entity
+                    .health
+                    .is_none_or(|health| !health.is_dead() || health.is_hurt())
+        });
+    }
+
+    // Faces of every entity's model, drawn instanced like the blocks
+    pub fn instances(&self) -> Vec<InstanceRaw> {
+        let mut instances: Vec<InstanceRaw> = self
+            .iter()
+            .flat_map(|(_, entity)| entity.instances())
+            .collect();
+        instances.truncate(MAX_ENTITY_INSTANCES);
+        instances
+    }
+}
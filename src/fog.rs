@@ -0,0 +1,57 @@
+use crate::camera::Camera;
+use crate::world_clock::WorldClock;
+use glam::Vec3;
+
+// Fraction of the view distance where the fog starts, so terrain fades out before it ends
+const FOG_START: f32 = 0.9;
+const UNDERWATER_FOG_DENSITY: f32 = 0.08;
+// Linear RGB
+const UNDERWATER_FOG_COLOR: [f32; 3] = [0.02, 0.05, 0.2];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FogMode {
+    // Fades in between `start` and `end`
+    Linear { start: f32, end: f32 },
+    // Thickens with distance without ever fully hiding things. Distances are in blocks.
+    Exponential { density: f32 },
+}
+
+// Blends things into `color` by their distance from the camera, see `fog_amount` in shader.wgsl
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub mode: FogMode,
+    // Linear RGB
+    pub color: Vec3,
+}
+
+impl Fog {
+    // Hides the edge of the view distance in the sky's horizon color, or closes in with murky blue
+    // under water
+    pub fn for_view(camera: &Camera, world_clock: &WorldClock, is_underwater: bool) -> Self {
+        if is_underwater {
+            return Fog {
+                mode: FogMode::Exponential {
+                    density: UNDERWATER_FOG_DENSITY,
+                },
+                color: UNDERWATER_FOG_COLOR.into(),
+            };
+        }
+
+        let [r, g, b] = world_clock.sky_color();
+        Fog {
+            mode: FogMode::Linear {
+                start: camera.zfar * FOG_START,
+                end: camera.zfar,
+            },
+            color: Vec3::new(r as f32, g as f32, b as f32),
+        }
+    }
+
+    // x: linear start, y: linear end, z: exponential density, 0 for linear fog
+    pub fn params(&self) -> [f32; 4] {
+        match self.mode {
+            FogMode::Linear { start, end } => [start, end, 0.0, 0.0],
+            FogMode::Exponential { density } => [0.0, 0.0, density, 0.0],
+        }
+    }
+}
@@ -0,0 +1,378 @@
+// Times frames over a fixed camera path, once with greedy meshing and once without, see
+// `WorldState::greedy_meshing`. Start it with `--time-frames` on native or `?timeFrames` on the
+// web, the results are logged. `cargo bench --bench meshing` does the same offscreen.
+//
+// The camera turns once in place above where it is, so no chunks come into view while timing and
+// every frame draws terrain that's already meshed. The clock stands still, so both settings draw
+// the same sky and shadows.
+use crate::camera::{Camera, CameraUpdateResult};
+use crate::world::{BlockType, CHUNK_XZ_SIZE, CHUNK_Y_SIZE};
+use crate::{Game, State};
+use cgmath::{InnerSpace, Point3, Vector3};
+use std::fmt::Write;
+
+// Frames drawn before timing starts, e.g. while the driver compiles shaders
+const NUM_WARMUP_FRAMES: usize = 30;
+// One full turn of the camera
+pub const NUM_TIMED_FRAMES: usize = 360;
+// Above the highest block under the camera
+const CAMERA_HEIGHT: f32 = 24.0;
+// Below the horizon
+const CAMERA_PITCH_DEGREES: f32 = 20.0;
+// Noon, so there are shadows
+const TIME_OF_DAY: f32 = 0.5;
+
+// In milliseconds
+#[derive(Debug, Default)]
+pub struct FrameTimes {
+    // Spent in `Game::render_frame`. Natively that includes waiting for the GPU to finish the
+    // frame. WebGL can't wait for it, there it's the time to issue the draw calls.
+    pub render: Vec<f64>,
+    // Between the starts of consecutive frames. In a window and on the web frames don't come
+    // faster than the display refreshes.
+    pub interval: Vec<f64>,
+}
+
+fn mean(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+fn percentile(samples: &[f64], fraction: f64) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted[((sorted.len() - 1) as f64 * fraction).round() as usize]
+}
+
+fn on_or_off(greedy_meshing: bool) -> &'static str {
+    if greedy_meshing {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+// One row per greedy meshing setting
+pub fn report(results: &[(bool, FrameTimes)]) -> String {
+    let mut report = format!(
+        "{:<16} {:>12} {:>12} {:>14} {:>14}\n",
+        "greedy meshing", "render ms", "render p95", "interval ms", "interval p95"
+    );
+    for (greedy_meshing, frame_times) in results {
+        writeln!(
+            report,
+            "{:<16} {:>12.2} {:>12.2} {:>14.2} {:>14.2}",
+            on_or_off(*greedy_meshing),
+            mean(&frame_times.render),
+            percentile(&frame_times.render, 0.95),
+            mean(&frame_times.interval),
+            percentile(&frame_times.interval, 0.95),
+        )
+        .unwrap();
+    }
+    report
+}
+
+enum Phase {
+    // Until the visible chunks are meshed with the current setting
+    Meshing,
+    Warmup {
+        frames_left: usize,
+    },
+    Timing {
+        frame_idx: usize,
+        frame_times: FrameTimes,
+    },
+}
+
+pub struct FrameTimer {
+    eye: Point3<f32>,
+    // Settings left to time, the current one last
+    greedy_meshing: Vec<bool>,
+    phase: Phase,
+    last_render_start: Option<instant::Instant>,
+    // Restored once done
+    initial_greedy_meshing: bool,
+    results: Vec<(bool, FrameTimes)>,
+}
+
+impl FrameTimer {
+    // Sets the camera for the current frame, instead of the camera controller
+    pub fn move_camera(&self, camera: &mut Camera) -> CameraUpdateResult {
+        let frame_idx = match &self.phase {
+            Phase::Timing { frame_idx, .. } => *frame_idx,
+            _ => 0,
+        };
+        let yaw = (frame_idx as f32 / NUM_TIMED_FRAMES as f32 * 360.0).to_radians();
+        let pitch = CAMERA_PITCH_DEGREES.to_radians();
+        let forward = Vector3::new(
+            yaw.cos() * pitch.cos(),
+            -pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        );
+
+        let block_location =
+            |eye: Point3<f32>| Point3::new(eye.x as usize, eye.y as usize, eye.z as usize);
+        let old_block_location = block_location(camera.eye);
+        camera.eye = self.eye;
+        camera.target = self.eye + forward.normalize();
+        camera.update_frustum();
+        let new_block_location = block_location(camera.eye);
+
+        let chunk_location =
+            |block: Point3<usize>| [block.x / CHUNK_XZ_SIZE, block.z / CHUNK_XZ_SIZE];
+        let old_chunk_location = chunk_location(old_block_location);
+        let new_chunk_location = chunk_location(new_block_location);
+        CameraUpdateResult {
+            did_move: true,
+            did_translate: true,
+            did_move_blocks: old_block_location != new_block_location,
+            did_move_chunks: old_chunk_location != new_chunk_location,
+            new_block_location,
+            old_chunk_location,
+            new_chunk_location,
+        }
+    }
+}
+
+// Remeshes every visible chunk in the background
+fn set_greedy_meshing(state: &mut State, chunk_order: &[[usize; 2]], greedy_meshing: bool) {
+    if state.world_state.greedy_meshing == greedy_meshing {
+        return;
+    }
+    state.world_state.greedy_meshing = greedy_meshing;
+    for &chunk_idx in chunk_order {
+        state.chunk_jobs.request_mesh(chunk_idx);
+    }
+}
+
+impl Game {
+    // Takes over the camera until both settings are timed
+    pub(crate) fn start_timing_frames(&mut self) {
+        if self.frame_timer.is_some() {
+            return;
+        }
+        let state = &mut self.state;
+        state.world_state.world_clock.set_time_of_day(TIME_OF_DAY);
+        // Stay in the same block column, and so in the same chunk
+        let [x, z] = [state.camera.eye.x as usize, state.camera.eye.z as usize];
+        let ground_y = (0..CHUNK_Y_SIZE)
+            .rev()
+            .find(|&y| state.world_state.block_type_at([x, y, z]) != BlockType::Empty)
+            .unwrap_or(0);
+        let eye = Point3::new(
+            x as f32 + 0.5,
+            ground_y as f32 + CAMERA_HEIGHT,
+            z as f32 + 0.5,
+        );
+
+        let initial_greedy_meshing = state.world_state.greedy_meshing;
+        self.frame_timer = Some(FrameTimer {
+            eye,
+            greedy_meshing: vec![false, true],
+            phase: Phase::Meshing,
+            last_render_start: None,
+            initial_greedy_meshing,
+            results: vec![],
+        });
+        set_greedy_meshing(state, &self.scene.chunk_order, true);
+    }
+
+    // Call after presenting each frame, with the time `render_frame` was called. Returns the
+    // results once every setting is timed.
+    pub(crate) fn frame_rendered(
+        &mut self,
+        render_start: instant::Instant,
+    ) -> Option<Vec<(bool, FrameTimes)>> {
+        let frame_timer = self.frame_timer.as_mut()?;
+        #[cfg(not(target_arch = "wasm32"))]
+        self.state.device.poll(wgpu::Maintain::Wait);
+        let render_ms = render_start.elapsed().as_secs_f64() * 1000.0;
+        let interval_ms = frame_timer
+            .last_render_start
+            .replace(render_start)
+            .map(|last_render_start| (render_start - last_render_start).as_secs_f64() * 1000.0);
+
+        match &mut frame_timer.phase {
+            Phase::Meshing => {
+                if self.state.chunk_jobs.is_idle() {
+                    frame_timer.phase = Phase::Warmup {
+                        frames_left: NUM_WARMUP_FRAMES,
+                    };
+                }
+                return None;
+            }
+            Phase::Warmup { frames_left } => {
+                *frames_left -= 1;
+                if *frames_left == 0 {
+                    log::info!(
+                        "Timing {} frames with greedy meshing {}",
+                        NUM_TIMED_FRAMES,
+                        on_or_off(*frame_timer.greedy_meshing.last().unwrap())
+                    );
+                    frame_timer.phase = Phase::Timing {
+                        frame_idx: 0,
+                        frame_times: FrameTimes::default(),
+                    };
+                }
+                return None;
+            }
+            Phase::Timing {
+                frame_idx,
+                frame_times,
+            } => {
+                frame_times.render.push(render_ms);
+                frame_times.interval.extend(interval_ms);
+                *frame_idx += 1;
+                if *frame_idx < NUM_TIMED_FRAMES {
+                    return None;
+                }
+
+                let greedy_meshing = frame_timer.greedy_meshing.pop().unwrap();
+                frame_timer
+                    .results
+                    .push((greedy_meshing, std::mem::take(frame_times)));
+            }
+        }
+
+        // Next setting
+        if let Some(&greedy_meshing) = frame_timer.greedy_meshing.last() {
+            frame_timer.phase = Phase::Meshing;
+            set_greedy_meshing(&mut self.state, &self.scene.chunk_order, greedy_meshing);
+            return None;
+        }
+
+        let frame_timer = self.frame_timer.take().unwrap();
+        log::info!("Frame times:\n{}", report(&frame_timer.results));
+        set_greedy_meshing(
+            &mut self.state,
+            &self.scene.chunk_order,
+            frame_timer.initial_greedy_meshing,
+        );
+        Some(frame_timer.results)
+    }
+}
+
+// Renders offscreen at the given size, with the resource pack and render distance the game would
+// start with
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn time_frames_headless(width: u32, height: u32) -> Vec<(bool, FrameTimes)> {
+    let mut game = Game::new_headless(winit::dpi::PhysicalSize::new(width, height)).await;
+    let texture = game.state.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("frame_timing_texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: game.state.surface_config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let spawner = crate::spawner::Spawner::new();
+    let mut game_loop: crate::game_loop::GameLoop =
+        crate::game_loop::GameLoop::new(crate::UPDATES_PER_SECOND, crate::MAX_FRAME_TIME);
+    game.start_timing_frames();
+    loop {
+        let game_cell = std::cell::RefCell::new(&mut game);
+        let mut results = None;
+        game_loop.next_frame(
+            |g| {
+                game_cell
+                    .borrow_mut()
+                    .update_tick(g, false, &mut false, &mut false);
+            },
+            |_| {
+                let mut game = game_cell.borrow_mut();
+                let render_start = instant::Instant::now();
+                game.render_to(&view, &spawner);
+                results = game.frame_rendered(render_start);
+            },
+        );
+        spawner.run_until_stalled();
+        if let Some(results) = results {
+            return results;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_timer_at(eye: Point3<f32>, frame_idx: usize) -> FrameTimer {
+        FrameTimer {
+            eye,
+            greedy_meshing: vec![true],
+            phase: Phase::Timing {
+                frame_idx,
+                frame_times: FrameTimes::default(),
+            },
+            last_render_start: None,
+            initial_greedy_meshing: true,
+            results: vec![],
+        }
+    }
+
+    #[test]
+    fn camera_turns_in_place() {
+        let eye = Point3::new(100.5, 80.5, 200.5);
+        let mut camera = Camera::new(
+            eye,
+            (0.0, 0.0, 0.0).into(),
+            Vector3::unit_y(),
+            Vector3::unit_y(),
+            16.0 / 9.0,
+            70.0,
+            0.1,
+            250.0,
+        );
+
+        let mut forwards = vec![];
+        for frame_idx in 0..NUM_TIMED_FRAMES {
+            let update_result = frame_timer_at(eye, frame_idx).move_camera(&mut camera);
+            assert!(!update_result.did_move_blocks && !update_result.did_move_chunks);
+            assert_eq!(camera.eye, eye);
+            forwards.push(camera.forward_normal());
+        }
+
+        // Half way through it looks the other way, at the same angle below the horizon
+        let [first, half_way] = [forwards[0], forwards[NUM_TIMED_FRAMES / 2]];
+        assert!((first.x + half_way.x).abs() < 1e-4 && (first.z + half_way.z).abs() < 1e-4);
+        assert!(first.y < 0.0 && (first.y - half_way.y).abs() < 1e-4);
+    }
+
+    #[test]
+    fn report_has_a_row_per_setting() {
+        let frame_times = |samples: &[f64]| FrameTimes {
+            render: samples.to_vec(),
+            interval: samples.to_vec(),
+        };
+        let results = [
+            (true, frame_times(&[3.0, 1.0, 10.0, 2.0])),
+            (false, frame_times(&[])),
+        ];
+
+        let report = report(&results);
+        let rows = report.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+        // Mean, then the slowest of the four frames as the 95th percentile
+        assert_eq!(
+            rows[1].split_whitespace().collect::<Vec<_>>(),
+            ["on", "4.00", "10.00", "4.00", "10.00"]
+        );
+        assert_eq!(
+            rows[2].split_whitespace().collect::<Vec<_>>(),
+            ["off", "0.00", "0.00", "0.00", "0.00"]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use super::instance::InstanceRaw;
+use cgmath::{Quaternion, Rotation, Vector3};
+
+// Merges coplanar faces that look the same into larger faces, to cut down on the number of
+// instances we need to draw. One mesher handles the faces of one direction (e.g. all top faces)
+// of a chunk.
+//
+// https://0fps.net/2012/06/30/meshing-in-a-minecraft-game/
+pub struct GreedyMesher {
+    dims: [usize; 3],
+    normal_axis: usize,
+    // Block axes along the face's local x and z edges, and whether they point in the positive direction
+    tiling_axes: [(usize, bool); 2],
+    faces: Vec<([usize; 3], InstanceRaw)>,
+    // Scratch space for a single slice, holds indices into `faces`. Reused between slices.
+    slice: Vec<u32>,
+}
+
+const NO_FACE: u32 = u32::MAX;
+
+impl GreedyMesher {
+    // `rotation` must be the rotation used for the faces' instances, `dims` the size of the chunk
+    pub fn new(dims: [usize; 3], rotation: Quaternion<f32>) -> Self {
+        let axis_of = |v: Vector3<f32>| {
+            let v: [f32; 3] = v.into();
+            let axis = (0..3).find(|&i| v[i].abs() > 0.5).unwrap();
+            (axis, v[axis] > 0.0)
+        };
+        let (normal_axis, _) = axis_of(rotation.rotate_vector(Vector3::new(0.0, 1.0, 0.0)));
+        let tiling_axes = [
+            axis_of(rotation.rotate_vector(Vector3::new(1.0, 0.0, 0.0))),
+            axis_of(rotation.rotate_vector(Vector3::new(0.0, 0.0, 1.0))),
+        ];
+
+        Self {
+            dims,
+            normal_axis,
+            tiling_axes,
+            faces: vec![],
+            slice: vec![NO_FACE; dims[tiling_axes[0].0] * dims[tiling_axes[1].0]],
+        }
+    }
+
+    // `chunk_rel_pos` is the position of the block the face belongs to within the chunk
+    pub fn add_face(&mut self, chunk_rel_pos: [usize; 3], instance: InstanceRaw) {
+        self.faces.push((chunk_rel_pos, instance));
+    }
+
+    // Consumes all faces added so far, pushing the merged faces into `instances`
+    pub fn mesh(&mut self, instances: &mut Vec<InstanceRaw>) {
+        let [(u_axis, u_positive), (v_axis, v_positive)] = self.tiling_axes;
+        let (u_size, v_size) = (self.dims[u_axis], self.dims[v_axis]);
+        let normal_axis = self.normal_axis;
+
+        // Position of a face in its slice, measured along the face's local edges
+        let slice_coords = |pos: [usize; 3]| {
+            let u = if u_positive {
+                pos[u_axis]
+            } else {
+                u_size - 1 - pos[u_axis]
+            };
+            let v = if v_positive {
+                pos[v_axis]
+            } else {
+                v_size - 1 - pos[v_axis]
+            };
+            (u, v)
+        };
+
+        self.faces.sort_unstable_by_key(|(pos, _)| pos[normal_axis]);
+
+        let mut slice_start = 0;
+        while slice_start < self.faces.len() {
+            let slice_idx = self.faces[slice_start].0[normal_axis];
+            let slice_end = self.faces[slice_start..]
+                .iter()
+                .position(|(pos, _)| pos[normal_axis] != slice_idx)
+                .map_or(self.faces.len(), |len| slice_start + len);
+
+            // Only scan the part of the slice that has faces, chunks are mostly empty or solid
+            let (mut min_v, mut max_v) = (v_size, 0);
+            for (face_idx, &(pos, _)) in self
+                .faces
+                .iter()
+                .enumerate()
+                .take(slice_end)
+                .skip(slice_start)
+            {
+                let (u, v) = slice_coords(pos);
+                self.slice[v * u_size + u] = face_idx as u32;
+                min_v = min_v.min(v);
+                max_v = max_v.max(v);
+            }
+
+            let faces = &self.faces;
+            let slice = &mut self.slice;
+            let can_merge = |slice: &[u32], u: usize, v: usize, with: &InstanceRaw| {
+                let face_idx = slice[v * u_size + u];
+                face_idx != NO_FACE && faces[face_idx as usize].1.can_merge_with(with)
+            };
+
+            for v in min_v..=max_v {
+                for u in 0..u_size {
+                    let origin = match slice[v * u_size + u] {
+                        NO_FACE => continue,
+                        face_idx => faces[face_idx as usize].1,
+                    };
+
+                    let mut width = 1;
+                    while u + width < u_size && can_merge(slice, u + width, v, &origin) {
+                        width += 1;
+                    }
+                    let mut height = 1;
+                    while v + height <= max_v
+                        && (u..u + width).all(|u| can_merge(slice, u, v + height, &origin))
+                    {
+                        height += 1;
+                    }
+
+                    for merged_v in v..v + height {
+                        slice[merged_v * u_size + u..merged_v * u_size + u + width].fill(NO_FACE);
+                    }
+
+                    // The face at the origin spans one grid cell along its local edges, so
+                    // scaling it covers all of the merged faces
+                    instances.push(origin.scaled_by([width as f32, height as f32]));
+                }
+            }
+
+            slice_start = slice_end;
+        }
+
+        self.faces.clear();
+    }
+
+    // Like `mesh`, but pushes every face on its own
+    pub fn mesh_unmerged(&mut self, instances: &mut Vec<InstanceRaw>) {
+        instances.extend(self.faces.drain(..).map(|(_, instance)| instance));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{Face, NO_AMBIENT_OCCLUSION};
+    use cgmath::MetricSpace;
+
+    const DIMS: [usize; 3] = [16, 4, 16];
+    const DIRT: [f32; 2] = [0.0, 0.0];
+    const STONE: [f32; 2] = [0.25, 0.0];
+
+    fn top_face([x, y, z]: [usize; 3], tile: [f32; 2], ambient_occlusion: [f32; 4]) -> InstanceRaw {
+        InstanceRaw::new(
+            Vector3::new(x as f32, y as f32, z as f32) + Face::Top.instance_offset(),
+            Face::Top.rotation(),
+            tile,
+            [1.0, 1.0, 1.0, 1.0],
+            0,
+            ambient_occlusion,
+        )
+    }
+
+    fn mesh(faces: impl IntoIterator<Item = ([usize; 3], InstanceRaw)>) -> Vec<InstanceRaw> {
+        let mut mesher = GreedyMesher::new(DIMS, Face::Top.rotation());
+        for (pos, face) in faces {
+            mesher.add_face(pos, face);
+        }
+        let mut instances = vec![];
+        mesher.mesh(&mut instances);
+        instances
+    }
+
+    // Top faces of a whole 16x16 layer of blocks
+    fn mesh_layer(face_at: impl Fn([usize; 3]) -> InstanceRaw) -> Vec<InstanceRaw> {
+        mesh(iproduct!(0..DIMS[2], 0..DIMS[0]).map(|(z, x)| ([x, 0, z], face_at([x, 0, z]))))
+    }
+
+    #[test]
+    fn full_slice_merges_into_one_face() {
+        let instances = mesh_layer(|pos| top_face(pos, DIRT, NO_AMBIENT_OCCLUSION));
+        assert_eq!(instances.len(), 1);
+        assert!(
+            instances[0]
+                .center()
+                .distance(cgmath::Point3::new(8.0, 1.0, 8.0))
+                < 1e-4
+        );
+    }
+
+    #[test]
+    fn faces_with_different_tiles_dont_merge() {
+        let checkerboard = mesh_layer(|pos @ [x, _, z]| {
+            let tile = if (x + z) % 2 == 0 { DIRT } else { STONE };
+            top_face(pos, tile, NO_AMBIENT_OCCLUSION)
+        });
+        assert_eq!(checkerboard.len(), 16 * 16);
+
+        let halves = mesh_layer(|pos @ [x, _, _]| {
+            let tile = if x < 8 { DIRT } else { STONE };
+            top_face(pos, tile, NO_AMBIENT_OCCLUSION)
+        });
+        assert_eq!(halves.len(), 2);
+    }
+
+    #[test]
+    fn faces_with_different_ambient_occlusion_dont_merge() {
+        let shaded = [0.45, 1.0, 1.0, 1.0];
+        let instances = mesh_layer(|pos| {
+            let ambient_occlusion = if pos == [5, 0, 5] {
+                shaded
+            } else {
+                NO_AMBIENT_OCCLUSION
+            };
+            top_face(pos, DIRT, ambient_occlusion)
+        });
+        assert!(instances.len() > 1);
+        // The shaded face is left on its own
+        let shaded_face = top_face([5, 0, 5], DIRT, shaded);
+        let shaded_instances = instances
+            .iter()
+            .filter(|instance| instance.can_merge_with(&shaded_face))
+            .collect::<Vec<_>>();
+        assert_eq!(shaded_instances.len(), 1);
+        assert!(shaded_instances[0].center().distance(shaded_face.center()) < 1e-4);
+    }
+
+    #[test]
+    fn faces_in_different_slices_dont_merge() {
+        let instances =
+            mesh((0..2).map(|y| ([0, y, 0], top_face([0, y, 0], DIRT, NO_AMBIENT_OCCLUSION))));
+        assert_eq!(instances.len(), 2);
+    }
+}
@@ -1,3 +1,8 @@
+// Bits for `InstanceRaw::flags`, must match the constants in the shaders
+pub const INSTANCE_FLAG_EMISSIVE: u32 = 1 << 0;
+// Top of a body of water, animated and see-through depending on the view angle
+pub const INSTANCE_FLAG_WATER_SURFACE: u32 = 1 << 1;
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
@@ -5,6 +10,11 @@ pub struct InstanceRaw {
     rotation: [f32; 4],
     texture_atlas_offset: [f32; 2],
     color_adjust: [f32; 4],
+    flags: u32,
+    // Brightness at each corner of the face, see world::FaceCorners
+    ambient_occlusion: [f32; 4],
+    // Size of the face in blocks along its two edges, the texture is tiled across it
+    scale: [f32; 2],
 }
 
 impl InstanceRaw {
@@ -13,15 +23,57 @@ impl InstanceRaw {
         rotation: cgmath::Quaternion<f32>,
         texture_atlas_offset: [f32; 2],
         color_adjust: [f32; 4],
+        flags: u32,
+        ambient_occlusion: [f32; 4],
     ) -> Self {
         InstanceRaw {
             position: [position.x, position.y, position.z, 1.0],
             rotation: [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s],
-            texture_atlas_offset: texture_atlas_offset,
-            color_adjust: color_adjust,
+            texture_atlas_offset,
+            color_adjust,
+            flags,
+            ambient_occlusion,
+            scale: [1.0, 1.0],
         }
     }
 
+    pub fn with_scale(mut self, scale: [f32; 2]) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    // Stretches the face on top of its current scale
+    pub fn scaled_by(mut self, [scale_x, scale_z]: [f32; 2]) -> Self {
+        self.scale = [self.scale[0] * scale_x, self.scale[1] * scale_z];
+        self
+    }
+
+    // Whether two faces look identical apart from their position, i.e. they can be merged
+    // into a single larger face
+    pub fn can_merge_with(&self, other: &InstanceRaw) -> bool {
+        self.rotation == other.rotation
+            && self.texture_atlas_offset == other.texture_atlas_offset
+            && self.color_adjust == other.color_adjust
+            && self.flags == other.flags
+            && self.ambient_occlusion == other.ambient_occlusion
+            && self.scale == other.scale
+    }
+
+    // Middle of the face, the face quad spans [0, scale] in x and z before it's rotated
+    pub fn center(&self) -> cgmath::Point3<f32> {
+        use cgmath::Rotation;
+
+        let [x, y, z, w] = self.rotation;
+        let rotation = cgmath::Quaternion::new(w, x, y, z);
+        let [position_x, position_y, position_z, _] = self.position;
+        cgmath::Point3::new(position_x, position_y, position_z)
+            + rotation.rotate_vector(cgmath::Vector3::new(
+                self.scale[0] / 2.0,
+                0.0,
+                self.scale[1] / 2.0,
+            ))
+    }
+
     pub fn size() -> usize {
         use std::mem;
         mem::size_of::<InstanceRaw>()
@@ -56,7 +108,32 @@ impl InstanceRaw {
                     shader_location: 10,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 15]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 19]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
             ],
         }
     }
 }
+
+// Farthest from `eye` first, so translucent faces blend over the faces behind them
+pub fn sort_back_to_front(instances: &mut [InstanceRaw], eye: cgmath::Point3<f32>) {
+    use cgmath::MetricSpace;
+
+    // Bits of non-negative floats sort the same way as the floats themselves
+    instances.sort_by_cached_key(|instance| {
+        std::cmp::Reverse(instance.center().distance2(eye).to_bits())
+    });
+}
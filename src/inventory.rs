@@ -0,0 +1,231 @@
+use crate::item::Item;
+use crate::world::BlockType;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+pub const HOTBAR_SLOT_COUNT: usize = 9;
+// The hotbar is the first row of slots, the rest are only filled up once it's full
+pub const SLOT_COUNT: usize = HOTBAR_SLOT_COUNT * 4;
+// Of everything but tools, see `Item::max_stack_size`
+pub const MAX_STACK_SIZE: u32 = 64;
+// Native builds save the inventory in this file, relative to the working directory. The web build
+// saves it in the page's localStorage instead, see ts/index.ts.
+#[cfg(not(target_arch = "wasm32"))]
+pub const INVENTORY_SAVE_PATH: &str = "inventory.json";
+
+// Light sources can't be found in the world, so they're handed out at the start
+const STARTING_ITEMS: [ItemStack; 3] = [
+    ItemStack {
+        item: Item::Block(BlockType::Torch),
+        count: 64,
+    },
+    ItemStack {
+        item: Item::Block(BlockType::Glowstone),
+        count: 16,
+    },
+    ItemStack {
+        item: Item::Block(BlockType::Lantern),
+        count: 16,
+    },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ItemStack {
+    pub item: Item,
+    // Never 0, emptied slots are `None`
+    pub count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inventory {
+    slots: [Option<ItemStack>; SLOT_COUNT],
+    selected_hotbar_slot: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        let mut inventory = Inventory {
+            slots: [None; SLOT_COUNT],
+            selected_hotbar_slot: 0,
+        };
+        for item_stack in STARTING_ITEMS {
+            inventory.add(item_stack.item, item_stack.count);
+        }
+        inventory
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    pub fn selected_hotbar_slot(&self) -> usize {
+        self.selected_hotbar_slot
+    }
+
+    pub fn select_hotbar_slot(&mut self, slot: usize) {
+        self.selected_hotbar_slot = slot.min(HOTBAR_SLOT_COUNT - 1);
+    }
+
+    // Moves the selection by `delta` slots, wrapping around at either end of the hotbar
+    pub fn scroll_hotbar(&mut self, delta: i32) {
+        let slot = self.selected_hotbar_slot as i32 + delta;
+        self.selected_hotbar_slot = slot.rem_euclid(HOTBAR_SLOT_COUNT as i32) as usize;
+    }
+
+    pub fn selected(&self) -> Option<ItemStack> {
+        self.slots[self.selected_hotbar_slot]
+    }
+
+    // Adds to stacks of the same item first, then to empty slots. Returns how many didn't fit.
+    pub fn add(&mut self, item: Item, mut count: u32) -> u32 {
+        let max_stack_size = item.max_stack_size();
+        for item_stack in self.slots.iter_mut().flatten() {
+            if item_stack.item == item {
+                let added = count.min(max_stack_size.saturating_sub(item_stack.count));
+                item_stack.count += added;
+                count -= added;
+            }
+        }
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if count == 0 {
+                break;
+            }
+            let added = count.min(max_stack_size);
+            *slot = Some(ItemStack { item, count: added });
+            count -= added;
+        }
+        count
+    }
+
+    // How many of `item` there are across all slots
+    pub fn count(&self, item: Item) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|item_stack| item_stack.item == item)
+            .map(|item_stack| item_stack.count)
+            .sum()
+    }
+
+    // Takes `count` of `item` out of the inventory, from the last slots first so the hotbar is
+    // emptied last. Takes nothing and returns false if there aren't enough.
+    pub fn remove(&mut self, item: Item, mut count: u32) -> bool {
+        if self.count(item) < count {
+            return false;
+        }
+        for slot in self.slots.iter_mut().rev() {
+            let item_stack = match slot {
+                Some(item_stack) if item_stack.item == item => item_stack,
+                _ => continue,
+            };
+            let removed = count.min(item_stack.count);
+            item_stack.count -= removed;
+            count -= removed;
+            if item_stack.count == 0 {
+                *slot = None;
+            }
+        }
+        true
+    }
+
+    // Takes one item out of the selected slot, to place it
+    pub fn take_selected(&mut self) -> Option<Item> {
+        let slot = &mut self.slots[self.selected_hotbar_slot];
+        let item_stack = slot.as_mut()?;
+        let item = item_stack.item;
+        item_stack.count -= 1;
+        if item_stack.count == 0 {
+            *slot = None;
+        }
+        Some(item)
+    }
+
+    // Saved by the page, or in `INVENTORY_SAVE_PATH` natively, see `from_json`
+    pub fn to_json(&self) -> serde_json::Value {
+        let slots: Vec<serde_json::Value> = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, item_stack)| {
+                let item_stack = item_stack.as_ref()?;
+                Some(serde_json::json!({
+                    "slot": slot,
+                    "item": item_stack.item.to_string(),
+                    "count": item_stack.count,
+                }))
+            })
+            .collect();
+        serde_json::json!({
+            "selected_hotbar_slot": self.selected_hotbar_slot,
+            "slots": slots,
+        })
+    }
+
+    // `None` if the JSON isn't a saved inventory. Slots with unknown items are left empty.
+    pub fn from_json(json: &serde_json::Value) -> Option<Self> {
+        let mut inventory = Inventory {
+            slots: [None; SLOT_COUNT],
+            selected_hotbar_slot: 0,
+        };
+        inventory.select_hotbar_slot(json["selected_hotbar_slot"].as_u64()? as usize);
+        for slot_json in json["slots"].as_array()? {
+            let slot = slot_json["slot"].as_u64()? as usize;
+            let item = Item::from_name(slot_json["item"].as_str()?);
+            let count = slot_json["count"].as_u64()? as u32;
+            match item {
+                Some(item) if slot < SLOT_COUNT && count > 0 => {
+                    let count = count.min(item.max_stack_size());
+                    inventory.slots[slot] = Some(ItemStack { item, count });
+                }
+                _ => (),
+            }
+        }
+        Some(inventory)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_inventory(path: &Path, inventory: &Inventory) -> std::io::Result<()> {
+    std::fs::write(path, inventory.to_json().to_string())
+}
+
+// `None` if nothing was saved yet
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_inventory(path: &Path) -> Option<Inventory> {
+    let json = std::fs::read_to_string(path).ok()?;
+    let inventory = serde_json::from_str(&json)
+        .ok()
+        .and_then(|json| Inventory::from_json(&json));
+    if inventory.is_none() {
+        log::warn!("Failed to load inventory: {}", json);
+    }
+    inventory
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_inventory_loads_back() {
+        let path = std::env::temp_dir().join(format!("inventory_{}.json", std::process::id()));
+        assert_eq!(load_inventory(&path), None);
+
+        let mut inventory = Inventory::new();
+        inventory.add(Item::Block(BlockType::Stone), 70);
+        inventory.select_hotbar_slot(4);
+        save_inventory(&path, &inventory).unwrap();
+        assert_eq!(load_inventory(&path), Some(inventory));
+
+        std::fs::write(&path, "{}").unwrap();
+        assert_eq!(load_inventory(&path), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
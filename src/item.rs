@@ -0,0 +1,75 @@
+use crate::inventory::MAX_STACK_SIZE;
+use crate::mining::Tool;
+use crate::world::BlockType;
+use std::fmt;
+
+// Anything that can be held in the inventory. Blocks are placed back into the world, the rest are
+// only crafted with or held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Item {
+    Block(BlockType),
+    Stick,
+    WoodenPickaxe,
+    StonePickaxe,
+    WoodenShovel,
+    WoodenAxe,
+}
+
+impl Item {
+    // Items that aren't blocks
+    const NON_BLOCK_ITEMS: [Item; 5] = [
+        Item::Stick,
+        Item::WoodenPickaxe,
+        Item::StonePickaxe,
+        Item::WoodenShovel,
+        Item::WoodenAxe,
+    ];
+
+    // Blocks go by their `BlockType` name, e.g. "OakPlank"
+    pub fn from_name(name: &str) -> Option<Item> {
+        Item::NON_BLOCK_ITEMS
+            .into_iter()
+            .find(|item| item.to_string() == name)
+            .or_else(|| BlockType::from_name(name).map(Item::Block))
+    }
+
+    pub fn block_type(self) -> Option<BlockType> {
+        match self {
+            Item::Block(block_type) => Some(block_type),
+            _ => None,
+        }
+    }
+
+    pub fn tool(self) -> Option<Tool> {
+        match self {
+            Item::WoodenPickaxe => Some(Tool::WoodenPickaxe),
+            Item::StonePickaxe => Some(Tool::StonePickaxe),
+            Item::WoodenShovel => Some(Tool::WoodenShovel),
+            Item::WoodenAxe => Some(Tool::WoodenAxe),
+            _ => None,
+        }
+    }
+
+    // Tools don't stack
+    pub fn max_stack_size(self) -> u32 {
+        match self.tool() {
+            Some(_) => 1,
+            None => MAX_STACK_SIZE,
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Item::Block(block_type) => write!(f, "{}", block_type),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl From<BlockType> for Item {
+    fn from(block_type: BlockType) -> Self {
+        Item::Block(block_type)
+    }
+}
@@ -0,0 +1,711 @@
+use crate::byte_codec::{ByteReader, ByteWriter};
+use crate::camera::Camera;
+use crate::lod::{LodChunkMask, LodMesh, LodTile, LodTileIdx};
+use crate::world::{ChunkData, ChunkSnapshot, GeneratedChunk, WorldState};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+
+#[cfg(target_arch = "wasm32")]
+use crate::wasm_utils;
+#[cfg(target_arch = "wasm32")]
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsCast;
+
+// Budget for running jobs on the main thread each tick when no Web Worker has loaded
+#[cfg(target_arch = "wasm32")]
+const INLINE_JOB_BUDGET_MS: u128 = 4;
+// Each Web Worker loads its own copy of the wasm module, so don't start too many
+#[cfg(target_arch = "wasm32")]
+const MAX_WEB_WORKERS: usize = 4;
+// Budget for adding finished chunks to the world and snapshotting chunks for meshing each tick,
+// the rest waits for the next tick
+const CHUNK_UPDATE_BUDGET_MS: u128 = 4;
+
+pub enum ChunkJob {
+    Generate([usize; 2]),
+    Mesh {
+        snapshot: ChunkSnapshot,
+        version: u64,
+    },
+    MeshLod(LodTile),
+}
+
+pub enum ChunkJobResult {
+    Generated(GeneratedChunk),
+    Meshed { chunk_data: ChunkData, version: u64 },
+    MeshedLod(LodMesh),
+}
+
+impl ChunkJob {
+    // Lower runs first, see `JobQueue::priorities`
+    fn priority(&self, priorities: &HashMap<[usize; 2], usize>) -> usize {
+        let chunk_idx = match self {
+            ChunkJob::Generate(chunk_idx) => chunk_idx,
+            ChunkJob::Mesh { snapshot, .. } => &snapshot.chunk_idx,
+            // After the full resolution chunks, inner rings first
+            ChunkJob::MeshLod(tile) => return priorities.len() + tile.idx.cell_size,
+        };
+        *priorities.get(chunk_idx).unwrap_or(&usize::MAX)
+    }
+
+    fn run(self) -> ChunkJobResult {
+        match self {
+            ChunkJob::Generate(chunk_idx) => {
+                ChunkJobResult::Generated(GeneratedChunk::generate(chunk_idx))
+            }
+            ChunkJob::Mesh { snapshot, version } => ChunkJobResult::Meshed {
+                chunk_data: snapshot.compute_mesh(),
+                version,
+            },
+            ChunkJob::MeshLod(tile) => ChunkJobResult::MeshedLod(tile.compute_mesh()),
+        }
+    }
+
+    // Only the main thread encodes jobs, and only on the web
+    #[cfg(any(target_arch = "wasm32", test))]
+    fn encode(&self, writer: &mut ByteWriter) {
+        match self {
+            ChunkJob::Generate(chunk_idx) => {
+                writer.write_u8(0);
+                writer.write_usizes(*chunk_idx);
+            }
+            ChunkJob::Mesh { snapshot, version } => {
+                writer.write_u8(1);
+                snapshot.encode(writer);
+                writer.write_u64(*version);
+            }
+            ChunkJob::MeshLod(tile) => {
+                writer.write_u8(2);
+                tile.encode(writer);
+            }
+        }
+    }
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        match reader.read_u8()? {
+            0 => Some(ChunkJob::Generate(reader.read_usizes()?)),
+            1 => Some(ChunkJob::Mesh {
+                snapshot: ChunkSnapshot::decode(reader)?,
+                version: reader.read_u64()?,
+            }),
+            2 => Some(ChunkJob::MeshLod(LodTile::decode(reader)?)),
+            _ => None,
+        }
+    }
+}
+
+impl ChunkJobResult {
+    fn encode(&self, writer: &mut ByteWriter) {
+        match self {
+            ChunkJobResult::Generated(generated_chunk) => {
+                writer.write_u8(0);
+                generated_chunk.encode(writer);
+            }
+            ChunkJobResult::Meshed {
+                chunk_data,
+                version,
+            } => {
+                writer.write_u8(1);
+                chunk_data.encode(writer);
+                writer.write_u64(*version);
+            }
+            ChunkJobResult::MeshedLod(lod_mesh) => {
+                writer.write_u8(2);
+                lod_mesh.encode(writer);
+            }
+        }
+    }
+
+    #[cfg(any(target_arch = "wasm32", test))]
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        match reader.read_u8()? {
+            0 => Some(ChunkJobResult::Generated(GeneratedChunk::decode(reader)?)),
+            1 => Some(ChunkJobResult::Meshed {
+                chunk_data: ChunkData::decode(reader)?,
+                version: reader.read_u64()?,
+            }),
+            2 => Some(ChunkJobResult::MeshedLod(LodMesh::decode(reader)?)),
+            _ => None,
+        }
+    }
+}
+
+// Runs a job encoded with `ChunkJob::encode` and returns the encoded result. This is what the Web
+// Workers in ts/chunkWorker.ts call. `None` if the job doesn't decode, e.g. when the worker's wasm
+// module is from an older build than the page's.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn run_encoded_chunk_job(job: &[u8]) -> Option<Vec<u8>> {
+    let job = ChunkJob::decode(&mut ByteReader::new(job))?;
+    let mut writer = ByteWriter::new();
+    job.run().encode(&mut writer);
+    Some(writer.into_bytes())
+}
+
+struct JobQueue {
+    pending: Vec<ChunkJob>,
+    // Position of each chunk in the chunk order, jobs for closer chunks run first
+    priorities: HashMap<[usize; 2], usize>,
+    is_shut_down: bool,
+}
+
+impl JobQueue {
+    fn pop_highest_priority(&mut self) -> Option<ChunkJob> {
+        let (job_idx, _) = self
+            .pending
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, job)| job.priority(&self.priorities))?;
+        Some(self.pending.swap_remove(job_idx))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone, Copy, PartialEq)]
+enum WebWorkerState {
+    Loading,
+    Idle,
+    Busy,
+    // Couldn't run a job, it gets no more of them
+    Failed,
+}
+
+// A Web Worker running ts/chunkWorker.ts. Jobs and results are passed as bytes, the worker
+// doesn't share memory with the main thread.
+#[cfg(target_arch = "wasm32")]
+struct WebWorker {
+    worker: web_sys::Worker,
+    state: Rc<Cell<WebWorkerState>>,
+    // Kept until its result is back, so it can run on the main thread if the worker fails it
+    running_job: Rc<RefCell<Option<ChunkJob>>>,
+    failed_jobs_sender: mpsc::Sender<ChunkJob>,
+    _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WebWorker {
+    fn new(
+        results_sender: mpsc::Sender<ChunkJobResult>,
+        failed_jobs_sender: mpsc::Sender<ChunkJob>,
+    ) -> Option<Self> {
+        let worker = wasm_utils::js_create_chunk_worker()?;
+        let state = Rc::new(Cell::new(WebWorkerState::Loading));
+        let running_job = Rc::new(RefCell::new(None));
+        let on_message = {
+            let state = state.clone();
+            let running_job = running_job.clone();
+            let failed_jobs_sender = failed_jobs_sender.clone();
+            Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+                // The worker sends an empty buffer once it has loaded, then a result for each job.
+                // Jobs it can't decode get an error message instead.
+                let result = match event.data().dyn_into::<js_sys::ArrayBuffer>() {
+                    Ok(buffer) if buffer.byte_length() == 0 => {
+                        state.set(WebWorkerState::Idle);
+                        return;
+                    }
+                    Ok(buffer) => {
+                        let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+                        ChunkJobResult::decode(&mut ByteReader::new(&bytes))
+                            .ok_or_else(|| JsValue::from_str("Malformed chunk job result"))
+                    }
+                    Err(error) => Err(error),
+                };
+                let job = running_job.borrow_mut().take();
+                // Sends fail once the pool went away, then nobody cares about the job anymore
+                match result {
+                    Ok(result) => {
+                        let _ = results_sender.send(result);
+                        state.set(WebWorkerState::Idle);
+                    }
+                    Err(error) => {
+                        log::error!("Chunk worker failed a job: {:?}", error);
+                        if let Some(job) = job {
+                            let _ = failed_jobs_sender.send(job);
+                        }
+                        state.set(WebWorkerState::Failed);
+                    }
+                }
+            }) as Box<dyn FnMut(web_sys::MessageEvent)>)
+        };
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Some(Self {
+            worker,
+            state,
+            running_job,
+            failed_jobs_sender,
+            _on_message: on_message,
+        })
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(
+            self.state.get(),
+            WebWorkerState::Idle | WebWorkerState::Busy
+        )
+    }
+
+    fn run(&self, job: ChunkJob) {
+        let mut writer = ByteWriter::new();
+        job.encode(&mut writer);
+        // Hand the buffer over instead of copying it
+        let buffer = js_sys::Uint8Array::from(&writer.into_bytes()[..]).buffer();
+        match self
+            .worker
+            .post_message_with_transfer(&buffer, &js_sys::Array::of1(&buffer))
+        {
+            Ok(()) => {
+                *self.running_job.borrow_mut() = Some(job);
+                self.state.set(WebWorkerState::Busy);
+            }
+            Err(error) => {
+                log::error!("Failed to post chunk job: {:?}", error);
+                let _ = self.failed_jobs_sender.send(job);
+                self.state.set(WebWorkerState::Failed);
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Drop for WebWorker {
+    fn drop(&mut self) {
+        self.worker.terminate();
+    }
+}
+
+// Runs chunk jobs on a pool of worker threads. Workers only see the job's own data, the world is
+// only ever touched on the main thread.
+//
+// On the web there are no threads unless the wasm module is built with shared memory (atomics),
+// which wasm-pack doesn't do out of the box. There the jobs go to Web Workers instead, one at a
+// time each so they still run closest chunks first. Until a worker has loaded, or if none can be
+// started, jobs run on the main thread, a few milliseconds' worth per tick. So do jobs a worker
+// failed to run.
+pub struct JobPool {
+    queue: Arc<(Mutex<JobQueue>, Condvar)>,
+    results: mpsc::Receiver<ChunkJobResult>,
+    #[cfg(target_arch = "wasm32")]
+    results_sender: mpsc::Sender<ChunkJobResult>,
+    #[cfg(target_arch = "wasm32")]
+    web_workers: Vec<WebWorker>,
+    #[cfg(target_arch = "wasm32")]
+    failed_jobs: mpsc::Receiver<ChunkJob>,
+    #[cfg(not(target_arch = "wasm32"))]
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+impl JobPool {
+    pub fn new() -> Self {
+        let queue = Arc::new((
+            Mutex::new(JobQueue {
+                pending: vec![],
+                priorities: HashMap::new(),
+                is_shut_down: false,
+            }),
+            Condvar::new(),
+        ));
+        let (results_sender, results) = mpsc::channel();
+
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                // Leave a core for the main thread
+                let num_web_workers = web_sys::window()
+                    .map_or(1, |window| window.navigator().hardware_concurrency() as usize)
+                    .saturating_sub(1)
+                    .clamp(1, MAX_WEB_WORKERS);
+                let (failed_jobs_sender, failed_jobs) = mpsc::channel();
+                let web_workers = (0..num_web_workers)
+                    .map_while(|_| {
+                        WebWorker::new(results_sender.clone(), failed_jobs_sender.clone())
+                    })
+                    .collect();
+
+                Self {
+                    queue,
+                    results,
+                    results_sender,
+                    web_workers,
+                    failed_jobs,
+                }
+            } else {
+                // Leave a core for the main thread
+                let num_workers = std::thread::available_parallelism()
+                    .map_or(1, |n| n.get() - 1)
+                    .max(1);
+                let workers = (0..num_workers)
+                    .map(|worker_idx| {
+                        let queue = queue.clone();
+                        let results_sender = results_sender.clone();
+                        std::thread::Builder::new()
+                            .name(format!("chunk worker {}", worker_idx))
+                            .spawn(move || Self::work(&queue, &results_sender))
+                            .unwrap()
+                    })
+                    .collect();
+
+                Self {
+                    queue,
+                    results,
+                    workers,
+                }
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn work(queue: &(Mutex<JobQueue>, Condvar), results_sender: &mpsc::Sender<ChunkJobResult>) {
+        let (queue, job_available) = queue;
+        loop {
+            let job = {
+                let mut queue = queue.lock().unwrap();
+                loop {
+                    if queue.is_shut_down {
+                        return;
+                    }
+                    if let Some(job) = queue.pop_highest_priority() {
+                        break job;
+                    }
+                    queue = job_available.wait(queue).unwrap();
+                }
+            };
+
+            // Main thread went away, nobody cares about the result
+            if results_sender.send(job.run()).is_err() {
+                return;
+            }
+        }
+    }
+
+    pub fn submit(&self, job: ChunkJob) {
+        let (queue, job_available) = &*self.queue;
+        queue.lock().unwrap().pending.push(job);
+        job_available.notify_one();
+    }
+
+    // Pending jobs run in this order, jobs for chunks not in it run last
+    pub fn set_priorities(&self, chunk_order: &[[usize; 2]]) {
+        let (queue, _) = &*self.queue;
+        queue.lock().unwrap().priorities = chunk_order
+            .iter()
+            .enumerate()
+            .map(|(priority, &chunk_idx)| (chunk_idx, priority))
+            .collect();
+    }
+
+    pub fn finished_jobs(&mut self) -> Vec<ChunkJobResult> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let (queue, _) = &*self.queue;
+            let mut queue = queue.lock().unwrap();
+            let has_running_web_worker = self.web_workers.iter().any(WebWorker::is_running);
+            if has_running_web_worker {
+                for web_worker in self.web_workers.iter() {
+                    if web_worker.state.get() != WebWorkerState::Idle {
+                        continue;
+                    }
+                    match queue.pop_highest_priority() {
+                        Some(job) => web_worker.run(job),
+                        None => break,
+                    }
+                }
+            }
+
+            let start = instant::Instant::now();
+            while start.elapsed().as_millis() < INLINE_JOB_BUDGET_MS {
+                let job = match self.failed_jobs.try_recv() {
+                    Ok(job) => job,
+                    Err(_) if has_running_web_worker => break,
+                    Err(_) => match queue.pop_highest_priority() {
+                        Some(job) => job,
+                        None => break,
+                    },
+                };
+                self.results_sender.send(job.run()).unwrap();
+            }
+        }
+
+        self.results.try_iter().collect()
+    }
+}
+
+impl Default for JobPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for JobPool {
+    fn drop(&mut self) {
+        let (queue, job_available) = &*self.queue;
+        queue.lock().unwrap().is_shut_down = true;
+        job_available.notify_all();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+    }
+}
+
+// Keeps track of which visible chunks need to be generated or (re)meshed, and hands that work to
+// the job pool.
+pub struct ChunkJobs {
+    pool: JobPool,
+    finished_jobs: VecDeque<ChunkJobResult>,
+    // Visible chunks, see `WorldState::get_chunk_order_by_distance`
+    visible_chunks: HashSet<[usize; 2]>,
+    // Chunks that need a new mesh
+    stale_meshes: HashSet<[usize; 2]>,
+    generating: HashSet<[usize; 2]>,
+    // Jobs submitted to the pool whose results haven't come back yet
+    num_running_jobs: usize,
+    // Latest mesh requested for each chunk, results of older mesh jobs are dropped
+    mesh_versions: HashMap<[usize; 2], u64>,
+    // LOD tiles around the camera, results for tiles that changed in the meantime are dropped
+    lod_tiles: HashMap<LodTileIdx, LodChunkMask>,
+    finished_lod_meshes: Vec<LodMesh>,
+}
+
+impl ChunkJobs {
+    pub fn new() -> Self {
+        Self {
+            pool: JobPool::new(),
+            finished_jobs: VecDeque::new(),
+            visible_chunks: HashSet::new(),
+            stale_meshes: HashSet::new(),
+            generating: HashSet::new(),
+            num_running_jobs: 0,
+            mesh_versions: HashMap::new(),
+            lod_tiles: HashMap::new(),
+            finished_lod_meshes: vec![],
+        }
+    }
+
+    pub fn set_chunk_order(&mut self, chunk_order: &[[usize; 2]]) {
+        self.pool.set_priorities(chunk_order);
+        self.visible_chunks = chunk_order.iter().cloned().collect();
+
+        let visible_chunks = &self.visible_chunks;
+        self.stale_meshes
+            .retain(|chunk_idx| visible_chunks.contains(chunk_idx));
+        self.mesh_versions
+            .retain(|chunk_idx, _| visible_chunks.contains(chunk_idx));
+    }
+
+    // Meshes the tiles that are new or now draw different chunks
+    pub fn set_lod_tiles(&mut self, lod_tiles: HashMap<LodTileIdx, LodChunkMask>) {
+        for (idx, chunk_mask) in lod_tiles.iter() {
+            if self.lod_tiles.get(idx) != Some(chunk_mask) {
+                self.num_running_jobs += 1;
+                self.pool.submit(ChunkJob::MeshLod(LodTile {
+                    idx: *idx,
+                    chunk_mask: chunk_mask.clone(),
+                }));
+            }
+        }
+        self.lod_tiles = lod_tiles;
+    }
+
+    // LOD meshes finished since the last call, for the current tiles only
+    pub fn take_finished_lod_meshes(&mut self) -> Vec<LodMesh> {
+        std::mem::take(&mut self.finished_lod_meshes)
+    }
+
+    // Meshes the chunk in the background, generating it first if needed
+    pub fn request_mesh(&mut self, chunk_idx: [usize; 2]) {
+        if self.visible_chunks.contains(&chunk_idx) {
+            self.stale_meshes.insert(chunk_idx);
+        }
+    }
+
+    // Meshes the chunk right away, e.g. so placed and broken blocks show up on the next frame
+    pub fn mesh_now(
+        &mut self,
+        world_state: &mut WorldState,
+        chunk_idx: [usize; 2],
+        camera: &Camera,
+    ) -> ChunkData {
+        self.stale_meshes.remove(&chunk_idx);
+        self.next_mesh_version(chunk_idx);
+        world_state.compute_chunk_mesh(chunk_idx, camera)
+    }
+
+    // Adds finished chunks to the world and starts jobs for stale chunks. Returns the meshes that
+    // are ready to be uploaded.
+    pub fn update(&mut self, world_state: &mut WorldState, camera: &Camera) -> Vec<ChunkData> {
+        let start = instant::Instant::now();
+        let is_over_budget = || start.elapsed().as_millis() >= CHUNK_UPDATE_BUDGET_MS;
+
+        let mut meshes = vec![];
+        let finished_jobs = self.pool.finished_jobs();
+        self.num_running_jobs -= finished_jobs.len();
+        self.finished_jobs.extend(finished_jobs);
+        while !is_over_budget() {
+            let result = match self.finished_jobs.pop_front() {
+                Some(result) => result,
+                None => break,
+            };
+            match result {
+                ChunkJobResult::Generated(generated_chunk) => {
+                    self.generating.remove(&generated_chunk.chunk_idx);
+                    for chunk_idx in world_state.install_generated_chunk(generated_chunk) {
+                        self.request_mesh(chunk_idx);
+                    }
+                }
+                ChunkJobResult::Meshed {
+                    chunk_data,
+                    version,
+                } => {
+                    if self.mesh_versions.get(&chunk_data.position) == Some(&version) {
+                        meshes.push(chunk_data);
+                    }
+                }
+                ChunkJobResult::MeshedLod(lod_mesh) => {
+                    if self.lod_tiles.get(&lod_mesh.idx) == Some(&lod_mesh.chunk_mask) {
+                        self.finished_lod_meshes.push(lod_mesh);
+                    }
+                }
+            }
+        }
+
+        for chunk_idx in self.stale_meshes.iter().cloned().collect::<Vec<_>>() {
+            if is_over_budget() {
+                break;
+            }
+
+            // Wait for the visible neighbors too, their blocks decide which faces on the chunk
+            // border are hidden. Otherwise we'd mesh the chunk again as soon as they're done.
+            // Chunks on the edge of the world have no neighbors past it.
+            let [chunk_x, chunk_z] = chunk_idx;
+            let neighborhood = [
+                Some(chunk_idx),
+                Some([chunk_x + 1, chunk_z]),
+                chunk_x.checked_sub(1).map(|x| [x, chunk_z]),
+                Some([chunk_x, chunk_z + 1]),
+                chunk_z.checked_sub(1).map(|z| [chunk_x, z]),
+            ];
+            let mut is_ready = true;
+            for neighbor_idx in neighborhood.into_iter().flatten() {
+                if self.visible_chunks.contains(&neighbor_idx)
+                    && !world_state.is_chunk_generated(neighbor_idx)
+                {
+                    is_ready = false;
+                    if self.generating.insert(neighbor_idx) {
+                        self.num_running_jobs += 1;
+                        self.pool.submit(ChunkJob::Generate(neighbor_idx));
+                    }
+                }
+            }
+            if !is_ready {
+                continue;
+            }
+
+            self.stale_meshes.remove(&chunk_idx);
+            let version = self.next_mesh_version(chunk_idx);
+            let snapshot = world_state.chunk_snapshot(chunk_idx, camera);
+            self.num_running_jobs += 1;
+            self.pool.submit(ChunkJob::Mesh { snapshot, version });
+        }
+
+        meshes
+    }
+
+    // Every visible chunk has its latest mesh and every LOD tile too, and `update` handed them all
+    // out. E.g. frames are timed once this holds, see frame_timing.rs.
+    pub fn is_idle(&self) -> bool {
+        self.stale_meshes.is_empty()
+            && self.num_running_jobs == 0
+            && self.finished_jobs.is_empty()
+            && self.finished_lod_meshes.is_empty()
+    }
+
+    fn next_mesh_version(&mut self, chunk_idx: [usize; 2]) -> u64 {
+        let version = self.mesh_versions.entry(chunk_idx).or_insert(0);
+        *version += 1;
+        *version
+    }
+}
+
+impl Default for ChunkJobs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_job(job: &ChunkJob) -> Vec<u8> {
+        let mut writer = ByteWriter::new();
+        job.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    fn encode_result(result: &ChunkJobResult) -> Vec<u8> {
+        let mut writer = ByteWriter::new();
+        result.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    // What the main thread gets back from a Web Worker
+    fn run_on_web_worker(job: &ChunkJob) -> ChunkJobResult {
+        let bytes = run_encoded_chunk_job(&encode_job(job)).unwrap();
+        let mut reader = ByteReader::new(&bytes);
+        let result = ChunkJobResult::decode(&mut reader).unwrap();
+        assert!(reader.is_empty());
+        result
+    }
+
+    #[test]
+    fn generated_chunk_survives_encoding() {
+        // Generation places trees and flowers at random, so compare against the worker's own
+        // result
+        let bytes = run_encoded_chunk_job(&encode_job(&ChunkJob::Generate([3, 4]))).unwrap();
+        let result = ChunkJobResult::decode(&mut ByteReader::new(&bytes)).unwrap();
+
+        match &result {
+            ChunkJobResult::Generated(generated_chunk) => {
+                assert_eq!(generated_chunk.chunk_idx, [3, 4])
+            }
+            _ => panic!("expected a generated chunk"),
+        }
+        assert_eq!(encode_result(&result), bytes);
+    }
+
+    #[test]
+    fn lod_mesh_matches_running_the_job_directly() {
+        let tile = || LodTile {
+            idx: LodTileIdx {
+                cell_size: 4,
+                tile: [5, 6],
+            },
+            chunk_mask: (0..LodTileIdx::chunk_width(4).pow(2))
+                .map(|chunk_offset| chunk_offset % 3 != 0)
+                .collect(),
+        };
+
+        let result = run_on_web_worker(&ChunkJob::MeshLod(tile()));
+
+        assert!(matches!(&result, ChunkJobResult::MeshedLod(lod_mesh)
+            if lod_mesh.idx == tile().idx && lod_mesh.chunk_mask == tile().chunk_mask));
+        assert_eq!(
+            encode_result(&result),
+            encode_result(&ChunkJob::MeshLod(tile()).run())
+        );
+    }
+
+    #[test]
+    fn malformed_jobs_dont_decode() {
+        let bytes = encode_job(&ChunkJob::Generate([3, 4]));
+        assert!(ChunkJob::decode(&mut ByteReader::new(&bytes[..bytes.len() - 1])).is_none());
+        assert!(ChunkJob::decode(&mut ByteReader::new(&[9])).is_none());
+        assert_eq!(run_encoded_chunk_job(&bytes[..bytes.len() - 1]), None);
+    }
+}
@@ -3,25 +3,57 @@ extern crate itertools;
 #[macro_use]
 extern crate bmp;
 
+pub mod atlas;
+pub mod byte_codec;
 pub mod camera;
 pub mod color;
+pub mod combat;
+pub mod crafting;
+pub mod culling;
 pub mod dom_controls;
+pub mod dropped_item;
+pub mod entity;
 pub mod face;
+pub mod fog;
+pub mod frame_timing;
 pub mod game_loop;
+pub mod greedy_mesh;
 pub mod instance;
+pub mod inventory;
+pub mod item;
+pub mod jobs;
 pub mod light;
+pub mod lod;
 pub mod map_generation;
+pub mod mining;
+pub mod mob;
+pub mod occlusion;
+pub mod pathfinding;
+pub mod physics;
+pub mod resource_pack;
+pub mod sky;
 pub mod spawner;
 pub mod texture;
 pub mod vec_extra;
 pub mod vertex;
 pub mod world;
 pub mod wasm_utils;
+pub mod world_clock;
 
-use cgmath::Point3;
+use cgmath::{MetricSpace, Point3};
+use collision::Aabb;
 use dom_controls::DomControlsUserEvent;
+use entity::Entities;
 use spawner::Spawner;
-use std::{borrow::Cow, cell::RefCell, collections::HashSet, future::Future, mem, pin::Pin, task};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    future::Future,
+    mem,
+    pin::Pin,
+    task,
+};
 use wgpu::{util::DeviceExt, SurfaceTexture};
 use winit::{
     event::{DeviceEvent, ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent},
@@ -38,9 +70,14 @@ static RENDER_CHARACTER_ENTITY: bool = false;
 #[allow(dead_code)]
 const VERBOSE_LOGS: bool = false;
 
+// See `game_loop::GameLoop`
+const UPDATES_PER_SECOND: u32 = 100;
+const MAX_FRAME_TIME: f64 = 1.0 / 60.0;
+
 struct State {
     surface_config: wgpu::SurfaceConfiguration,
-    surface: wgpu::Surface,
+    // `None` when rendering offscreen, see `State::new_headless`
+    surface: Option<wgpu::Surface>,
     device: wgpu::Device,
     queue: wgpu::Queue,
 
@@ -48,7 +85,10 @@ struct State {
     camera_controller: camera::CameraController,
     camera_uniform: camera::CameraUniform,
     light_uniform: light::LightUniform,
+    sky_uniform: sky::SkyUniform,
+    shadow_quality: light::ShadowQuality,
     world_state: world::WorldState,
+    chunk_jobs: jobs::ChunkJobs,
 }
 
 struct VertexBufers {
@@ -69,10 +109,37 @@ struct IndexCounts {
     character_entity: usize,
 }
 
+// Built from the shader templates, rebuilt when the shadow settings change
+struct ShaderPipelines {
+    shadow_map_pipeline: wgpu::RenderPipeline,
+    opaque_pipeline: wgpu::RenderPipeline,
+    translucent_pipeline: wgpu::RenderPipeline,
+    pipeline_solid_color: Option<wgpu::RenderPipeline>,
+    pipeline_wire: Option<wgpu::RenderPipeline>,
+    pipeline_wire_no_instancing: Option<wgpu::RenderPipeline>,
+}
+
+struct SceneBindGroupLayouts<'a> {
+    albedo_only_texture: &'a wgpu::BindGroupLayout,
+    texture: &'a wgpu::BindGroupLayout,
+    camera: &'a wgpu::BindGroupLayout,
+    light: &'a wgpu::BindGroupLayout,
+    shadow_cascade: &'a wgpu::BindGroupLayout,
+}
+
 struct Scene {
     vertex_buffers: VertexBufers,
     index_buffers: IndexBufers,
     index_counts: IndexCounts,
+    albedo_only_texture_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    // Kept with the two above to rebuild the pipelines when the shadow settings change
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    light_bind_group_layout: wgpu::BindGroupLayout,
+    shadow_cascade_bind_group_layout: wgpu::BindGroupLayout,
+    // Kept to recreate the texture bind groups when the shadow map is resized
+    texture_atlas: texture::Texture,
+    // Both sample the block atlas, and are recreated when the resource pack changes
     albedo_only_texture_bind_group: wgpu::BindGroup,
     texture_bind_group: wgpu::BindGroup,
     camera_bind_group: wgpu::BindGroup,
@@ -82,21 +149,46 @@ struct Scene {
     light_buf: wgpu::Buffer,
     chunk_render_descriptors: Vec<ChunkRenderDescriptor>,
     chunk_order: Vec<[usize; 2]>,
+    lod_render_descriptors: HashMap<lod::LodTileIdx, LodRenderDescriptor>,
     depth_texture: texture::Texture,
     opaque_pipeline: wgpu::RenderPipeline,
     translucent_pipeline: wgpu::RenderPipeline,
 
+    // Cracks over the block being mined, and which block and break stage they show
+    break_overlay_buf: wgpu::Buffer,
+    break_overlay: Option<([usize; 3], u32)>,
+    entity_instances_buf: wgpu::Buffer,
+    entity_instance_count: u32,
+
+    // Kept to recreate `texture_bind_group`, which samples it, when the resource pack changes.
+    // Resized with the shadow settings.
     shadow_map_texture: texture::Texture,
+    // One per cascade, see `light::ShadowCascade`
+    shadow_map_layer_views: Vec<wgpu::TextureView>,
+    shadow_cascade_bufs: Vec<wgpu::Buffer>,
+    shadow_cascade_bind_groups: Vec<wgpu::BindGroup>,
     shadow_map_pipeline: wgpu::RenderPipeline,
 
+    // Drawn first in the forward pass, see sky.rs
+    sky_pipeline: wgpu::RenderPipeline,
+    sky_buf: wgpu::Buffer,
+    sky_bind_group: wgpu::BindGroup,
+
     pipeline_wire: Option<wgpu::RenderPipeline>,
     pipeline_wire_no_instancing: Option<wgpu::RenderPipeline>,
     pipeline_solid_color: Option<wgpu::RenderPipeline>,
+
+    culling_stats: culling::CullingStats,
 }
 
 struct Game {
     state: State,
     scene: Scene,
+    // Pack the textures come from, `None` for the built-in textures
+    #[cfg(not(target_arch = "wasm32"))]
+    resource_pack_path: Option<std::path::PathBuf>,
+    // Moves the camera while frames are timed, see frame_timing.rs
+    frame_timer: Option<frame_timing::FrameTimer>,
 }
 
 // impl Deref for Game {
@@ -116,34 +208,253 @@ struct Game {
 struct AnnotatedInstanceBuffer {
     buffer: wgpu::Buffer,
     len: usize,
+    // Number of instances the buffer has room for
+    capacity: usize,
     data_type: world::ChunkDataType,
 }
+
+impl AnnotatedInstanceBuffer {
+    // Leave some headroom so placing or breaking a few blocks doesn't reallocate the buffer
+    const MIN_CAPACITY: usize = 256;
+
+    fn new(
+        device: &wgpu::Device,
+        typed_instances: &world::TypedInstances,
+        chunk_idx: [usize; 2],
+    ) -> Self {
+        let capacity = Self::capacity_for(typed_instances.instance_data.len());
+        let buffer = Self::create_buffer(device, typed_instances, chunk_idx, capacity);
+
+        Self {
+            buffer,
+            len: typed_instances.instance_data.len(),
+            capacity,
+            data_type: typed_instances.data_type.clone(),
+        }
+    }
+
+    fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        typed_instances: &world::TypedInstances,
+        chunk_idx: [usize; 2],
+    ) {
+        let len = typed_instances.instance_data.len();
+        if len > self.capacity {
+            // Chunk mesh outgrew the buffer, e.g. after digging into a hillside
+            self.capacity = Self::capacity_for(len);
+            self.buffer = Self::create_buffer(device, typed_instances, chunk_idx, self.capacity);
+        } else {
+            queue.write_buffer(
+                &self.buffer,
+                0,
+                bytemuck::cast_slice(&typed_instances.instance_data),
+            );
+        }
+        self.len = len;
+    }
+
+    fn capacity_for(len: usize) -> usize {
+        (len + len / 2).max(Self::MIN_CAPACITY)
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        typed_instances: &world::TypedInstances,
+        [chunk_x, chunk_z]: [usize; 2],
+        capacity: usize,
+    ) -> wgpu::Buffer {
+        let instance_byte_contents: &[u8] = bytemuck::cast_slice(&typed_instances.instance_data);
+        let unpadded_size: u64 = (capacity * instance::InstanceRaw::size())
+            .try_into()
+            .unwrap();
+
+        // Valid vulkan usage is
+        // 1. buffer size must be a multiple of COPY_BUFFER_ALIGNMENT.
+        // 2. buffer size must be greater than 0.
+        // Therefore we round the value up to the nearest multiple, and ensure it's at least COPY_BUFFER_ALIGNMENT.
+        let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+        let padded_size =
+            ((unpadded_size + align_mask) & !align_mask).max(wgpu::COPY_BUFFER_ALIGNMENT);
+
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&*format!(
+                "Instance Buffer {:?} {},{}",
+                typed_instances.data_type, chunk_x, chunk_z
+            )),
+            size: padded_size,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: true,
+        });
+
+        instance_buffer.slice(..).get_mapped_range_mut()[..instance_byte_contents.len()]
+            .copy_from_slice(instance_byte_contents);
+        instance_buffer.unmap();
+
+        instance_buffer
+    }
+}
+
+// How far the camera moves before translucent faces of nearby chunks are sorted again, in blocks.
+// Chunks further away than a chunk's width wait for proportionally larger moves.
+const TRANSLUCENT_RESORT_DISTANCE: f32 = 0.5;
+
 struct ChunkRenderDescriptor {
-    #[allow(dead_code)]
     world_chunk_position: [usize; 2],
     annotated_instance_buffers: Vec<AnnotatedInstanceBuffer>,
+    // See `world::ChunkData::y_bounds`
+    y_bounds: [f32; 2],
+    connectivity: occlusion::ChunkConnectivity,
+    // Copies of the translucent and semi-translucent instances, to sort them again as the camera
+    // moves, see `ChunkRenderDescriptor::resort_translucent_instances`
+    translucent_instances: Vec<world::TypedInstances>,
+    translucent_sorted_from: Point3<f32>,
+}
+
+impl ChunkRenderDescriptor {
+    fn set_translucent_instances(&mut self, chunk_data: &world::ChunkData) {
+        self.translucent_instances = chunk_data
+            .typed_instances_vec
+            .iter()
+            .filter(|typed_instances| typed_instances.data_type != ChunkDataType::Opaque)
+            .cloned()
+            .collect();
+        self.translucent_sorted_from = chunk_data.translucent_sorted_from;
+    }
+
+    // Translucent faces blend in the order they're drawn, which changes as the camera moves. The
+    // farther away the chunk, the further the camera can move before the order noticeably changes.
+    fn resort_translucent_instances(&mut self, queue: &wgpu::Queue, eye: Point3<f32>) {
+        if self.translucent_instances.is_empty() {
+            return;
+        }
+        let chunk_center = culling::chunk_aabb(self.world_chunk_position, self.y_bounds).center();
+        let chunk_distance = eye.distance(chunk_center) / CHUNK_XZ_SIZE as f32;
+        if eye.distance(self.translucent_sorted_from)
+            < TRANSLUCENT_RESORT_DISTANCE * chunk_distance.max(1.0)
+        {
+            return;
+        }
+
+        self.translucent_sorted_from = eye;
+        for typed_instances in self.translucent_instances.iter_mut() {
+            if typed_instances.instance_data.is_empty() {
+                continue;
+            }
+            instance::sort_back_to_front(&mut typed_instances.instance_data, eye);
+            if let Some(instance_buffer) = self
+                .annotated_instance_buffers
+                .iter()
+                .find(|ib| ib.data_type == typed_instances.data_type)
+            {
+                queue.write_buffer(
+                    &instance_buffer.buffer,
+                    0,
+                    bytemuck::cast_slice(&typed_instances.instance_data),
+                );
+            }
+        }
+    }
+}
+
+struct LodRenderDescriptor {
+    annotated_instance_buffers: Vec<AnnotatedInstanceBuffer>,
+    // See `lod::LodMesh::y_bounds`
+    y_bounds: [f32; 2],
+}
+
+impl LodRenderDescriptor {
+    fn new(device: &wgpu::Device, lod_mesh: &lod::LodMesh) -> Self {
+        Self {
+            annotated_instance_buffers: lod_mesh
+                .typed_instances_vec
+                .iter()
+                .map(|typed_instances| {
+                    AnnotatedInstanceBuffer::new(device, typed_instances, lod_mesh.idx.first_chunk())
+                })
+                .collect(),
+            y_bounds: lod_mesh.y_bounds,
+        }
+    }
+
+    // Tile now draws a different set of chunks, e.g. after the camera moved
+    fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, lod_mesh: &lod::LodMesh) {
+        self.y_bounds = lod_mesh.y_bounds;
+        for (instance_buffer, typed_instances) in self
+            .annotated_instance_buffers
+            .iter_mut()
+            .zip(lod_mesh.typed_instances_vec.iter())
+        {
+            instance_buffer.update(device, queue, typed_instances, lod_mesh.idx.first_chunk());
+        }
+    }
+}
+
+// What survived culling this frame, see `Game::cull_chunks`
+struct CulledChunks {
+    // In `chunk_order`
+    camera_chunks: Vec<[usize; 2]>,
+    // Same chunks farthest first, so their translucent faces blend over the ones behind them
+    translucent_chunks: Vec<[usize; 2]>,
+    // One list per shadow cascade, in `chunk_order`
+    shadow_chunks: Vec<Vec<[usize; 2]>>,
+    // Farthest first
+    lod_tiles: Vec<lod::LodTileIdx>,
+}
+
+// Terrain past the full resolution chunks doesn't cast shadows, see lod.rs
+// `--shadows=low`, `medium` or `high` on native, see `light::ShadowQuality`
+fn initial_shadow_quality() -> light::ShadowQuality {
+    #[cfg(not(target_arch = "wasm32"))]
+    for arg in std::env::args() {
+        if let Some(name) = arg.strip_prefix("--shadows=") {
+            match light::ShadowQuality::from_name(name) {
+                Some(shadow_quality) => return shadow_quality,
+                None => log::warn!("Unknown shadow quality: {}", name),
+            }
+        }
+    }
+    light::DEFAULT_SHADOW_QUALITY
+}
+
+fn shadow_distance(world_state: &world::WorldState) -> f32 {
+    (world_state.visible_chunk_width() / 2 * CHUNK_XZ_SIZE) as f32
 }
 
 impl State {
     async fn new(window: &winit::window::Window) -> Self {
+        Self::create(Some(window), window.inner_size()).await
+    }
+
+    // Renders into a texture instead of a window, see `frame_timing::time_frames_headless`
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn new_headless(size: winit::dpi::PhysicalSize<u32>) -> Self {
+        Self::create(None, size).await
+    }
+
+    async fn create(
+        window: Option<&winit::window::Window>,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Self {
         cfg_if::cfg_if! {
             if #[cfg(target_arch = "wasm32")] {
-               let backend = wgpu::Backends::SECONDARY;
+               let mut backend = wgpu::Backends::SECONDARY;
             } else {
-               let backend = wgpu::Backends::PRIMARY;
+               let mut backend = wgpu::Backends::PRIMARY;
             }
         };
+        // Offscreen, also take OpenGL, e.g. a software renderer on a machine without a GPU
+        if window.is_none() {
+            backend |= wgpu::Backends::GL;
+        }
         let instance = wgpu::Instance::new(backend);
 
-        let size = window.inner_size();
-        let surface = unsafe {
-            let surface = instance.create_surface(&window);
-            surface
-        };
+        let surface = window.map(|window| unsafe { instance.create_surface(window) });
 
         log::warn!("WGPU setup");
         let adapter =
-            wgpu::util::initialize_adapter_from_env_or_default(&instance, backend, Some(&surface))
+            wgpu::util::initialize_adapter_from_env_or_default(&instance, backend, surface.as_ref())
                 .await
                 .expect("No suitable GPU adapters found on the system!");
 
@@ -164,9 +475,6 @@ impl State {
             .await
             .expect("Unable to find a suitable GPU adapter!");
 
-        let supported_formats = surface.get_supported_formats(&adapter);
-        log::warn!("Supported formats: {:?}", supported_formats);
-
         cfg_if::cfg_if! {
             if #[cfg(target_arch = "wasm32")] {
                let chosen_format = wgpu::TextureFormat::Rgba8UnormSrgb;
@@ -175,7 +483,16 @@ impl State {
             }
         };
 
-        assert!(supported_formats.contains(&chosen_format));
+        let chosen_format = match &surface {
+            Some(surface) => {
+                let supported_formats = surface.get_supported_formats(&adapter);
+                log::warn!("Supported formats: {:?}", supported_formats);
+                assert!(supported_formats.contains(&chosen_format));
+                chosen_format
+            }
+            // Same as on the web, every backend can render to it
+            None => wgpu::TextureFormat::Rgba8UnormSrgb,
+        };
 
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -185,13 +502,15 @@ impl State {
             present_mode: wgpu::PresentMode::Fifo,
             alpha_mode: wgpu::CompositeAlphaMode::Opaque,
         };
-        surface.configure(&device, &surface_config);
+        if let Some(surface) = &surface {
+            surface.configure(&device, &surface_config);
+        }
 
         let camera_controller = camera::CameraController::new(0.15, 0.01);
 
         // Start in the center
         let center = world::get_world_center();
-        let zfar = 250.0;
+        let zfar = lod::view_distance(world::DEFAULT_VISIBLE_CHUNK_WIDTH);
         let camera = camera::Camera::new(
             Point3::<f32>::new(center.x as f32, center.y as f32, center.z as f32),
             // have it look at the origin
@@ -205,33 +524,40 @@ impl State {
             zfar,
         );
 
-        let sunlight_pos = glam::Vec3::new(40.0, 30.0, 40.0);
-
-        let scale_factor = 1.0;
-        let sunlight_ortho_proj_coords = vertex::CuboidCoords {
-            left: -(CHUNK_XZ_SIZE as f32 * scale_factor * 2.0),
-            right: CHUNK_XZ_SIZE as f32 * scale_factor * 2.0,
-            bottom: -(CHUNK_XZ_SIZE as f32 * scale_factor * 2.0),
-            top: CHUNK_XZ_SIZE as f32 * scale_factor,
-            near: 0.0,
-            // Can't be too far or z-depth values won't have enough precision
-            far: 125.0,
-        };
-
-        // Light
-        let light_uniform = light::LightUniform::new(
-            [0.0, 5.0, 0.0].into(),
-            [1.0, 1.0, 1.0].into(),
-            sunlight_pos,
-            sunlight_ortho_proj_coords,
-            [2048, 2048],
-        );
-
         let mut camera_uniform = camera::CameraUniform::new();
         camera_uniform.update_view_proj(&camera);
 
         let mut world_state = world::WorldState::new();
+        // The page restores the time of day on the web, once the game is running
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(time_of_day) =
+            world_clock::load_time_of_day(std::path::Path::new(world_clock::TIME_OF_DAY_SAVE_PATH))
+        {
+            world_state.world_clock.set_time_of_day(time_of_day);
+        }
+        // Same for the inventory
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(inventory) =
+            inventory::load_inventory(std::path::Path::new(inventory::INVENTORY_SAVE_PATH))
+        {
+            world_state.inventory = inventory;
+        }
         world_state.initial_setup(&camera);
+        camera_uniform.update_fog(&fog::Fog::for_view(
+            &camera,
+            &world_state.world_clock,
+            world_state.character_entity.is_underwater,
+        ));
+
+        // Light
+        let shadow_quality = initial_shadow_quality();
+        let mut light_uniform = light::LightUniform::new(
+            &world_state.world_clock,
+            shadow_quality.settings().shadow_map_pixel_size,
+        );
+        light_uniform.update_light_space_proj(&camera, shadow_distance(&world_state));
+
+        let sky_uniform = sky::SkyUniform::new(&camera, &world_state.world_clock);
 
         State {
             surface_config,
@@ -243,13 +569,16 @@ impl State {
             camera_controller,
             camera_uniform,
             light_uniform,
+            sky_uniform,
+            shadow_quality,
             world_state,
+            chunk_jobs: jobs::ChunkJobs::new(),
         }
     }
 }
 
 impl Scene {
-    async fn new(initial_state: &mut State) -> Self {
+    async fn new(initial_state: &mut State, resource_pack: &resource_pack::ResourcePack) -> Self {
         let surface_config = &initial_state.surface_config;
         let device = &initial_state.device;
         let queue = &initial_state.queue;
@@ -308,7 +637,7 @@ impl Scene {
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
                         },
                         count: None,
@@ -354,129 +683,534 @@ impl Scene {
                 }],
             });
 
-        let vertex_buffer_layouts = &[vertex::Vertex::desc(), instance::InstanceRaw::desc()];
+        let shadow_cascade_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            mem::size_of::<light::ShadowCascadeRaw>() as u64,
+                        ),
+                    },
+                    count: None,
+                }],
+            });
 
-        let shadow_map_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shadow Map Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shadow_map.wgsl"))),
-        });
+        let ShaderPipelines {
+            shadow_map_pipeline,
+            opaque_pipeline,
+            translucent_pipeline,
+            pipeline_solid_color,
+            pipeline_wire,
+            pipeline_wire_no_instancing,
+        } = Self::create_shader_pipelines(
+            device,
+            surface_config,
+            &SceneBindGroupLayouts {
+                albedo_only_texture: &albedo_only_texture_bind_group_layout,
+                texture: &texture_bind_group_layout,
+                camera: &camera_bind_group_layout,
+                light: &light_bind_group_layout,
+                shadow_cascade: &shadow_cascade_bind_group_layout,
+            },
+            initial_state.shadow_quality.settings(),
+        );
 
-        // The main shader is a handlebars template so we can change some compile-time constants
-        let handlebars = handlebars::Handlebars::new();
-        let shader_wgsl_str = handlebars
-            .render_template(
-                include_str!("shader.wgsl"),
-                &serde_json::json!({
-                    "z_far": world::Z_FAR,
-                    "z_fade_start": world::Z_FADE_START,
-                }),
-            )
-            .expect("Failed to render shader template");
+        let sky_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(
+                                mem::size_of::<sky::SkyUniform>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    // Cloud Map
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Main Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&shader_wgsl_str)),
+        let sky_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sky Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("sky.wgsl"))),
         });
 
-        log::info!("Creating shadow map render pipeline");
-        let shadow_map_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
+        log::info!("Creating sky render pipeline");
+        let sky_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
             layout: Some(
                 &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: None,
-                    bind_group_layouts: &[
-                        &camera_bind_group_layout,
-                        &light_bind_group_layout,
-                        &albedo_only_texture_bind_group_layout,
-                    ],
+                    bind_group_layouts: &[&camera_bind_group_layout, &sky_bind_group_layout],
                     push_constant_ranges: &[],
                 }),
             ),
             vertex: wgpu::VertexState {
-                module: &shadow_map_shader,
+                module: &sky_shader,
                 entry_point: "vs_main",
-                buffers: vertex_buffer_layouts,
+                buffers: &[],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shadow_map_shader,
+                module: &sky_shader,
                 entry_point: "fs_main",
-                targets: &[],
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
             }),
-            primitive: wgpu::PrimitiveState {
-                cull_mode: Some(wgpu::Face::Back),
-                ..Default::default()
-            },
+            primitive: wgpu::PrimitiveState::default(),
+            // Only clouds write a depth closer than the far plane
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: texture::Texture::DEPTH_FORMAT,
                 depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare: wgpu::CompareFunction::Always,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState::default(),
             multiview: None,
         });
-        log::info!("Shadow map render pipeline complete");
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[
-                &texture_bind_group_layout,
-                &camera_bind_group_layout,
-                &light_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
+        let light_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light VB"),
+            contents: bytemuck::cast_slice(&[light_uniform.to_raw()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let create_forward_pass_pipeline = |cull_mode: Option<wgpu::Face>| {
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: vertex_buffer_layouts,
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: surface_config.format,
-                        blend: Some(wgpu::BlendState {
-                            color: wgpu::BlendComponent {
-                                operation: wgpu::BlendOperation::Add,
-                                src_factor: wgpu::BlendFactor::SrcAlpha,
-                                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            },
-                            alpha: wgpu::BlendComponent::REPLACE,
-                        }),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    cull_mode,
-                    ..Default::default()
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: texture::Texture::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            })
-        };
-        log::info!("Creating forward-pass opaque render pipeline");
-        let opaque_pipeline = create_forward_pass_pipeline(Some(wgpu::Face::Back));
+        let face = face::Face::new();
+        let sunlight_vtx_data = light_uniform.vertex_data_for_sunlight();
+        let character_vtx_data = world_state.character_entity.vertex_data();
 
-        log::info!("Creating forward-pass translucent render pipeline");
-        let translucent_pipeline = create_forward_pass_pipeline(None);
+        let vertex_buffers = VertexBufers {
+            blocks: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Main Vertex Buffer"),
+                contents: bytemuck::cast_slice(&face.vertex_data),
+                usage: wgpu::BufferUsages::VERTEX,
+            }),
+            light_volume: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Light Volume Vertex Buffer"),
+                contents: bytemuck::cast_slice(&sunlight_vtx_data.vertex_data),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }),
+            character_entity: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Character Entity Vertex Buffer"),
+                contents: bytemuck::cast_slice(&character_vtx_data.vertex_data),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }),
+        };
 
-        let pipeline_solid_color = if RENDER_CHARACTER_ENTITY {
-            Some(
-                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        let index_buffers = IndexBufers {
+            blocks: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Main Index Buffer"),
+                contents: bytemuck::cast_slice(&face.index_data),
+                usage: wgpu::BufferUsages::INDEX,
+            }),
+            light_volume: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Light Volume Index Buffer"),
+                contents: bytemuck::cast_slice(&sunlight_vtx_data.index_data),
+                usage: wgpu::BufferUsages::INDEX,
+            }),
+            character_entity: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Character Entity Index Buffer"),
+                contents: bytemuck::cast_slice(&character_vtx_data.index_data),
+                usage: wgpu::BufferUsages::INDEX,
+            }),
+        };
+
+        let index_counts = IndexCounts {
+            blocks: face.index_data.len(),
+            light_volume: sunlight_vtx_data.index_data.len(),
+            character_entity: character_vtx_data.index_data.len(),
+        };
+
+        let texture_atlas = texture::Texture::create_pixel_art_texture(
+            &resource_pack.build_atlas(),
+            device,
+            queue,
+            "Texture Atlas",
+        );
+
+        let break_overlay_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Break Overlay Instance Buffer"),
+            size: (world::Face::ALL.len() * instance::InstanceRaw::size()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let entity_instances_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Entity Instance Buffer"),
+            size: (entity::MAX_ENTITY_INSTANCES * instance::InstanceRaw::size())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Camera
+        let camera_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_staging_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Staging Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Shadow Map
+        let (shadow_map_texture, shadow_map_layer_views) =
+            Self::create_shadow_map(device, light_uniform.shadow_map_pixel_size);
+
+        // Create bind groups
+        let (albedo_only_texture_bind_group, texture_bind_group) = Self::create_texture_bind_groups(
+            device,
+            &albedo_only_texture_bind_group_layout,
+            &texture_bind_group_layout,
+            &texture_atlas,
+            &shadow_map_texture,
+        );
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buf.as_entire_binding(),
+            }],
+            label: None,
+        });
+        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &light_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buf.as_entire_binding(),
+            }],
+            label: None,
+        });
+
+        let sky_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Buffer"),
+            contents: bytemuck::cast_slice(&[initial_state.sky_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let cloud_map_texture =
+            sky::create_cloud_map_texture(device, queue, map_generation::WORLD_SEED + 1);
+        let sky_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &sky_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sky_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&cloud_map_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&cloud_map_texture.sampler),
+                },
+            ],
+            label: None,
+        });
+        let shadow_cascade_bufs = (0..light::NUM_SHADOW_CASCADES)
+            .map(|cascade_idx| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&*format!("Shadow Cascade {} Buffer", cascade_idx)),
+                    contents: bytemuck::cast_slice(&[light_uniform.shadow_cascade_raw(cascade_idx)]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                })
+            })
+            .collect::<Vec<_>>();
+        let shadow_cascade_bind_groups = shadow_cascade_bufs
+            .iter()
+            .map(|shadow_cascade_buf| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &shadow_cascade_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: shadow_cascade_buf.as_entire_binding(),
+                    }],
+                    label: None,
+                })
+            })
+            .collect();
+
+        let (all_chunk_data, chunk_order) = world_state.generate_world_data(&camera);
+        initial_state.chunk_jobs.set_chunk_order(&chunk_order);
+        // Far terrain shows up as its tiles get meshed in the background
+        initial_state
+            .chunk_jobs
+            .set_lod_tiles(lod::lod_tiles_around(
+                &initial_state.camera,
+                world_state.visible_chunk_width(),
+            ));
+        let chunk_dims = all_chunk_data.dims();
+
+        let mut chunk_render_descriptors: Vec<ChunkRenderDescriptor> = vec![];
+
+        #[cfg(target_arch = "wasm32")]
+        let mut chunk_i = 0;
+        #[cfg(target_arch = "wasm32")]
+        let num_chunks = chunk_dims[0] * chunk_dims[1];
+        for (chunk_x, chunk_z) in iproduct!(0..chunk_dims[0], 0..chunk_dims[1]) {
+            log::info!("Creating chunk render descriptor for {},{}", chunk_x, chunk_z);
+
+            let chunk_data = &all_chunk_data[[chunk_x, chunk_z]];
+
+            let mut annotated_instance_buffers: Vec<AnnotatedInstanceBuffer> = vec![];
+            for typed_instances in &chunk_data.typed_instances_vec {
+                annotated_instance_buffers.push(AnnotatedInstanceBuffer::new(
+                    device,
+                    typed_instances,
+                    [chunk_x, chunk_z],
+                ));
+            }
+
+            let mut chunk_render_descriptor = ChunkRenderDescriptor {
+                world_chunk_position: chunk_data.position,
+                annotated_instance_buffers,
+                y_bounds: chunk_data.y_bounds,
+                connectivity: chunk_data.connectivity.clone(),
+                translucent_instances: vec![],
+                translucent_sorted_from: chunk_data.translucent_sorted_from,
+            };
+            chunk_render_descriptor.set_translucent_instances(chunk_data);
+            chunk_render_descriptors.push(chunk_render_descriptor);
+            let render_descriptor_idx = chunk_render_descriptors.len() - 1;
+            world_state.set_render_descriptor_idx(chunk_data.position, render_descriptor_idx);
+            
+            #[cfg(target_arch = "wasm32")] {
+                wasm_utils::js_update_game_state_load_progress((chunk_i + 1) as f64 / num_chunks as f64);
+                chunk_i += 1;
+                // Yield to JS to prevent page from getting stuck
+                wasm_utils::yield_().await;
+            }
+        }
+
+        let depth_texture = texture::Texture::create_depth_texture(
+            "depth_texture",
+            &device,
+            [surface_config.width, surface_config.height],
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                ..Default::default()
+            },
+        );
+
+        Scene {
+            vertex_buffers,
+            index_buffers,
+            index_counts,
+            albedo_only_texture_bind_group_layout,
+            texture_bind_group_layout,
+            camera_bind_group_layout,
+            light_bind_group_layout,
+            shadow_cascade_bind_group_layout,
+            texture_atlas,
+            albedo_only_texture_bind_group,
+            texture_bind_group,
+            camera_bind_group,
+            light_bind_group,
+            camera_buf,
+            camera_staging_buf,
+            light_buf,
+            chunk_render_descriptors,
+            chunk_order,
+            lod_render_descriptors: HashMap::new(),
+            depth_texture,
+            opaque_pipeline,
+            translucent_pipeline,
+
+            break_overlay_buf,
+            break_overlay: None,
+            entity_instances_buf,
+            entity_instance_count: 0,
+
+            shadow_map_pipeline,
+            shadow_map_texture,
+            shadow_map_layer_views,
+            shadow_cascade_bufs,
+            shadow_cascade_bind_groups,
+
+            sky_pipeline,
+            sky_buf,
+            sky_bind_group,
+            pipeline_wire,
+            pipeline_wire_no_instancing,
+            pipeline_solid_color,
+
+            culling_stats: culling::CullingStats::default(),
+        }
+    }
+
+    // Builds the pipelines that use the shader templates, which depend on the shadow settings
+    fn create_shader_pipelines(
+        device: &wgpu::Device,
+        surface_config: &wgpu::SurfaceConfiguration,
+        layouts: &SceneBindGroupLayouts,
+        shadow_settings: light::ShadowSettings,
+    ) -> ShaderPipelines {
+        let vertex_buffer_layouts = &[vertex::Vertex::desc(), instance::InstanceRaw::desc()];
+
+        // Shaders are handlebars templates so we can pick the shadow filter and fill in the atlas
+        // layout
+        let handlebars = handlebars::Handlebars::new();
+        let shader_template_data = serde_json::json!({
+            "shadow_filter": shadow_settings.filter.template_name(),
+            "atlas": atlas::shader_template_data(),
+        });
+        let shadow_map_wgsl_str = handlebars
+            .render_template(include_str!("shadow_map.wgsl"), &shader_template_data)
+            .expect("Failed to render shadow map shader template");
+        let shader_wgsl_str = handlebars
+            .render_template(include_str!("shader.wgsl"), &shader_template_data)
+            .expect("Failed to render shader template");
+
+        let shadow_map_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Map Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&shadow_map_wgsl_str)),
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Main Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&shader_wgsl_str)),
+        });
+
+        log::info!("Creating shadow map render pipeline");
+        let shadow_map_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(
+                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[
+                        layouts.camera,
+                        layouts.shadow_cascade,
+                        layouts.albedo_only_texture,
+                    ],
+                    push_constant_ranges: &[],
+                }),
+            ),
+            vertex: wgpu::VertexState {
+                module: &shadow_map_shader,
+                entry_point: "vs_main",
+                buffers: vertex_buffer_layouts,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shadow_map_shader,
+                entry_point: "fs_main",
+                targets: &[],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        log::info!("Shadow map render pipeline complete");
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[
+                layouts.texture,
+                layouts.camera,
+                layouts.light,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let create_forward_pass_pipeline = |cull_mode: Option<wgpu::Face>| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: vertex_buffer_layouts,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: surface_config.format,
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                operation: wgpu::BlendOperation::Add,
+                                src_factor: wgpu::BlendFactor::SrcAlpha,
+                                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                            },
+                            alpha: wgpu::BlendComponent::REPLACE,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: texture::Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        log::info!("Creating forward-pass opaque render pipeline");
+        let opaque_pipeline = create_forward_pass_pipeline(Some(wgpu::Face::Back));
+
+        log::info!("Creating forward-pass translucent render pipeline");
+        let translucent_pipeline = create_forward_pass_pipeline(None);
+
+        let pipeline_solid_color = if RENDER_CHARACTER_ENTITY {
+            Some(
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                     label: None,
                     layout: Some(&pipeline_layout),
                     vertex: wgpu::VertexState {
@@ -574,84 +1308,26 @@ impl Scene {
         let pipeline_wire = create_wire_pipeline("vs_main", Some(wgpu::Face::Back));
         let pipeline_wire_no_instancing = create_wire_pipeline("vs_wire_no_instancing", None);
 
-        let light_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Light VB"),
-            contents: bytemuck::cast_slice(&[light_uniform.to_raw()]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let face = face::Face::new();
-        let sunlight_vtx_data = light_uniform.vertex_data_for_sunlight();
-        let character_vtx_data = world_state.character_entity.vertex_data();
-
-        let vertex_buffers = VertexBufers {
-            blocks: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Main Vertex Buffer"),
-                contents: bytemuck::cast_slice(&face.vertex_data),
-                usage: wgpu::BufferUsages::VERTEX,
-            }),
-            light_volume: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Light Volume Vertex Buffer"),
-                contents: bytemuck::cast_slice(&sunlight_vtx_data.vertex_data),
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            }),
-            character_entity: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Character Entity Vertex Buffer"),
-                contents: bytemuck::cast_slice(&character_vtx_data.vertex_data),
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            }),
-        };
-
-        let index_buffers = IndexBufers {
-            blocks: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Main Index Buffer"),
-                contents: bytemuck::cast_slice(&face.index_data),
-                usage: wgpu::BufferUsages::INDEX,
-            }),
-            light_volume: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Light Volume Index Buffer"),
-                contents: bytemuck::cast_slice(&sunlight_vtx_data.index_data),
-                usage: wgpu::BufferUsages::INDEX,
-            }),
-            character_entity: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Character Entity Index Buffer"),
-                contents: bytemuck::cast_slice(&character_vtx_data.index_data),
-                usage: wgpu::BufferUsages::INDEX,
-            }),
-        };
-
-        let index_counts = IndexCounts {
-            blocks: face.index_data.len(),
-            light_volume: sunlight_vtx_data.index_data.len(),
-            character_entity: character_vtx_data.index_data.len(),
-        };
-
-        let texture_atlas = texture::Texture::create_pixel_art_image_texture(
-            include_bytes!("../assets/minecruft_atlas.png"),
-            device,
-            queue,
-            "Texture Atlas",
-        );
-
-        // Camera
-        let camera_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[camera_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-        let camera_staging_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Staging Buffer"),
-            contents: bytemuck::cast_slice(&[camera_uniform]),
-            usage: wgpu::BufferUsages::UNIFORM
-                | wgpu::BufferUsages::COPY_SRC
-                | wgpu::BufferUsages::COPY_DST,
-        });
+        ShaderPipelines {
+            shadow_map_pipeline,
+            opaque_pipeline,
+            translucent_pipeline,
+            pipeline_solid_color,
+            pipeline_wire,
+            pipeline_wire_no_instancing,
+        }
+    }
 
-        // Shadow Map
-        let shadow_map_texture = texture::Texture::create_depth_texture(
+    // One layer per cascade, see `light::ShadowCascade`
+    fn create_shadow_map(
+        device: &wgpu::Device,
+        shadow_map_pixel_size: [u32; 2],
+    ) -> (texture::Texture, Vec<wgpu::TextureView>) {
+        texture::Texture::create_depth_texture_array(
             "shadow_map_texture",
-            &device,
-            light_uniform.shadow_map_pixel_size,
+            device,
+            shadow_map_pixel_size,
+            light::NUM_SHADOW_CASCADES as u32,
             &wgpu::SamplerDescriptor {
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -665,11 +1341,18 @@ impl Scene {
                 lod_max_clamp: 100.0,
                 ..Default::default()
             },
-        );
+        )
+    }
 
-        // Create bind groups
+    fn create_texture_bind_groups(
+        device: &wgpu::Device,
+        albedo_only_texture_bind_group_layout: &wgpu::BindGroupLayout,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        texture_atlas: &texture::Texture,
+        shadow_map_texture: &texture::Texture,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
         let albedo_only_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &albedo_only_texture_bind_group_layout,
+            layout: albedo_only_texture_bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -683,7 +1366,7 @@ impl Scene {
             label: None,
         });
         let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
+            layout: texture_bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -704,148 +1387,307 @@ impl Scene {
             ],
             label: None,
         });
-        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buf.as_entire_binding(),
-            }],
-            label: None,
-        });
-        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buf.as_entire_binding(),
-            }],
-            label: None,
-        });
+        (albedo_only_texture_bind_group, texture_bind_group)
+    }
+
+    // Shows the cracks of the block being mined, or hides them
+    fn set_break_overlay(&mut self, queue: &wgpu::Queue, mining_progress: mining::MiningProgress) {
+        let break_overlay = match mining_progress {
+            mining::MiningProgress::Mining {
+                block_pos,
+                break_stage,
+            } => Some((block_pos, break_stage)),
+            _ => None,
+        };
+        if break_overlay == self.break_overlay {
+            return;
+        }
+        if let Some((block_pos, break_stage)) = break_overlay {
+            queue.write_buffer(
+                &self.break_overlay_buf,
+                0,
+                bytemuck::cast_slice(&mining::break_overlay_instances(block_pos, break_stage)),
+            );
+        }
+        self.break_overlay = break_overlay;
+    }
+
+    // Entities move and animate, so they're rewritten every tick
+    fn set_entities(&mut self, queue: &wgpu::Queue, entities: &Entities) {
+        let instances = entities.instances();
+        queue.write_buffer(&self.entity_instances_buf, 0, bytemuck::cast_slice(&instances));
+        self.entity_instance_count = instances.len() as u32;
+    }
+
+    // Rebuilds the block atlas from `resource_pack` and points the texture bind groups at it
+    fn set_resource_pack(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resource_pack: &resource_pack::ResourcePack,
+    ) {
+        self.texture_atlas = texture::Texture::create_pixel_art_texture(
+            &resource_pack.build_atlas(),
+            device,
+            queue,
+            "Texture Atlas",
+        );
+        (self.albedo_only_texture_bind_group, self.texture_bind_group) =
+            Self::create_texture_bind_groups(
+                device,
+                &self.albedo_only_texture_bind_group_layout,
+                &self.texture_bind_group_layout,
+                &self.texture_atlas,
+                &self.shadow_map_texture,
+            );
+    }
+
+    // Rebuilds the pipelines with the new shadow filter and resizes the shadow map
+    fn set_shadow_settings(
+        &mut self,
+        device: &wgpu::Device,
+        surface_config: &wgpu::SurfaceConfiguration,
+        shadow_settings: light::ShadowSettings,
+    ) {
+        ShaderPipelines {
+            shadow_map_pipeline: self.shadow_map_pipeline,
+            opaque_pipeline: self.opaque_pipeline,
+            translucent_pipeline: self.translucent_pipeline,
+            pipeline_solid_color: self.pipeline_solid_color,
+            pipeline_wire: self.pipeline_wire,
+            pipeline_wire_no_instancing: self.pipeline_wire_no_instancing,
+        } = Self::create_shader_pipelines(
+            device,
+            surface_config,
+            &SceneBindGroupLayouts {
+                albedo_only_texture: &self.albedo_only_texture_bind_group_layout,
+                texture: &self.texture_bind_group_layout,
+                camera: &self.camera_bind_group_layout,
+                light: &self.light_bind_group_layout,
+                shadow_cascade: &self.shadow_cascade_bind_group_layout,
+            },
+            shadow_settings,
+        );
+
+        (self.shadow_map_texture, self.shadow_map_layer_views) =
+            Self::create_shadow_map(device, shadow_settings.shadow_map_pixel_size);
+        (self.albedo_only_texture_bind_group, self.texture_bind_group) =
+            Self::create_texture_bind_groups(
+                device,
+                &self.albedo_only_texture_bind_group_layout,
+                &self.texture_bind_group_layout,
+                &self.texture_atlas,
+                &self.shadow_map_texture,
+            );
+    }
+    // Follows the camera or a new render distance: chunks that came into view take over the render
+    // descriptors of chunks that went out of view, and the pool grows or shrinks by the difference
+    fn update_visible_chunks(&mut self, state: &mut State) {
+        let new_chunk_order = state.world_state.get_chunk_order_by_distance(&state.camera);
+
+        let new_chunk_order_hashset = new_chunk_order.iter().cloned().collect::<HashSet<_>>();
+        let old_chunk_order_hashset = self.chunk_order.iter().cloned().collect::<HashSet<_>>();
+
+        let new_chunks = (&new_chunk_order_hashset - &old_chunk_order_hashset)
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let old_chunks = (&old_chunk_order_hashset - &new_chunk_order_hashset)
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for (i, &new_chunk) in new_chunks.iter().enumerate() {
+            state.world_state.allocate_chunk_neighborhood(new_chunk);
 
-        let (all_chunk_data, chunk_order) = world_state.generate_world_data(&camera);
-        let chunk_dims = all_chunk_data.dims();
+            let render_descriptor_idx = match old_chunks.get(i) {
+                Some(&old_chunk) => {
+                    let render_descriptor_idx =
+                        state.world_state.get_render_descriptor_idx(old_chunk);
+                    state
+                        .world_state
+                        .set_render_descriptor_idx(old_chunk, world::NO_RENDER_DESCRIPTOR_INDEX);
+                    render_descriptor_idx
+                }
+                // Instance buffers are created once the chunk's mesh is ready
+                None => {
+                    self.chunk_render_descriptors.push(ChunkRenderDescriptor {
+                        world_chunk_position: new_chunk,
+                        annotated_instance_buffers: vec![],
+                        y_bounds: [0.0, 0.0],
+                        connectivity: occlusion::ChunkConnectivity::ALL,
+                        translucent_instances: vec![],
+                        translucent_sorted_from: state.camera.eye,
+                    });
+                    self.chunk_render_descriptors.len() - 1
+                }
+            };
+            state
+                .world_state
+                .set_render_descriptor_idx(new_chunk, render_descriptor_idx);
+
+            // Draw nothing until the new chunk's mesh is ready
+            let chunk_render_descriptor = &mut self.chunk_render_descriptors[render_descriptor_idx];
+            chunk_render_descriptor.world_chunk_position = new_chunk;
+            for instance_buffer in chunk_render_descriptor
+                .annotated_instance_buffers
+                .iter_mut()
+            {
+                instance_buffer.len = 0;
+            }
+            chunk_render_descriptor.y_bounds = [0.0, 0.0];
+            chunk_render_descriptor.connectivity = occlusion::ChunkConnectivity::ALL;
+            chunk_render_descriptor.translucent_instances.clear();
+        }
 
-        let mut chunk_render_descriptors: Vec<ChunkRenderDescriptor> = vec![];
+        // Render distance shrank, free the descriptors nobody took over. The last descriptor moves
+        // into the freed slot, so its chunk needs to point at the new index.
+        for &old_chunk in old_chunks.iter().skip(new_chunks.len()) {
+            let render_descriptor_idx = state.world_state.get_render_descriptor_idx(old_chunk);
+            state
+                .world_state
+                .set_render_descriptor_idx(old_chunk, world::NO_RENDER_DESCRIPTOR_INDEX);
+            self.chunk_render_descriptors
+                .swap_remove(render_descriptor_idx);
+            if let Some(moved_descriptor) = self.chunk_render_descriptors.get(render_descriptor_idx)
+            {
+                state.world_state.set_render_descriptor_idx(
+                    moved_descriptor.world_chunk_position,
+                    render_descriptor_idx,
+                );
+            }
+        }
 
+        self.chunk_order = new_chunk_order;
+        state.chunk_jobs.set_chunk_order(&self.chunk_order);
+        for chunk_idx in new_chunks {
+            state.chunk_jobs.request_mesh(chunk_idx);
+        }
+
+        // Tiles that changed keep drawing their old mesh until the new one is ready
+        let lod_tiles =
+            lod::lod_tiles_around(&state.camera, state.world_state.visible_chunk_width());
+        self.lod_render_descriptors
+            .retain(|idx, _| lod_tiles.contains_key(idx));
+        state.chunk_jobs.set_lod_tiles(lod_tiles);
+    }
+}
+
+impl Game {
+    // Changes the render distance, in chunks along each side of the full resolution terrain
+    fn set_visible_chunk_width(&mut self, visible_chunk_width: usize) {
+        let state = &mut self.state;
+        if !state.world_state.set_visible_chunk_width(visible_chunk_width) {
+            return;
+        }
+
+        let visible_chunk_width = state.world_state.visible_chunk_width();
+        log::info!("Render distance set to {} chunks", visible_chunk_width);
         #[cfg(target_arch = "wasm32")]
-        let mut chunk_i = 0;
-        #[cfg(target_arch = "wasm32")]
-        let num_chunks = chunk_dims[0] * chunk_dims[1];
-        for (chunk_x, chunk_z) in iproduct!(0..chunk_dims[0], 0..chunk_dims[1]) {
-            log::info!("Creating chunk render descriptor for {},{}", chunk_x, chunk_z);
+        wasm_utils::js_handle_render_distance_changed(visible_chunk_width);
+        state.camera.zfar = lod::view_distance(visible_chunk_width);
+        state.camera.update_frustum();
+        self.scene.update_visible_chunks(state);
+    }
 
-            let chunk_data = &all_chunk_data[[chunk_x, chunk_z]];
+    fn set_shadow_quality(&mut self, shadow_quality: light::ShadowQuality) {
+        let state = &mut self.state;
+        if shadow_quality == state.shadow_quality {
+            return;
+        }
 
-            let mut annotated_instance_buffers: Vec<AnnotatedInstanceBuffer> = vec![];
-            for typed_instances in &chunk_data.typed_instances_vec {
-                let instance_byte_contents: &[u8] =
-                    bytemuck::cast_slice(&typed_instances.instance_data);
-
-                const NUM_FACES: usize = 6;
-
-                // Divide by 2 since worst case is a "3D checkerboard" where every other space is filled
-                let mut max_number_faces_possible =
-                    world::NUM_BLOCKS_IN_CHUNK * instance::InstanceRaw::size() * NUM_FACES / 2;
-                // HACK(aleks) divide by 16 because too much memory
-                max_number_faces_possible /= 16;
-
-                let unpadded_size: u64 = max_number_faces_possible.try_into().unwrap();
-
-                // Valid vulkan usage is
-                // 1. buffer size must be a multiple of COPY_BUFFER_ALIGNMENT.
-                // 2. buffer size must be greater than 0.
-                // Therefore we round the value up to the nearest multiple, and ensure it's at least COPY_BUFFER_ALIGNMENT.
-                let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
-                let padded_size =
-                    ((unpadded_size + align_mask) & !align_mask).max(wgpu::COPY_BUFFER_ALIGNMENT);
-
-                let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                    label: Some(&*format!(
-                        "Instance Buffer {:?} {},{}",
-                        typed_instances.data_type, chunk_x, chunk_z
-                    )),
-                    size: padded_size,
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                    mapped_at_creation: true,
-                });
-
-                instance_buffer.slice(..).get_mapped_range_mut()
-                    [..instance_byte_contents.len() as usize]
-                    .copy_from_slice(instance_byte_contents);
-                instance_buffer.unmap();
-
-                annotated_instance_buffers.push(AnnotatedInstanceBuffer {
-                    buffer: instance_buffer,
-                    len: typed_instances.instance_data.len(),
-                    data_type: typed_instances.data_type.clone(),
-                });
-            }
+        log::info!("Shadow quality set to {:?}", shadow_quality);
+        state.shadow_quality = shadow_quality;
+        let shadow_settings = shadow_quality.settings();
+        state.light_uniform.shadow_map_pixel_size = shadow_settings.shadow_map_pixel_size;
+        self.scene
+            .set_shadow_settings(&state.device, &state.surface_config, shadow_settings);
+    }
 
-            chunk_render_descriptors.push(ChunkRenderDescriptor {
-                world_chunk_position: chunk_data.position,
-                annotated_instance_buffers,
-            });
-            let render_descriptor_idx = chunk_render_descriptors.len() - 1;
-            world_state.set_render_descriptor_idx(chunk_data.position, render_descriptor_idx);
-            
-            #[cfg(target_arch = "wasm32")] {
-                wasm_utils::js_update_game_state_load_progress((chunk_i + 1) as f64 / num_chunks as f64);
-                chunk_i += 1;
-                // Yield to JS to prevent page from getting stuck
-                wasm_utils::yield_().await;
+    // Jumps to a time of day, as a fraction of the day, see `WorldClock`
+    fn set_time_of_day(&mut self, time_of_day: f32) {
+        let world_state = &mut self.state.world_state;
+        world_state.world_clock.set_time_of_day(time_of_day);
+        log::info!(
+            "Time of day set to {:.3}",
+            world_state.world_clock.time_of_day()
+        );
+        Self::time_of_day_changed(world_state);
+    }
+
+    // Saves the time of day, so it can be restored next time. On the web the page saves it.
+    fn time_of_day_changed(world_state: &world::WorldState) {
+        let time_of_day = world_state.world_clock.time_of_day();
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                wasm_utils::js_handle_time_of_day_changed(time_of_day);
+            } else {
+                let path = std::path::Path::new(world_clock::TIME_OF_DAY_SAVE_PATH);
+                if let Err(err) = world_clock::save_time_of_day(path, time_of_day) {
+                    log::warn!("Failed to save time of day: {}", err);
+                }
             }
         }
+    }
 
-        let depth_texture = texture::Texture::create_depth_texture(
-            "depth_texture",
-            &device,
-            [surface_config.width, surface_config.height],
-            &wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Linear,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                compare: Some(wgpu::CompareFunction::LessEqual),
-                lod_min_clamp: -100.0,
-                lod_max_clamp: 100.0,
-                ..Default::default()
-            },
-        );
+    async fn new(window: &winit::window::Window) -> Self {
+        Self::with_state(State::new(window).await).await
+    }
 
-        Scene {
-            vertex_buffers,
-            index_buffers,
-            index_counts,
-            albedo_only_texture_bind_group,
-            texture_bind_group,
-            camera_bind_group,
-            light_bind_group,
-            camera_buf,
-            camera_staging_buf,
-            light_buf,
-            chunk_render_descriptors,
-            chunk_order,
-            depth_texture,
-            opaque_pipeline,
-            translucent_pipeline,
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn new_headless(size: winit::dpi::PhysicalSize<u32>) -> Self {
+        Self::with_state(State::new_headless(size).await).await
+    }
 
-            shadow_map_pipeline,
-            shadow_map_texture,
+    async fn with_state(mut state: State) -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                // The page switches packs once the game is running, see
+                // `dom_controls::load_resource_pack`
+                let resource_pack = resource_pack::ResourcePack::builtin();
+            } else {
+                let resource_pack_path = std::env::var_os(resource_pack::RESOURCE_PACK_ENV_VAR)
+                    .map(std::path::PathBuf::from);
+                let resource_pack = match &resource_pack_path {
+                    Some(path) => resource_pack::ResourcePack::load_or_builtin(path),
+                    None => resource_pack::ResourcePack::builtin(),
+                };
+            }
+        }
+        let scene = Scene::new(&mut state, &resource_pack).await;
 
-            pipeline_wire,
-            pipeline_wire_no_instancing,
-            pipeline_solid_color,
+        Game {
+            state,
+            scene,
+            #[cfg(not(target_arch = "wasm32"))]
+            resource_pack_path,
+            frame_timer: None,
         }
     }
-}
 
-impl Game {
-    async fn new(window: &winit::window::Window) -> Self {
-        let mut state = State::new(&window).await;
-        let scene = Scene::new(&mut state).await;
+    fn set_resource_pack(&mut self, resource_pack: &resource_pack::ResourcePack) {
+        log::info!("Switched to the {} resource pack", resource_pack.name);
+        self.scene
+            .set_resource_pack(&self.state.device, &self.state.queue, resource_pack);
+    }
 
-        Game { state, scene }
+    // Switches to the next pack in `resource_pack::RESOURCE_PACKS_DIR`, and back to the built-in
+    // textures after the last one
+    #[cfg(not(target_arch = "wasm32"))]
+    fn cycle_resource_pack(&mut self) {
+        let pack_paths = resource_pack::available_pack_paths();
+        let next_idx = self
+            .resource_pack_path
+            .as_ref()
+            .and_then(|current_path| pack_paths.iter().position(|path| path == current_path))
+            .map_or(0, |idx| idx + 1);
+        self.resource_pack_path = pack_paths.get(next_idx).cloned();
+
+        let resource_pack = match &self.resource_pack_path {
+            Some(path) => resource_pack::ResourcePack::load_or_builtin(path),
+            None => resource_pack::ResourcePack::builtin(),
+        };
+        self.set_resource_pack(&resource_pack);
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -857,9 +1699,9 @@ impl Game {
                 self.state.surface_config.width,
                 self.state.surface_config.height
             );
-            self.state
-                .surface
-                .configure(&self.state.device, &self.state.surface_config);
+            if let Some(surface) = &self.state.surface {
+                surface.configure(&self.state.device, &self.state.surface_config);
+            }
             self.scene.depth_texture = texture::Texture::create_depth_texture(
                 "depth_texture",
                 &self.state.device,
@@ -888,7 +1730,8 @@ impl Game {
     pub fn update_tick(
         &mut self,
         game_loop: &mut game_loop::GameLoop,
-        left_mouse_clicked: &mut bool,
+        is_mining: bool,
+        attack_clicked: &mut bool,
         right_mouse_clicked: &mut bool,
     ) {
         let state = &mut self.state;
@@ -896,43 +1739,75 @@ impl Game {
 
         state.world_state.physics_tick(game_loop, &state.camera);
 
-        let update_result = state
-            .camera_controller
-            .update_camera(&mut state.camera, &state.world_state);
+        let update_result = match &self.frame_timer {
+            Some(frame_timer) => frame_timer.move_camera(&mut state.camera),
+            None => state
+                .camera_controller
+                .update_camera(&mut state.camera, &state.world_state),
+        };
         state.camera_uniform.update_view_proj(&state.camera);
+        state.camera_uniform.update_fog(&fog::Fog::for_view(
+            &state.camera,
+            &state.world_state.world_clock,
+            state.world_state.character_entity.is_underwater,
+        ));
+        state.camera_uniform.update_time(game_loop.running_time());
         state.queue.write_buffer(
             &scene.camera_staging_buf,
             0,
             bytemuck::cast_slice(&[state.camera_uniform]),
         );
 
-        state.light_uniform.update_light_space_proj(&state.camera);
+        // The clock stands still while frames are timed, so every frame draws the same sky
+        if self.frame_timer.is_none() {
+            let is_new_hour = state
+                .world_state
+                .world_clock
+                .advance(game_loop.fixed_time_step());
+            if is_new_hour {
+                Self::time_of_day_changed(&state.world_state);
+            }
+        }
+        state
+            .light_uniform
+            .update_sky_light(&state.world_state.world_clock);
+        state
+            .light_uniform
+            .update_light_space_proj(&state.camera, shadow_distance(&state.world_state));
+        for (cascade_idx, shadow_cascade_buf) in scene.shadow_cascade_bufs.iter().enumerate() {
+            state.queue.write_buffer(
+                shadow_cascade_buf,
+                0,
+                bytemuck::cast_slice(&[state.light_uniform.shadow_cascade_raw(cascade_idx)]),
+            );
+        }
+        state.light_uniform.point_lights = state.world_state.nearest_point_lights(&state.camera);
+
+        state
+            .sky_uniform
+            .update(&state.camera, &state.world_state.world_clock);
+        state.queue.write_buffer(
+            &scene.sky_buf,
+            0,
+            bytemuck::cast_slice(&[state.sky_uniform]),
+        );
 
-        // HACK: janky way to tell shader that we're under water
-        let mut raw_light_uniform = state.light_uniform.to_raw();
-        raw_light_uniform.is_underwater = state.world_state.character_entity.is_underwater as u32;
         state.queue.write_buffer(
             &scene.light_buf,
             0,
-            bytemuck::cast_slice(&[raw_light_uniform]),
+            bytemuck::cast_slice(&[state.light_uniform.to_raw()]),
         );
 
-        #[derive(PartialEq)]
-        struct ChunkModification {
-            new_chunk: [usize; 2],
-            old_chunk: [usize; 2],
-        }
-        let mut chunk_mods: Vec<ChunkModification> = vec![];
+        // Chunks with placed, broken or highlighted blocks
+        let mut modified_chunks: Vec<[usize; 2]> = vec![];
 
         if update_result.did_translate {
             let chunks_modified = state.world_state.highlight_colliding_block(&state.camera);
-            for chunk_idx in chunks_modified {
-                chunk_mods.push(ChunkModification {
-                    new_chunk: chunk_idx,
-                    old_chunk: chunk_idx,
-                });
-            }
+            modified_chunks.extend(chunks_modified);
+        }
 
+        // The shadow cascades follow the camera's view
+        if update_result.did_move || update_result.did_translate {
             let sunlight_vtx_data = state.light_uniform.vertex_data_for_sunlight();
             state.queue.write_buffer(
                 &scene.vertex_buffers.light_volume,
@@ -942,146 +1817,123 @@ impl Game {
         }
 
         if update_result.did_move_blocks {
-            let chunk_idx = update_result.new_chunk_location;
-            chunk_mods.push(ChunkModification {
-                new_chunk: chunk_idx,
-                old_chunk: chunk_idx,
-            });
+            modified_chunks.push(update_result.new_chunk_location);
         }
 
-        // Break a block with the camera!
-        if *left_mouse_clicked || *right_mouse_clicked {
-            let chunks_modified = if *right_mouse_clicked {
-                state
-                    .world_state
-                    .place_block(&state.camera, state.world_state.place_block_type)
-            } else {
-                state.world_state.break_block(&state.camera)
-            };
-            *left_mouse_clicked = false;
+        // Break a block with the camera, once it's been mined for long enough!
+        let mining_progress = state.world_state.mining_tick(
+            &state.camera,
+            is_mining,
+            *attack_clicked,
+            game_loop.fixed_time_step() as f32,
+        );
+        *attack_clicked = false;
+        scene.set_break_overlay(&state.queue, mining_progress);
+        let mut chunks_modified = vec![];
+        if let mining::MiningProgress::Broken { block_pos } = mining_progress {
+            chunks_modified.extend(state.world_state.harvest_block_at(block_pos));
+        }
+        state
+            .world_state
+            .entities_tick(game_loop.fixed_time_step() as f32);
+        scene.set_entities(&state.queue, &state.world_state.entities);
+        if *right_mouse_clicked {
+            chunks_modified.extend(state.world_state.place_selected_block(&state.camera));
             *right_mouse_clicked = false;
-
-            for chunk_idx in chunks_modified {
-                chunk_mods.push(ChunkModification {
-                    new_chunk: chunk_idx,
-                    old_chunk: chunk_idx,
-                });
-            }
+        }
+        if !chunks_modified.is_empty() {
+            modified_chunks.extend(chunks_modified);
 
             if !update_result.did_move {
                 let chunks_modified = state.world_state.highlight_colliding_block(&state.camera);
-                for chunk_idx in chunks_modified {
-                    chunk_mods.push(ChunkModification {
-                        new_chunk: chunk_idx,
-                        old_chunk: chunk_idx,
-                    });
-                }
+                modified_chunks.extend(chunks_modified);
             }
         }
 
-        if update_result.did_move_chunks {
-            let new_chunk_order = state.world_state.get_chunk_order_by_distance(&state.camera);
+        #[cfg(not(target_arch = "wasm32"))]
+        let chunk_update_time = std::time::Instant::now();
 
-            let new_chunk_order_hashset = new_chunk_order.iter().cloned().collect::<HashSet<_>>();
-            let old_chunk_order_hashset = scene.chunk_order.iter().cloned().collect::<HashSet<_>>();
+        if update_result.did_move_chunks {
+            scene.update_visible_chunks(state);
+        }
 
-            let new_chunks = (&new_chunk_order_hashset - &old_chunk_order_hashset)
-                .iter()
-                .cloned()
-                .collect::<Vec<_>>();
-            let old_chunks = (&old_chunk_order_hashset - &new_chunk_order_hashset)
-                .iter()
-                .cloned()
-                .collect::<Vec<_>>();
-            let neighbors_to_new_chunks = state
-                .world_state
-                .find_chunk_neighbors(&new_chunks, &scene.chunk_order);
+        // Generation and meshing of chunks that came into view happens in the background
+        let mut new_chunk_datas = state
+            .chunk_jobs
+            .update(&mut state.world_state, &state.camera);
+
+        modified_chunks.sort();
+        modified_chunks.dedup();
+        for chunk_idx in modified_chunks {
+            new_chunk_datas.push(state.chunk_jobs.mesh_now(
+                &mut state.world_state,
+                chunk_idx,
+                &state.camera,
+            ));
+        }
 
-            for chunk in neighbors_to_new_chunks {
-                chunk_mods.push(ChunkModification {
-                    new_chunk: chunk,
-                    old_chunk: chunk,
-                })
-            }
-            for (new_chunk, old_chunk) in izip!(new_chunks, old_chunks) {
-                chunk_mods.push(ChunkModification {
-                    new_chunk,
-                    old_chunk,
-                });
+        for lod_mesh in state.chunk_jobs.take_finished_lod_meshes() {
+            match scene.lod_render_descriptors.get_mut(&lod_mesh.idx) {
+                Some(lod_render_descriptor) => {
+                    lod_render_descriptor.update(&state.device, &state.queue, &lod_mesh)
+                }
+                None => {
+                    scene.lod_render_descriptors.insert(
+                        lod_mesh.idx,
+                        LodRenderDescriptor::new(&state.device, &lod_mesh),
+                    );
+                }
             }
-
-            scene.chunk_order = new_chunk_order;
         }
 
-        if !chunk_mods.is_empty() {
-            #[cfg(not(target_arch = "wasm32"))]
-            let chunk_mod_time = std::time::Instant::now();
-
-            chunk_mods.dedup();
-
-            for chunk_mod in chunk_mods.iter() {
-                state.world_state.maybe_allocate_chunk(chunk_mod.new_chunk);
+        for new_chunk_data in new_chunk_datas.into_iter() {
+            let render_descriptor_idx = state
+                .world_state
+                .get_render_descriptor_idx(new_chunk_data.position);
+            // Chunk went out of view in the meantime
+            if render_descriptor_idx == world::NO_RENDER_DESCRIPTOR_INDEX {
+                continue;
             }
-            #[cfg(not(target_arch = "wasm32"))]
-            if VERBOSE_LOGS && update_result.did_move_chunks {
-                println!(
-                    "Took {}ms to allocate chunks",
-                    chunk_mod_time.elapsed().as_millis()
-                );
+            let chunk_render_descriptor = &mut scene.chunk_render_descriptors[render_descriptor_idx];
+            chunk_render_descriptor.y_bounds = new_chunk_data.y_bounds;
+            chunk_render_descriptor.set_translucent_instances(&new_chunk_data);
+            chunk_render_descriptor.connectivity = new_chunk_data.connectivity;
+
+            for typed_instances in new_chunk_data.typed_instances_vec.iter() {
+                let maybe_instance_buffer = chunk_render_descriptor
+                    .annotated_instance_buffers
+                    .iter_mut()
+                    .find(|ib| ib.data_type == typed_instances.data_type);
+
+                match maybe_instance_buffer {
+                    Some(instance_buffer) => instance_buffer.update(
+                        &state.device,
+                        &state.queue,
+                        typed_instances,
+                        new_chunk_data.position,
+                    ),
+                    // Descriptor was added when the render distance grew
+                    None => chunk_render_descriptor.annotated_instance_buffers.push(
+                        AnnotatedInstanceBuffer::new(
+                            &state.device,
+                            typed_instances,
+                            new_chunk_data.position,
+                        ),
+                    ),
+                }
             }
+        }
 
-            let new_chunk_datas = chunk_mods
-                .iter()
-                .map(|chunk_mod| {
-                    let new_chunk_data = state
-                        .world_state
-                        .compute_chunk_mesh(chunk_mod.new_chunk, &state.camera);
-
-                    let render_descriptor_idx = state
-                        .world_state
-                        .get_render_descriptor_idx(chunk_mod.old_chunk);
-                    if chunk_mod.new_chunk != chunk_mod.old_chunk {
-                        state.world_state.set_render_descriptor_idx(
-                            chunk_mod.old_chunk,
-                            world::NO_RENDER_DESCRIPTOR_INDEX,
-                        );
-                        state
-                            .world_state
-                            .set_render_descriptor_idx(chunk_mod.new_chunk, render_descriptor_idx);
-                    }
-
-                    (new_chunk_data, render_descriptor_idx)
-                })
-                .collect::<Vec<_>>();
-
-            #[cfg(not(target_arch = "wasm32"))]
-            if VERBOSE_LOGS && update_result.did_move_chunks {
-                println!(
-                    "Took {}ms to update chunks",
-                    chunk_mod_time.elapsed().as_millis()
-                );
-            }
+        for chunk_render_descriptor in scene.chunk_render_descriptors.iter_mut() {
+            chunk_render_descriptor.resort_translucent_instances(&state.queue, state.camera.eye);
+        }
 
-            for (new_chunk_data, render_descriptor_idx) in new_chunk_datas.into_iter() {
-                let chunk_render_descriptor =
-                    &mut scene.chunk_render_descriptors[render_descriptor_idx];
-
-                for typed_instances in new_chunk_data.typed_instances_vec.iter() {
-                    let maybe_instance_buffer = chunk_render_descriptor
-                        .annotated_instance_buffers
-                        .iter_mut()
-                        .find(|ib| ib.data_type == typed_instances.data_type);
-
-                    if let Some(instance_buffer) = maybe_instance_buffer {
-                        state.queue.write_buffer(
-                            &instance_buffer.buffer,
-                            0,
-                            bytemuck::cast_slice(&typed_instances.instance_data),
-                        );
-                        instance_buffer.len = typed_instances.instance_data.len();
-                    }
-                }
-            }
+        #[cfg(not(target_arch = "wasm32"))]
+        if VERBOSE_LOGS && update_result.did_move_chunks {
+            println!(
+                "Took {}ms to update chunks",
+                chunk_update_time.elapsed().as_millis()
+            );
         }
 
         let updated_character_vtx_data = state.world_state.character_entity.vertex_data();
@@ -1104,9 +1956,34 @@ impl Game {
             .world_state
             .get_render_descriptor_idx([chunk_x, chunk_z]);
         let chunk_render_datum = &self.scene.chunk_render_descriptors[render_descriptor_idx];
+        self.render_instance_buffers(
+            rpass,
+            &chunk_render_datum.annotated_instance_buffers,
+            data_type,
+        );
+    }
 
-        let maybe_instance_buffer = chunk_render_datum
-            .annotated_instance_buffers
+    fn render_lod_tile<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        idx: lod::LodTileIdx,
+        data_type: ChunkDataType,
+    ) {
+        let lod_render_descriptor = &self.scene.lod_render_descriptors[&idx];
+        self.render_instance_buffers(
+            rpass,
+            &lod_render_descriptor.annotated_instance_buffers,
+            data_type,
+        );
+    }
+
+    fn render_instance_buffers<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        annotated_instance_buffers: &'a [AnnotatedInstanceBuffer],
+        data_type: ChunkDataType,
+    ) {
+        let maybe_instance_buffer = annotated_instance_buffers
             .iter()
             .find(|&ib| ib.data_type == data_type);
 
@@ -1133,18 +2010,103 @@ impl Game {
         }
     }
 
-    pub fn render_frame(&mut self, spawner: &Spawner) -> SurfaceTexture {
+    fn cull_chunks(&mut self) -> CulledChunks {
         let state = &self.state;
         let scene = &self.scene;
 
-        let frame = match state.surface.get_current_texture() {
+        // Chunks hidden behind terrain can still cast shadows, so this only applies to the camera
+        let unoccluded_chunks = occlusion::find_visible_chunks(
+            &state.camera,
+            &scene.chunk_order.iter().cloned().collect(),
+            |chunk_idx| {
+                let render_descriptor_idx = state.world_state.get_render_descriptor_idx(chunk_idx);
+                &scene.chunk_render_descriptors[render_descriptor_idx].connectivity
+            },
+        );
+
+        let mut camera_chunks = vec![];
+        let mut translucent_chunks = vec![];
+        let mut num_occluded = 0;
+        let mut shadow_chunks = vec![vec![]; light::NUM_SHADOW_CASCADES];
+        for &chunk_idx in scene.chunk_order.iter() {
+            let render_descriptor_idx = state.world_state.get_render_descriptor_idx(chunk_idx);
+            let y_bounds = scene.chunk_render_descriptors[render_descriptor_idx].y_bounds;
+            // Nothing to draw yet
+            if y_bounds[0] == y_bounds[1] {
+                continue;
+            }
+
+            let aabb = culling::chunk_aabb(chunk_idx, y_bounds);
+            if culling::is_in_camera_frustum(&state.camera, &aabb) {
+                if unoccluded_chunks.contains(&chunk_idx) {
+                    camera_chunks.push(chunk_idx);
+                    translucent_chunks.push((chunk_idx, aabb));
+                } else {
+                    num_occluded += 1;
+                }
+            }
+            for (cascade, cascade_chunks) in state
+                .light_uniform
+                .shadow_cascades
+                .iter()
+                .zip(shadow_chunks.iter_mut())
+            {
+                if culling::is_in_light_volume(&cascade.light_space_matrix, &state.camera, &aabb) {
+                    cascade_chunks.push(chunk_idx);
+                }
+            }
+        }
+
+        // Far terrain doesn't cast shadows, it's past the last shadow cascade anyway
+        let mut lod_tiles = vec![];
+        for (&idx, lod_render_descriptor) in scene.lod_render_descriptors.iter() {
+            let aabb = culling::lod_tile_aabb(idx, lod_render_descriptor.y_bounds);
+            if culling::is_in_camera_frustum(&state.camera, &aabb) {
+                lod_tiles.push((idx, aabb));
+            }
+        }
+        let distance2 = |aabb: &collision::Aabb3<f32>| aabb.center().distance2(state.camera.eye);
+        lod_tiles.sort_by(|(_, a), (_, b)| distance2(b).total_cmp(&distance2(a)));
+        // `chunk_order` only changes when the camera crosses into another chunk
+        translucent_chunks.sort_by(|(_, a), (_, b)| distance2(b).total_cmp(&distance2(a)));
+
+        let num_chunks = scene.chunk_order.len();
+        let num_lod_tiles = scene.lod_render_descriptors.len();
+        // Summed over the shadow cascades
+        let num_shadow_drawn: usize = shadow_chunks.iter().map(Vec::len).sum();
+        let culling_stats = culling::CullingStats {
+            camera_drawn: camera_chunks.len(),
+            camera_culled: num_chunks - camera_chunks.len() - num_occluded,
+            camera_occluded: num_occluded,
+            shadow_drawn: num_shadow_drawn,
+            shadow_culled: num_chunks * light::NUM_SHADOW_CASCADES - num_shadow_drawn,
+            lod_drawn: lod_tiles.len(),
+            lod_culled: num_lod_tiles - lod_tiles.len(),
+        };
+        if VERBOSE_LOGS && culling_stats != self.scene.culling_stats {
+            log::info!("{:?}", culling_stats);
+        }
+        self.scene.culling_stats = culling_stats;
+
+        CulledChunks {
+            camera_chunks,
+            translucent_chunks: translucent_chunks
+                .into_iter()
+                .map(|(chunk_idx, _)| chunk_idx)
+                .collect(),
+            shadow_chunks,
+            lod_tiles: lod_tiles.into_iter().map(|(idx, _)| idx).collect(),
+        }
+    }
+
+    pub fn render_frame(&mut self, spawner: &Spawner) -> SurfaceTexture {
+        let state = &self.state;
+        let surface = state.surface.as_ref().expect("Rendering offscreen");
+        let frame = match surface.get_current_texture() {
             Ok(frame) => frame,
             Err(_) => {
-                state
-                    .surface
-                    .configure(&state.device, &state.surface_config);
-                state
-                    .surface
+                surface.configure(&state.device, &state.surface_config);
+                surface
                     .get_current_texture()
                     .expect("Failed to acquire next surface texture!")
             }
@@ -1153,6 +2115,23 @@ impl Game {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        self.render_to(&view, spawner);
+
+        return frame;
+    }
+
+    // Draws the frame into `view`, the window's surface texture unless rendering offscreen
+    fn render_to(&mut self, view: &wgpu::TextureView, spawner: &Spawner) {
+        let CulledChunks {
+            camera_chunks,
+            translucent_chunks,
+            shadow_chunks,
+            lod_tiles,
+        } = self.cull_chunks();
+
+        let state = &self.state;
+        let scene = &self.scene;
+
         state.device.push_error_scope(wgpu::ErrorFilter::Validation);
         let mut encoder = state
             .device
@@ -1164,12 +2143,12 @@ impl Game {
             0,
             mem::size_of::<camera::CameraUniform>().try_into().unwrap(),
         );
-        {
+        for (cascade_idx, cascade_chunks) in shadow_chunks.iter().enumerate() {
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
                 color_attachments: &[],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &scene.shadow_map_texture.view,
+                    view: &scene.shadow_map_layer_views[cascade_idx],
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Clear(1.0),
                         store: true,
@@ -1180,7 +2159,7 @@ impl Game {
 
             rpass.set_pipeline(&scene.shadow_map_pipeline);
             rpass.set_bind_group(0, &scene.camera_bind_group, &[]);
-            rpass.set_bind_group(1, &scene.light_bind_group, &[]);
+            rpass.set_bind_group(1, &scene.shadow_cascade_bind_groups[cascade_idx], &[]);
             rpass.set_bind_group(2, &scene.albedo_only_texture_bind_group, &[]);
             rpass.set_vertex_buffer(0, scene.vertex_buffers.blocks.slice(..));
             rpass.set_index_buffer(
@@ -1189,7 +2168,7 @@ impl Game {
             );
 
             for data_type in [ChunkDataType::Opaque, ChunkDataType::SemiTranslucent] {
-                for chunk_idx in scene.chunk_order.iter().rev() {
+                for chunk_idx in cascade_chunks.iter().rev() {
                     self.render_chunk(&mut rpass, *chunk_idx, data_type);
                 }
             }
@@ -1204,17 +2183,13 @@ impl Game {
             }
         }
 
-        let sky_color = wgpu::Color {
-            r: color::srgb_to_rgb(120.0 / 255.0),
-            g: color::srgb_to_rgb(167.0 / 255.0),
-            b: color::srgb_to_rgb(255.0 / 255.0),
-            a: 1.0,
-        };
+        let [r, g, b] = state.world_state.world_clock.sky_color();
+        let sky_color = wgpu::Color { r, g, b, a: 1.0 };
         {
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(sky_color),
@@ -1230,6 +2205,12 @@ impl Game {
                     stencil_ops: None,
                 }),
             });
+            // Sky first, so distant terrain fades into it
+            rpass.set_pipeline(&scene.sky_pipeline);
+            rpass.set_bind_group(0, &scene.camera_bind_group, &[]);
+            rpass.set_bind_group(1, &scene.sky_bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+
             rpass.set_pipeline(&scene.opaque_pipeline);
             rpass.set_bind_group(0, &scene.texture_bind_group, &[]);
             rpass.set_bind_group(1, &scene.camera_bind_group, &[]);
@@ -1240,9 +2221,21 @@ impl Game {
                 wgpu::IndexFormat::Uint16,
             );
 
-            for chunk_idx in scene.chunk_order.iter().rev() {
+            for chunk_idx in camera_chunks.iter().rev() {
                 self.render_chunk(&mut rpass, *chunk_idx, ChunkDataType::Opaque);
             }
+            // After the chunks in front of them, so most of their fragments fail the depth test
+            for idx in lod_tiles.iter().rev() {
+                self.render_lod_tile(&mut rpass, *idx, ChunkDataType::Opaque);
+            }
+            if scene.entity_instance_count > 0 {
+                rpass.set_vertex_buffer(1, scene.entity_instances_buf.slice(..));
+                rpass.draw_indexed(
+                    0..scene.index_counts.blocks as u32,
+                    0,
+                    0..scene.entity_instance_count,
+                );
+            }
 
             if RENDER_CHARACTER_ENTITY {
                 if let Some(ref pipe) = &scene.pipeline_solid_color {
@@ -1267,11 +2260,23 @@ impl Game {
             }
 
             rpass.set_pipeline(&scene.translucent_pipeline);
-            for chunk_idx in scene.chunk_order.iter().rev() {
+            // Back to front
+            for idx in lod_tiles.iter() {
+                self.render_lod_tile(&mut rpass, *idx, ChunkDataType::Translucent);
+            }
+            for chunk_idx in translucent_chunks.iter() {
                 for data_type in [ChunkDataType::Translucent, ChunkDataType::SemiTranslucent] {
                     self.render_chunk(&mut rpass, *chunk_idx, data_type);
                 }
             }
+            if scene.break_overlay.is_some() {
+                rpass.set_vertex_buffer(1, scene.break_overlay_buf.slice(..));
+                rpass.draw_indexed(
+                    0..scene.index_counts.blocks as u32,
+                    0,
+                    0..world::Face::ALL.len() as u32,
+                );
+            }
             rpass.set_pipeline(&scene.opaque_pipeline);
 
             if RENDER_LIGHT_DEBUG_DATA {
@@ -1296,8 +2301,6 @@ impl Game {
         spawner.spawn_local(ErrorFuture {
             inner: state.device.pop_error_scope(),
         });
-
-        return frame;
     }
 }
 
@@ -1318,10 +2321,6 @@ pub async fn run(width: usize, height: usize) {
     let event_loop = EventLoopBuilder::<DomControlsUserEvent>::with_user_event().build();
     unsafe {
         dom_controls::set_global_event_loop_proxy(&event_loop);
-        #[cfg(target_arch = "wasm32")]
-        dom_controls::place_block_type_changed(
-            &world::BlockType::DEFAULT_PLACE_BLOCK_TYPE.to_string(),
-        );
     }
 
     let window = winit::window::WindowBuilder::new()
@@ -1350,10 +2349,22 @@ pub async fn run(width: usize, height: usize) {
 
 
     let mut game = Game::new(&window).await;
+    #[cfg(target_arch = "wasm32")]
+    dom_controls::place_block_type_changed(game.state.world_state.inventory.selected());
+    #[cfg(target_arch = "wasm32")]
+    wasm_utils::js_handle_render_distance_changed(game.state.world_state.visible_chunk_width());
+    // On the web the page starts it with `?timeFrames`, see `dom_controls::time_frames`
+    #[cfg(not(target_arch = "wasm32"))]
+    if std::env::args().any(|arg| arg == "--time-frames") {
+        game.start_timing_frames();
+    }
 
     let mut cursor_grabbed = false;
 
-    let mut left_mouse_clicked = false;
+    // Held down to mine the targeted block
+    let mut is_mining = false;
+    // Set when the break button goes down, a targeted entity is hit once each time
+    let mut attack_clicked = false;
     let mut right_mouse_clicked = false;
 
     // Remove Loader element from DOM
@@ -1364,10 +2375,8 @@ pub async fn run(width: usize, height: usize) {
         wasm_utils::yield_().await;
     }
 
-    let updates_per_second = 100;
-    let max_frame_time = 1.0 / 60.0;
     let mut game_loop: game_loop::GameLoop =
-        game_loop::GameLoop::new(updates_per_second, max_frame_time);
+        game_loop::GameLoop::new(UPDATES_PER_SECOND, MAX_FRAME_TIME);
 
     let spawner = Spawner::new();
 
@@ -1395,11 +2404,37 @@ pub async fn run(width: usize, height: usize) {
                         (Some(VirtualKeyCode::Slash), ElementState::Pressed) => {
                             game.state.world_state.is_flying = !game.state.world_state.is_flying;
                         }
+                        // Change render distance with [ and ]
+                        (Some(VirtualKeyCode::LBracket), ElementState::Pressed) => {
+                            let visible_chunk_width = game.state.world_state.visible_chunk_width();
+                            game.set_visible_chunk_width(visible_chunk_width.saturating_sub(2));
+                        }
+                        (Some(VirtualKeyCode::RBracket), ElementState::Pressed) => {
+                            let visible_chunk_width = game.state.world_state.visible_chunk_width();
+                            game.set_visible_chunk_width(visible_chunk_width + 2);
+                        }
+                        // Skip ahead to the next of dawn, noon, dusk and midnight with T
+                        (Some(VirtualKeyCode::T), ElementState::Pressed) => {
+                            let time_of_day = game.state.world_state.world_clock.time_of_day();
+                            game.set_time_of_day(
+                                world_clock::TimeOfDay::next_after(time_of_day).time_of_day(),
+                            );
+                        }
+                        // Cycle through the shadow qualities with O
+                        (Some(VirtualKeyCode::O), ElementState::Pressed) => {
+                            game.set_shadow_quality(game.state.shadow_quality.next());
+                        }
+                        // Cycle through the resource packs with P
+                        #[cfg(not(target_arch = "wasm32"))]
+                        (Some(VirtualKeyCode::P), ElementState::Pressed) => {
+                            game.cycle_resource_pack();
+                        }
                         (Some(VirtualKeyCode::Escape), ElementState::Pressed) => {
                             window
                                 .set_cursor_grab(winit::window::CursorGrabMode::None)
                                 .expect("Failed to release curosr");
                             cursor_grabbed = false;
+                            is_mining = false;
                             last_cursor_lost_time = instant::Instant::now();
                             window.set_cursor_visible(true);
                         }
@@ -1434,15 +2469,22 @@ pub async fn run(width: usize, height: usize) {
                             window.set_cursor_visible(false);
                             cursor_grabbed = true;
                         } else {
-                            left_mouse_clicked = true;
+                            attack_clicked |= !is_mining;
+                            is_mining = true;
                         }
                     }
+                    (ElementState::Released, MouseButton::Left) => {
+                        is_mining = false;
+                    }
                     (ElementState::Pressed, MouseButton::Right) => {
                         right_mouse_clicked = true;
                     }
                     _ => (),
                 },
 
+                WindowEvent::MouseWheel { .. } if cursor_grabbed => {
+                    game.state.world_state.process_window_event(&event);
+                }
                 WindowEvent::Resized(physical_size) => {
                     game.resize(physical_size);
                 }
@@ -1463,7 +2505,11 @@ pub async fn run(width: usize, height: usize) {
 
             Event::UserEvent(event) => match event {
                 DomControlsUserEvent::AButtonPressed => {
-                    left_mouse_clicked = true;
+                    attack_clicked |= !is_mining;
+                    is_mining = true;
+                }
+                DomControlsUserEvent::AButtonReleased => {
+                    is_mining = false;
                 }
                 DomControlsUserEvent::BButtonPressed => {
                     right_mouse_clicked = true;
@@ -1481,8 +2527,39 @@ pub async fn run(width: usize, height: usize) {
                         ));
                     }
                 }
+                DomControlsUserEvent::RenderDistanceChanged {
+                    visible_chunk_width,
+                } => {
+                    game.set_visible_chunk_width(visible_chunk_width);
+                }
+                DomControlsUserEvent::TimeOfDayChanged { time_of_day } => {
+                    game.set_time_of_day(time_of_day);
+                }
+                DomControlsUserEvent::ShadowQualityChanged { shadow_quality } => {
+                    game.set_shadow_quality(shadow_quality);
+                }
+                DomControlsUserEvent::ResourcePackLoaded { zip_bytes } => {
+                    let resource_pack = resource_pack::ResourcePack::from_zip(
+                        std::io::Cursor::new(zip_bytes),
+                    )
+                    .unwrap_or_else(|err| {
+                        log::error!("Failed to load resource pack: {}", err);
+                        resource_pack::ResourcePack::builtin()
+                    });
+                    game.set_resource_pack(&resource_pack);
+                }
+                DomControlsUserEvent::InventoryLoaded { json } => {
+                    game.state.world_state.load_inventory(&json);
+                }
+                DomControlsUserEvent::TimeFramesRequested => {
+                    game.start_timing_frames();
+                }
+                DomControlsUserEvent::ResourcePackReset => {
+                    game.set_resource_pack(&resource_pack::ResourcePack::builtin());
+                }
                 DomControlsUserEvent::WebPointerLockLost => {
                     cursor_grabbed = false;
+                    is_mining = false;
                     last_cursor_lost_time = instant::Instant::now();
                     window
                         .set_cursor_grab(winit::window::CursorGrabMode::None)
@@ -1502,12 +2579,19 @@ pub async fn run(width: usize, height: usize) {
                 game_loop.next_frame(
                     |g| {
                         let mut game = game_cell.borrow_mut();
-                        game.update_tick(g, &mut left_mouse_clicked, &mut right_mouse_clicked);
+                        game.update_tick(
+                            g,
+                            is_mining,
+                            &mut attack_clicked,
+                            &mut right_mouse_clicked,
+                        );
                     },
                     |_| {
                         let mut game = game_cell.borrow_mut();
+                        let render_start = instant::Instant::now();
                         let frame = game.render_frame(&spawner);
                         frame.present();
+                        game.frame_rendered(render_start);
 
                         game.state.camera_controller.reset_mouse_delta();
                     },
@@ -1,90 +1,301 @@
 use crate::camera::Camera;
 use crate::vertex::{CuboidCoords, QuadListRenderData, Vertex};
+use crate::world_clock::WorldClock;
+use bytemuck::Zeroable;
 use glam::{Mat4, Vec3};
 
+// Must match the array size in shader.wgsl
+pub const MAX_POINT_LIGHTS: usize = 16;
+
+// Must match NUM_SHADOW_CASCADES in shader.wgsl. Each cascade has its own layer in the shadow map,
+// nearer cascades cover less ground with the same number of texels, so they get sharper shadows.
+pub const NUM_SHADOW_CASCADES: usize = 3;
+// How far from the camera each cascade reaches, along the view direction
+const SHADOW_CASCADE_SPLITS: [f32; NUM_SHADOW_CASCADES] = [24.0, 64.0, 160.0];
+// How far towards the sun past a cascade's bounds blocks still cast shadows into it. Can't be too
+// far or depth values won't have enough precision.
+const SHADOW_CASTER_DISTANCE: f32 = 128.0;
+// Depth bias against shadow acne, in blocks
+const SHADOW_DEPTH_BIAS: f32 = 0.25;
+// Far enough that the sky light's direction is the same everywhere in view
+const SKY_LIGHT_DISTANCE: f32 = 10000.0;
+// Where the sun's debug cube is drawn, see `vertex_data_for_sunlight`
+const SUN_DEBUG_DISTANCE: f32 = 50.0;
+
+// How shadow edges are filtered. Picked when the main shader is built, see `shadow_calculation`
+// in shader.wgsl.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadowFilter {
+    // One shadow map sample, hard and blocky edges
+    Naive,
+    // Percentage-closer filtering, averages a 3x3 block of samples to soften edges
+    Pcf,
+    // Revectorization-based shadow mapping, smooths the staircase along edges. The most samples.
+    Rbsm,
+}
+
+impl ShadowFilter {
+    // Name of the filter in the shader template
+    pub fn template_name(self) -> &'static str {
+        match self {
+            ShadowFilter::Naive => "naive",
+            ShadowFilter::Pcf => "pcf",
+            ShadowFilter::Rbsm => "rbsm",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShadowSettings {
+    pub filter: ShadowFilter,
+    // Width and height of each cascade's layer of the shadow map
+    pub shadow_map_pixel_size: [u32; 2],
+}
+
+// Presets for `ShadowSettings`. Set with the O key or `--shadows=<name>` on native, and the
+// `shadows` URL parameter on the web, see `dom_controls::set_shadow_quality`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadowQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl ShadowQuality {
+    pub fn settings(self) -> ShadowSettings {
+        match self {
+            // Cheap shadows for phones
+            ShadowQuality::Low => ShadowSettings {
+                filter: ShadowFilter::Naive,
+                shadow_map_pixel_size: [1024, 1024],
+            },
+            ShadowQuality::Medium => ShadowSettings {
+                filter: ShadowFilter::Pcf,
+                shadow_map_pixel_size: [2048, 2048],
+            },
+            ShadowQuality::High => ShadowSettings {
+                filter: ShadowFilter::Rbsm,
+                shadow_map_pixel_size: [2048, 2048],
+            },
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "low" => Some(ShadowQuality::Low),
+            "medium" => Some(ShadowQuality::Medium),
+            "high" => Some(ShadowQuality::High),
+            _ => None,
+        }
+    }
+
+    // The next preset up, wrapping around to the lowest
+    pub fn next(self) -> Self {
+        match self {
+            ShadowQuality::Low => ShadowQuality::Medium,
+            ShadowQuality::Medium => ShadowQuality::High,
+            ShadowQuality::High => ShadowQuality::Low,
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub const DEFAULT_SHADOW_QUALITY: ShadowQuality = ShadowQuality::Low;
+#[cfg(not(target_arch = "wasm32"))]
+pub const DEFAULT_SHADOW_QUALITY: ShadowQuality = ShadowQuality::High;
+
+// Light emitted by a block. Position is relative to the camera eye, like everything else in the
+// shader.
+#[derive(Clone, Copy, Debug)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointLightRaw {
+    position_radius: [f32; 4],
+    color: [f32; 4],
+}
+
+// Part of the shadow map covering a slice of the view frustum, see `update_light_space_proj`
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowCascade {
+    // Takes positions relative to the camera eye to the cascade's clip space
+    pub light_space_matrix: Mat4,
+    // `SHADOW_DEPTH_BIAS` in the cascade's depth units
+    pub depth_bias: f32,
+}
+
+// Uniform for rendering a single cascade of the shadow map
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowCascadeRaw {
+    light_space_matrix: [[f32; 4]; 4],
+}
+
 pub struct LightUniform {
     pub position: Vec3,
     pub color: Vec3,
+    pub ambient_strength: f32,
+    // Sunlight shines from `sun_position` towards `sun_target`. Both are relative to the camera
+    // eye, only the direction between them matters for shadows.
     pub sun_position: Vec3,
-    pub sun_position_camera_adjusted: Vec3,
     pub sun_target: Vec3,
-    pub sun_target_camera_adjusted: Vec3,
-    pub sunlight_ortho_proj_coords: CuboidCoords,
-    pub sunlight_ortho_proj: glam::Mat4,
+    pub shadow_cascades: [ShadowCascade; NUM_SHADOW_CASCADES],
     pub shadow_map_pixel_size: [u32; 2],
+    pub point_lights: Vec<PointLight>,
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct LightUniformRaw {
     position: [f32; 3],
-    _padding: u32,
+    ambient_strength: f32,
     color: [f32; 3],
-    pub is_underwater: u32, // HACK: this should be in a separate uniform but I'm stuffing here because I'm lazy
-    light_space_matrix: [[f32; 4]; 4],
+    _padding: u32,
+    light_space_matrices: [[[f32; 4]; 4]; NUM_SHADOW_CASCADES],
+    shadow_depth_biases: [f32; 4],
+    num_point_lights: u32,
+    _padding2: [u32; 3],
+    point_lights: [PointLightRaw; MAX_POINT_LIGHTS],
 }
 
 impl LightUniform {
-    pub fn new(
-        position: Vec3,
-        color: Vec3,
-        sun_position: Vec3,
-        sunlight_ortho_proj_coords: CuboidCoords,
-        shadow_map_pixel_size: [u32; 2],
-    ) -> Self {
-        let sunlight_ortho_proj = glam::Mat4::orthographic_rh(
-            sunlight_ortho_proj_coords.left,
-            sunlight_ortho_proj_coords.right,
-            sunlight_ortho_proj_coords.bottom,
-            sunlight_ortho_proj_coords.top,
-            sunlight_ortho_proj_coords.near,
-            sunlight_ortho_proj_coords.far,
-        );
-
-        Self {
-            position,
-            color,
-            sun_position,
-            sun_position_camera_adjusted: sun_position,
-            sun_target: [0.0, 0.0, 0.0].into(),
-            sun_target_camera_adjusted: [0.0, 0.0, 0.0].into(),
-            sunlight_ortho_proj_coords,
-            sunlight_ortho_proj,
+    pub fn new(world_clock: &WorldClock, shadow_map_pixel_size: [u32; 2]) -> Self {
+        let mut light_uniform = Self {
+            position: Vec3::ZERO,
+            color: Vec3::ZERO,
+            ambient_strength: 0.0,
+            sun_position: Vec3::ZERO,
+            sun_target: Vec3::ZERO,
+            shadow_cascades: [ShadowCascade {
+                light_space_matrix: Mat4::IDENTITY,
+                depth_bias: 0.0,
+            }; NUM_SHADOW_CASCADES],
             shadow_map_pixel_size,
-        }
+            point_lights: vec![],
+        };
+        light_uniform.update_sky_light(world_clock);
+        light_uniform
+    }
+
+    // Moves the sunlight with the time of day. At night the moon takes over, see `WorldClock`.
+    pub fn update_sky_light(&mut self, world_clock: &WorldClock) {
+        let direction = world_clock.sky_light_direction();
+        self.position = direction * SKY_LIGHT_DISTANCE;
+        self.color = world_clock.sky_light_color();
+        self.ambient_strength = world_clock.ambient_strength();
+        self.sun_position = self.sun_target + direction * SUN_DEBUG_DISTANCE;
     }
 
-    fn get_light_view_proj(&self) -> Mat4 {
-        glam::Mat4::look_at_rh(
-            self.sun_position_camera_adjusted.into(),
-            self.sun_target_camera_adjusted.into(),
+    // Rotates world space so the sunlight shines along -z
+    fn get_light_view(&self) -> Mat4 {
+        Mat4::look_at_rh(
+            Vec3::ZERO,
+            self.sun_target - self.sun_position,
             [0.0, 1.0, 0.0].into(),
         )
     }
 
     pub fn to_raw(&self) -> LightUniformRaw {
-        let light_space_matrix =
-            (self.sunlight_ortho_proj * self.get_light_view_proj()).to_cols_array_2d();
+        let mut shadow_depth_biases = [0.0; 4];
+        for (raw, cascade) in shadow_depth_biases
+            .iter_mut()
+            .zip(self.shadow_cascades.iter())
+        {
+            *raw = cascade.depth_bias;
+        }
+
+        let mut point_lights = [PointLightRaw::zeroed(); MAX_POINT_LIGHTS];
+        let num_point_lights = self.point_lights.len().min(MAX_POINT_LIGHTS);
+        for (raw, point_light) in point_lights.iter_mut().zip(self.point_lights.iter()) {
+            *raw = PointLightRaw {
+                position_radius: point_light.position.extend(point_light.radius).into(),
+                color: point_light.color.extend(1.0).into(),
+            };
+        }
 
         LightUniformRaw {
             position: self.position.into(),
-            _padding: 0,
+            ambient_strength: self.ambient_strength,
             color: self.color.into(),
-            is_underwater: 0,
-            light_space_matrix,
+            _padding: 0,
+            light_space_matrices: self
+                .shadow_cascades
+                .map(|cascade| cascade.light_space_matrix.to_cols_array_2d()),
+            shadow_depth_biases,
+            num_point_lights: num_point_lights as u32,
+            _padding2: [0; 3],
+            point_lights,
         }
     }
 
-    pub fn update_light_space_proj(&mut self, camera: &Camera) {
-        // let sun_adjust = Vec3::new(
-        //     f32::trunc(camera.eye.x) - camera.eye.x,
-        //     camera.initial_eye.y - camera.eye.y,
-        //     f32::trunc(camera.eye.z) - camera.eye.z,
-        // );
-        let sun_adjust = Vec3::new(0.0, camera.initial_eye.y - camera.eye.y, 0.0);
+    pub fn shadow_cascade_raw(&self, cascade_idx: usize) -> ShadowCascadeRaw {
+        ShadowCascadeRaw {
+            light_space_matrix: self.shadow_cascades[cascade_idx]
+                .light_space_matrix
+                .to_cols_array_2d(),
+        }
+    }
+
+    // Fits each cascade around its slice of the view frustum. `max_distance` caps how far the
+    // cascades reach, e.g. to where terrain stops casting shadows.
+    pub fn update_light_space_proj(&mut self, camera: &Camera, max_distance: f32) {
+        let light_view = self.get_light_view();
+        let eye = Vec3::new(camera.eye.x, camera.eye.y, camera.eye.z);
+        let forward = camera.forward_normal();
+        let forward = Vec3::new(forward.x, forward.y, forward.z);
+
+        // Distance from the view axis to the frustum's corners, per unit of distance along it
+        let half_fovy_tan = (camera.fovy.to_radians() * 0.5).tan();
+        let corner_slope = half_fovy_tan * (1.0 + camera.aspect * camera.aspect).sqrt();
 
-        self.sun_position_camera_adjusted = self.sun_position + sun_adjust;
-        self.sun_target_camera_adjusted = self.sun_target + sun_adjust;
+        let mut slice_start = camera.znear;
+        for (cascade, &split) in self.shadow_cascades.iter_mut().zip(&SHADOW_CASCADE_SPLITS) {
+            let slice_end = split.min(max_distance).max(slice_start);
+
+            // Bounding sphere of the slice. Its size doesn't change as the camera turns, so the
+            // shadow map's texels keep their size in the world.
+            let center_distance =
+                ((slice_start + slice_end) * 0.5 * (1.0 + corner_slope * corner_slope))
+                    .min(slice_end);
+            let radius = ((slice_end - center_distance).powi(2)
+                + (slice_end * corner_slope).powi(2))
+            .sqrt()
+            .ceil();
+            slice_start = slice_end;
+
+            // Move the cascade in whole texels so shadow edges don't shimmer as the camera moves.
+            // Texels are snapped in world space, relative to the eye they'd move with the camera.
+            let texel_size = 2.0 * radius / self.shadow_map_pixel_size[0] as f32;
+            let eye_light_space = light_view.transform_point3(eye);
+            let center_light_space =
+                eye_light_space + light_view.transform_vector3(forward * center_distance);
+            let snapped_center = Vec3::new(
+                (center_light_space.x / texel_size).floor() * texel_size,
+                (center_light_space.y / texel_size).floor() * texel_size,
+                center_light_space.z,
+            );
+
+            let projection = Mat4::orthographic_rh_gl(
+                -radius,
+                radius,
+                -radius,
+                radius,
+                -(radius + SHADOW_CASTER_DISTANCE),
+                radius,
+            );
+            *cascade = ShadowCascade {
+                light_space_matrix: projection
+                    * Mat4::from_translation(eye_light_space - snapped_center)
+                    * light_view,
+                // Depth goes from 0 to 1 over the cascade's depth range
+                depth_bias: SHADOW_DEPTH_BIAS / (2.0 * radius + SHADOW_CASTER_DISTANCE),
+            };
+        }
     }
 
     pub fn vertex_data_for_sunlight(&self) -> QuadListRenderData {
@@ -93,25 +304,31 @@ impl LightUniform {
             index_data: vec![],
         };
 
-        // HACK: using inverse() and modifying the projection coords is jank, but it matches up
-        // with what I see on screen...
-        let mut ortho_coords = self.sunlight_ortho_proj_coords.clone();
-        (ortho_coords.near, ortho_coords.far) = (-ortho_coords.far, ortho_coords.near);
-        Vertex::generate_quad_data_for_cuboid(
-            &ortho_coords,
-            Some(self.get_light_view_proj().inverse()),
-            &mut sunlight_vertex_data,
-        );
+        // Outline of each cascade, its clip space box taken back to camera relative positions
+        for cascade in self.shadow_cascades.iter() {
+            Vertex::generate_quad_data_for_cuboid(
+                &CuboidCoords {
+                    left: -1.0,
+                    right: 1.0,
+                    bottom: -1.0,
+                    top: 1.0,
+                    near: -1.0,
+                    far: 1.0,
+                },
+                Some(cascade.light_space_matrix.inverse()),
+                &mut sunlight_vertex_data,
+            );
+        }
 
         const SUNLIGHT_CUBE_SIZE: f32 = 1.0;
         Vertex::generate_quad_data_for_cuboid(
             &CuboidCoords {
-                left: self.sun_position_camera_adjusted.x - SUNLIGHT_CUBE_SIZE,
-                right: self.sun_position_camera_adjusted.x + SUNLIGHT_CUBE_SIZE,
-                bottom: self.sun_position_camera_adjusted.y - SUNLIGHT_CUBE_SIZE,
-                top: self.sun_position_camera_adjusted.y + SUNLIGHT_CUBE_SIZE,
-                near: self.sun_position_camera_adjusted.z - SUNLIGHT_CUBE_SIZE,
-                far: self.sun_position_camera_adjusted.z + SUNLIGHT_CUBE_SIZE,
+                left: self.sun_position.x - SUNLIGHT_CUBE_SIZE,
+                right: self.sun_position.x + SUNLIGHT_CUBE_SIZE,
+                bottom: self.sun_position.y - SUNLIGHT_CUBE_SIZE,
+                top: self.sun_position.y + SUNLIGHT_CUBE_SIZE,
+                near: self.sun_position.z - SUNLIGHT_CUBE_SIZE,
+                far: self.sun_position.z + SUNLIGHT_CUBE_SIZE,
             },
             None,
             &mut sunlight_vertex_data,
@@ -119,12 +336,12 @@ impl LightUniform {
 
         Vertex::generate_quad_data_for_cuboid(
             &CuboidCoords {
-                left: self.sun_target_camera_adjusted.x - SUNLIGHT_CUBE_SIZE,
-                right: self.sun_target_camera_adjusted.x + SUNLIGHT_CUBE_SIZE,
-                bottom: self.sun_target_camera_adjusted.y - SUNLIGHT_CUBE_SIZE,
-                top: self.sun_target_camera_adjusted.y + SUNLIGHT_CUBE_SIZE,
-                near: self.sun_target_camera_adjusted.z - SUNLIGHT_CUBE_SIZE,
-                far: self.sun_target_camera_adjusted.z + SUNLIGHT_CUBE_SIZE,
+                left: self.sun_target.x - SUNLIGHT_CUBE_SIZE,
+                right: self.sun_target.x + SUNLIGHT_CUBE_SIZE,
+                bottom: self.sun_target.y - SUNLIGHT_CUBE_SIZE,
+                top: self.sun_target.y + SUNLIGHT_CUBE_SIZE,
+                near: self.sun_target.z - SUNLIGHT_CUBE_SIZE,
+                far: self.sun_target.z + SUNLIGHT_CUBE_SIZE,
             },
             None,
             &mut sunlight_vertex_data,
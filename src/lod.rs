@@ -0,0 +1,496 @@
+use crate::byte_codec::{ByteReader, ByteWriter};
+use crate::camera::Camera;
+use crate::greedy_mesh::GreedyMesher;
+use crate::instance::{InstanceRaw, INSTANCE_FLAG_WATER_SURFACE};
+use crate::map_generation;
+use crate::world::{
+    self, BlockType, ChunkDataType, Face, TypedInstances, CHUNK_XZ_SIZE, CHUNK_Y_SIZE,
+    MAX_CHUNK_WORLD_WIDTH, MAX_HEIGHT, MIN_HEIGHT, NO_AMBIENT_OCCLUSION, WATER_BLOCK_Y_HEIGHT,
+    WATER_HEIGHT,
+};
+use cgmath::{ElementWise, Rotation, Vector3};
+use std::collections::HashMap;
+
+// Terrain past the full resolution chunks is drawn in rings of coarser tiles, made straight from
+// the heightmap. Each ring reaches twice as far as the one inside it, and its cells are twice as
+// large. A cell is a column of blocks `cell_size` wide and 1 block tall.
+pub const LOD_CELL_SIZES: [usize; 3] = [2, 4, 8];
+// Cells along each side of a tile
+pub const LOD_TILE_CELLS: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LodTileIdx {
+    pub cell_size: usize,
+    // Position in the grid of tiles with this cell size
+    pub tile: [usize; 2],
+}
+
+impl LodTileIdx {
+    pub fn chunk_width(cell_size: usize) -> usize {
+        LOD_TILE_CELLS * cell_size / CHUNK_XZ_SIZE
+    }
+
+    pub fn first_chunk(&self) -> [usize; 2] {
+        let chunk_width = Self::chunk_width(self.cell_size);
+        [self.tile[0] * chunk_width, self.tile[1] * chunk_width]
+    }
+}
+
+// Which of a tile's chunks it draws, indexed by `z * chunk_width + x`. The others are drawn at
+// full resolution or by a tile of another ring.
+pub type LodChunkMask = Vec<bool>;
+
+// Distance in chunks from the camera chunk to the outer edge of the ring. The full resolution
+// chunks reach half of `visible_chunk_width`, see `WorldState::iter_visible_chunks`.
+fn ring_radius(ring_idx: usize, visible_chunk_width: usize) -> usize {
+    (visible_chunk_width / 2) << (ring_idx + 1)
+}
+
+// Distance to the edge of the outermost ring, i.e. how far terrain is drawn
+pub fn view_distance(visible_chunk_width: usize) -> f32 {
+    (ring_radius(LOD_CELL_SIZES.len() - 1, visible_chunk_width) * CHUNK_XZ_SIZE) as f32
+}
+
+// `None` for chunks drawn at full resolution and chunks too far away to be drawn at all
+fn cell_size_for_chunk(
+    [chunk_x, chunk_z]: [usize; 2],
+    [camera_x, camera_z]: [usize; 2],
+    visible_chunk_width: usize,
+) -> Option<usize> {
+    let (dx, dz) = (
+        chunk_x as isize - camera_x as isize,
+        chunk_z as isize - camera_z as isize,
+    );
+    let is_within = |radius: usize| {
+        let radius = radius as isize;
+        (-radius..radius).contains(&dx) && (-radius..radius).contains(&dz)
+    };
+    if is_within(visible_chunk_width / 2) {
+        return None;
+    }
+
+    LOD_CELL_SIZES
+        .iter()
+        .enumerate()
+        .find(|&(ring_idx, _)| is_within(ring_radius(ring_idx, visible_chunk_width)))
+        .map(|(_, &cell_size)| cell_size)
+}
+
+// The tiles needed to draw the rings around the camera
+pub fn lod_tiles_around(
+    camera: &Camera,
+    visible_chunk_width: usize,
+) -> HashMap<LodTileIdx, LodChunkMask> {
+    let camera_chunk = [
+        (camera.eye.x / CHUNK_XZ_SIZE as f32) as usize,
+        (camera.eye.z / CHUNK_XZ_SIZE as f32) as usize,
+    ];
+
+    let mut tiles = HashMap::new();
+    for (ring_idx, &cell_size) in LOD_CELL_SIZES.iter().enumerate() {
+        let ring_radius = ring_radius(ring_idx, visible_chunk_width);
+        let chunk_width = LodTileIdx::chunk_width(cell_size);
+        let tile_range = |camera_chunk: usize| {
+            let first_chunk = camera_chunk.saturating_sub(ring_radius);
+            let last_chunk = (camera_chunk + ring_radius - 1).min(MAX_CHUNK_WORLD_WIDTH - 1);
+            (first_chunk / chunk_width)..=(last_chunk / chunk_width)
+        };
+
+        for (tile_x, tile_z) in iproduct!(tile_range(camera_chunk[0]), tile_range(camera_chunk[1]))
+        {
+            let idx = LodTileIdx {
+                cell_size,
+                tile: [tile_x, tile_z],
+            };
+            let [first_chunk_x, first_chunk_z] = idx.first_chunk();
+            let chunk_mask: LodChunkMask = iproduct!(0..chunk_width, 0..chunk_width)
+                .map(|(z, x)| {
+                    let chunk_idx = [first_chunk_x + x, first_chunk_z + z];
+                    cell_size_for_chunk(chunk_idx, camera_chunk, visible_chunk_width)
+                        == Some(cell_size)
+                })
+                .collect();
+            if chunk_mask.contains(&true) {
+                tiles.insert(idx, chunk_mask);
+            }
+        }
+    }
+
+    tiles
+}
+
+pub struct LodTile {
+    pub idx: LodTileIdx,
+    pub chunk_mask: LodChunkMask,
+}
+
+pub struct LodMesh {
+    pub idx: LodTileIdx,
+    pub chunk_mask: LodChunkMask,
+    pub typed_instances_vec: Vec<TypedInstances>,
+    // See `world::ChunkData::y_bounds`
+    pub y_bounds: [f32; 2],
+}
+
+impl LodTile {
+    pub fn compute_mesh(&self) -> LodMesh {
+        let cell_size = self.idx.cell_size;
+        let chunk_width = LodTileIdx::chunk_width(cell_size);
+        let cells_per_chunk = CHUNK_XZ_SIZE / cell_size;
+        let [first_chunk_x, first_chunk_z] = self.idx.first_chunk();
+        let (base_x, base_z) = (first_chunk_x * CHUNK_XZ_SIZE, first_chunk_z * CHUNK_XZ_SIZE);
+
+        // Height of the ground in each drawn cell, sampled at the cell's center
+        let mut heights = vec![None; LOD_TILE_CELLS * LOD_TILE_CELLS];
+        for (cell_z, cell_x) in iproduct!(0..LOD_TILE_CELLS, 0..LOD_TILE_CELLS) {
+            let chunk_offset = (cell_z / cells_per_chunk) * chunk_width + cell_x / cells_per_chunk;
+            if self.chunk_mask[chunk_offset] {
+                heights[cell_z * LOD_TILE_CELLS + cell_x] = Some(map_generation::elevation_at(
+                    [
+                        base_x + cell_x * cell_size + cell_size / 2,
+                        base_z + cell_z * cell_size + cell_size / 2,
+                    ],
+                    MIN_HEIGHT,
+                    MAX_HEIGHT,
+                ) as usize);
+            }
+        }
+        let height_at = |cell_x: isize, cell_z: isize| {
+            let cell_range = 0..LOD_TILE_CELLS as isize;
+            if cell_range.contains(&cell_x) && cell_range.contains(&cell_z) {
+                heights[cell_z as usize * LOD_TILE_CELLS + cell_x as usize]
+            } else {
+                None
+            }
+        };
+
+        let cell_dims = Vector3::new(cell_size as f32, 1.0, cell_size as f32);
+        let cell_face = |face: Face, [cell_x, y, cell_z]: [usize; 3], block_type: BlockType| {
+            let [top_offset, bottom_offset, side_offset] = block_type.texture_atlas_offsets();
+            let (texture_atlas_offset, brightness) = match face {
+                Face::Top => (top_offset, 1.0),
+                Face::Bottom => (bottom_offset, 1.0),
+                Face::Left | Face::Right => (side_offset, 0.7),
+                Face::Front | Face::Back => (side_offset, 0.8),
+            };
+            let rotation = face.rotation();
+            let position = Vector3::new(
+                (base_x + cell_x * cell_size) as f32,
+                y as f32,
+                (base_z + cell_z * cell_size) as f32,
+            ) + face.instance_offset().mul_element_wise(cell_dims);
+            // Stretch the face over the cell along whichever of its edges are horizontal
+            let edge_scale = |edge: Vector3<f32>| {
+                if rotation.rotate_vector(edge).y.abs() > 0.5 {
+                    1.0
+                } else {
+                    cell_size as f32
+                }
+            };
+
+            InstanceRaw::new(
+                position,
+                rotation,
+                texture_atlas_offset,
+                [brightness, brightness, brightness, 1.0],
+                0,
+                NO_AMBIENT_OCCLUSION,
+            )
+            .with_scale([edge_scale(Vector3::unit_x()), edge_scale(Vector3::unit_z())])
+        };
+
+        let dims = [LOD_TILE_CELLS, CHUNK_Y_SIZE, LOD_TILE_CELLS];
+        // Indexed by `Face`
+        let mut face_meshers = Face::ALL.map(|face| GreedyMesher::new(dims, face.rotation()));
+        let mut water_mesher = GreedyMesher::new(dims, Face::Top.rotation());
+        let mut max_height = 0;
+
+        for (cell_z, cell_x) in iproduct!(0..LOD_TILE_CELLS, 0..LOD_TILE_CELLS) {
+            let height = match height_at(cell_x as isize, cell_z as isize) {
+                Some(height) => height,
+                None => continue,
+            };
+            max_height = max_height.max(height);
+
+            // Same layers as `GeneratedChunk::generate`
+            let top_block_type = if height < WATER_HEIGHT as usize {
+                BlockType::Sand
+            } else {
+                BlockType::Grass
+            };
+            let block_type_at = |y: usize| {
+                if y == height {
+                    top_block_type
+                } else if y < WATER_HEIGHT as usize {
+                    BlockType::Sand
+                } else {
+                    BlockType::Dirt
+                }
+            };
+
+            face_meshers[Face::Top as usize].add_face(
+                [cell_x, height, cell_z],
+                cell_face(Face::Top, [cell_x, height, cell_z], top_block_type),
+            );
+
+            if height + 1 < WATER_HEIGHT as usize {
+                let water_y = WATER_HEIGHT as usize - 1;
+                // Lowered like in `ChunkSnapshot::compute_mesh`
+                let water_face = InstanceRaw::new(
+                    Vector3::new(
+                        (base_x + cell_x * cell_size) as f32,
+                        water_y as f32 + WATER_BLOCK_Y_HEIGHT,
+                        (base_z + cell_z * cell_size + cell_size) as f32,
+                    ),
+                    Face::Top.rotation(),
+                    BlockType::Water.texture_atlas_offsets()[0],
+                    [1.0, 1.0, 1.0, 0.7],
+                    INSTANCE_FLAG_WATER_SURFACE,
+                    NO_AMBIENT_OCCLUSION,
+                )
+                .with_scale([cell_size as f32, cell_size as f32]);
+                water_mesher.add_face([cell_x, water_y, cell_z], water_face);
+            }
+
+            // Walls down to the neighboring cells. Next to cells this tile doesn't draw, the
+            // neighbor's height isn't known, so the wall goes all the way down. These walls work as
+            // skirts that cover the cracks between rings, and between the innermost ring and the
+            // full resolution chunks.
+            for face in [Face::Left, Face::Right, Face::Front, Face::Back] {
+                let [offset_x, _, offset_z] = face.offset();
+                let wall_bottom =
+                    match height_at(cell_x as isize + offset_x, cell_z as isize + offset_z) {
+                        Some(neighbor_height) => neighbor_height + 1,
+                        None => 0,
+                    };
+                for y in wall_bottom..=height {
+                    face_meshers[face as usize].add_face(
+                        [cell_x, y, cell_z],
+                        cell_face(face, [cell_x, y, cell_z], block_type_at(y)),
+                    );
+                }
+            }
+        }
+
+        let mut opaque_instances = vec![];
+        for mesher in face_meshers.iter_mut() {
+            mesher.mesh(&mut opaque_instances);
+        }
+        let mut translucent_instances = vec![];
+        water_mesher.mesh(&mut translucent_instances);
+
+        LodMesh {
+            idx: self.idx,
+            chunk_mask: self.chunk_mask.clone(),
+            typed_instances_vec: vec![
+                TypedInstances {
+                    data_type: ChunkDataType::Opaque,
+                    instance_data: opaque_instances,
+                },
+                TypedInstances {
+                    data_type: ChunkDataType::Translucent,
+                    instance_data: translucent_instances,
+                },
+            ],
+            y_bounds: [0.0, (max_height + 1) as f32],
+        }
+    }
+}
+
+impl LodTileIdx {
+    fn encode(&self, writer: &mut ByteWriter) {
+        writer.write_usize(self.cell_size);
+        writer.write_usizes(self.tile);
+    }
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(LodTileIdx {
+            cell_size: reader.read_usize()?,
+            tile: reader.read_usizes()?,
+        })
+    }
+}
+
+fn encode_chunk_mask(chunk_mask: &LodChunkMask, writer: &mut ByteWriter) {
+    let bytes = chunk_mask
+        .iter()
+        .map(|&is_drawn| is_drawn as u8)
+        .collect::<Vec<_>>();
+    writer.write_bytes(&bytes);
+}
+
+fn decode_chunk_mask(reader: &mut ByteReader) -> Option<LodChunkMask> {
+    Some(
+        reader
+            .read_bytes()?
+            .iter()
+            .map(|&is_drawn| is_drawn != 0)
+            .collect(),
+    )
+}
+
+// Encoding for Web Workers, see `jobs::ChunkJob`
+impl LodTile {
+    pub fn encode(&self, writer: &mut ByteWriter) {
+        self.idx.encode(writer);
+        encode_chunk_mask(&self.chunk_mask, writer);
+    }
+
+    pub fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(LodTile {
+            idx: LodTileIdx::decode(reader)?,
+            chunk_mask: decode_chunk_mask(reader)?,
+        })
+    }
+}
+
+impl LodMesh {
+    pub fn encode(&self, writer: &mut ByteWriter) {
+        self.idx.encode(writer);
+        encode_chunk_mask(&self.chunk_mask, writer);
+        world::encode_typed_instances_vec(&self.typed_instances_vec, writer);
+        writer.write_f32s(self.y_bounds);
+    }
+
+    pub fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(LodMesh {
+            idx: LodTileIdx::decode(reader)?,
+            chunk_mask: decode_chunk_mask(reader)?,
+            typed_instances_vec: world::decode_typed_instances_vec(reader)?,
+            y_bounds: reader.read_f32s()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{MAX_VISIBLE_CHUNK_WIDTH, MIN_VISIBLE_CHUNK_WIDTH};
+
+    const VISIBLE_CHUNK_WIDTHS: [usize; 4] =
+        [MIN_VISIBLE_CHUNK_WIDTH, 10, 32, MAX_VISIBLE_CHUNK_WIDTH];
+    // In the middle of the world and next to its edges, where the rings are cut off
+    const CAMERA_CHUNKS: [[usize; 2]; 3] = [
+        [MAX_CHUNK_WORLD_WIDTH / 2, MAX_CHUNK_WORLD_WIDTH / 2],
+        [1, 2],
+        [MAX_CHUNK_WORLD_WIDTH - 1, MAX_CHUNK_WORLD_WIDTH / 2],
+    ];
+
+    fn camera_in_chunk([chunk_x, chunk_z]: [usize; 2]) -> Camera {
+        let eye = cgmath::Point3::new(
+            (chunk_x * CHUNK_XZ_SIZE) as f32 + 0.5,
+            64.0,
+            (chunk_z * CHUNK_XZ_SIZE) as f32 + 0.5,
+        );
+        Camera::new(
+            eye,
+            eye + Vector3::unit_x(),
+            Vector3::unit_y(),
+            Vector3::unit_y(),
+            1.0,
+            70.0,
+            0.1,
+            100.0,
+        )
+    }
+
+    // The cell sizes of the tiles that draw each chunk
+    fn drawn_chunks(
+        camera_chunk: [usize; 2],
+        visible_chunk_width: usize,
+    ) -> HashMap<[usize; 2], Vec<usize>> {
+        let mut drawn_chunks = HashMap::<[usize; 2], Vec<usize>>::new();
+        let tiles = lod_tiles_around(&camera_in_chunk(camera_chunk), visible_chunk_width);
+        for (idx, chunk_mask) in tiles {
+            let chunk_width = LodTileIdx::chunk_width(idx.cell_size);
+            assert_eq!(chunk_mask.len(), chunk_width * chunk_width);
+            let [first_chunk_x, first_chunk_z] = idx.first_chunk();
+            for (z, x) in iproduct!(0..chunk_width, 0..chunk_width) {
+                if chunk_mask[z * chunk_width + x] {
+                    drawn_chunks
+                        .entry([first_chunk_x + x, first_chunk_z + z])
+                        .or_default()
+                        .push(idx.cell_size);
+                }
+            }
+        }
+        drawn_chunks
+    }
+
+    // Smallest radius of a square around the camera chunk that contains the chunk. The squares
+    // reach one chunk less towards +X and +Z, like the full resolution chunks do.
+    fn distance_in_chunks(chunk: [usize; 2], camera_chunk: [usize; 2]) -> usize {
+        let axis_distance = |i: usize| {
+            let offset = chunk[i] as isize - camera_chunk[i] as isize;
+            if offset >= 0 {
+                offset as usize + 1
+            } else {
+                offset.unsigned_abs()
+            }
+        };
+        axis_distance(0).max(axis_distance(1))
+    }
+
+    #[test]
+    fn rings_leave_out_full_resolution_chunks() {
+        // Away from the edges, `WorldState::iter_visible_chunks` doesn't handle those
+        let camera_chunk = CAMERA_CHUNKS[0];
+        for visible_chunk_width in VISIBLE_CHUNK_WIDTHS {
+            let drawn_chunks = drawn_chunks(camera_chunk, visible_chunk_width);
+            // Same chunks as `WorldState::iter_visible_chunks`
+            let first_chunk = camera_chunk.map(|n| n - visible_chunk_width / 2);
+            for (x, z) in iproduct!(
+                first_chunk[0]..first_chunk[0] + visible_chunk_width,
+                first_chunk[1]..first_chunk[1] + visible_chunk_width
+            ) {
+                assert!(
+                    !drawn_chunks.contains_key(&[x, z]),
+                    "chunk {:?} at width {} is drawn at full resolution too",
+                    [x, z],
+                    visible_chunk_width
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rings_draw_every_chunk_out_to_the_view_distance_once() {
+        for (visible_chunk_width, camera_chunk) in iproduct!(VISIBLE_CHUNK_WIDTHS, CAMERA_CHUNKS) {
+            let drawn_chunks = drawn_chunks(camera_chunk, visible_chunk_width);
+            let outer_radius = ring_radius(LOD_CELL_SIZES.len() - 1, visible_chunk_width);
+            assert_eq!(
+                view_distance(visible_chunk_width),
+                (outer_radius * CHUNK_XZ_SIZE) as f32
+            );
+
+            let chunk_range = |camera_chunk: usize| {
+                camera_chunk.saturating_sub(outer_radius + 1)
+                    ..(camera_chunk + outer_radius + 1).min(MAX_CHUNK_WORLD_WIDTH)
+            };
+            let mut num_drawn = 0;
+            for (x, z) in iproduct!(chunk_range(camera_chunk[0]), chunk_range(camera_chunk[1])) {
+                let distance = distance_in_chunks([x, z], camera_chunk);
+                // Each ring draws the chunks between the one inside it and its own radius
+                let expected_cell_size = if distance <= visible_chunk_width / 2 {
+                    None
+                } else {
+                    (0..LOD_CELL_SIZES.len())
+                        .find(|&ring_idx| distance <= ring_radius(ring_idx, visible_chunk_width))
+                        .map(|ring_idx| LOD_CELL_SIZES[ring_idx])
+                };
+                let cell_sizes = drawn_chunks.get(&[x, z]).map(Vec::as_slice);
+                assert_eq!(
+                    cell_sizes,
+                    expected_cell_size.as_ref().map(std::slice::from_ref),
+                    "chunk {:?} at width {} around {:?}",
+                    [x, z],
+                    visible_chunk_width,
+                    camera_chunk
+                );
+                num_drawn += cell_sizes.is_some() as usize;
+            }
+            // Nothing past the outermost ring
+            assert_eq!(num_drawn, drawn_chunks.len());
+        }
+    }
+}
@@ -1,15 +1,17 @@
 use bmp::{Image, Pixel};
 
 use crate::world::CHUNK_XZ_SIZE;
-use noise::NoiseFn;
+use noise::{NoiseFn, Seedable};
+use std::sync::OnceLock;
+
+// Seeds all of the world's noise, so the same seed always makes the same world
+pub const WORLD_SEED: u32 = 0;
 
 const BASE_FREQUENCY: f64 = 5.0 / 16.0;
 const NUM_OCTAVES: usize = 4;
 
-struct WorldNoise {
-    noise: Option<noise::OpenSimplex>,
-}
-static mut NOISE_GENERATOR: WorldNoise = WorldNoise { noise: None };
+// Chunks are generated on worker threads, so the noise generator is shared between threads
+static NOISE_GENERATOR: OnceLock<noise::OpenSimplex> = OnceLock::new();
 
 type ChunkElevationMap = [[u16; CHUNK_XZ_SIZE]; CHUNK_XZ_SIZE];
 
@@ -19,55 +21,46 @@ pub fn generate_chunk_elevation_map(
     min_elevation: u16,
     max_elevation: u16,
 ) -> ChunkElevationMap {
-    let noise = unsafe {
-        match NOISE_GENERATOR.noise {
-            None => {
-                NOISE_GENERATOR.noise = Some(noise::OpenSimplex::new());
-                NOISE_GENERATOR.noise.unwrap()
-            }
-            _ => NOISE_GENERATOR.noise.unwrap(),
-        }
-    };
-
     let base_x = chunk_x * CHUNK_XZ_SIZE;
     let base_z = chunk_z * CHUNK_XZ_SIZE;
 
-    let mut elevation_map_f64 = [[0.0_f64; CHUNK_XZ_SIZE]; CHUNK_XZ_SIZE];
-
-    let max_height = max_elevation - min_elevation;
-
-    for (x, z) in iproduct!(0..CHUNK_XZ_SIZE, 0..CHUNK_XZ_SIZE) {
-        let (world_x, world_z) = (base_x + x, base_z + z);
-        let nx: f64 = ((world_x as f64) / (CHUNK_XZ_SIZE as f64)) * BASE_FREQUENCY;
-        let nz: f64 = ((world_z as f64) / (CHUNK_XZ_SIZE as f64)) * BASE_FREQUENCY;
-
-        let mut elevation = 0.0_f64;
-        let mut sum_of_amplitudes = 0.0_f64;
-
-        for i in 0..NUM_OCTAVES {
-            let octave = i32::pow(2, i as u32) as f64;
-            let amplitude = 1.0 / octave;
-
-            // Normalize [-1.0, 1.0] to [0.0, 1.0]
-            let noise_normalized = (noise.get([octave * nx, octave * nz]) + 1.0) / 2.0;
-            elevation += amplitude * noise_normalized;
-            sum_of_amplitudes += amplitude;
-        }
-
-        elevation /= sum_of_amplitudes;
-        elevation = f64::powf(elevation, 1.4);
-        elevation_map_f64[x][z] = elevation;
-    }
-
     let mut elevation_map_out: ChunkElevationMap = [[0_u16; CHUNK_XZ_SIZE]; CHUNK_XZ_SIZE];
     for (x, z) in iproduct!(0..CHUNK_XZ_SIZE, 0..CHUNK_XZ_SIZE) {
         elevation_map_out[x][z] =
-            (elevation_map_f64[x][z] * max_height as f64).floor() as u16 - min_elevation;
+            elevation_at([base_x + x, base_z + z], min_elevation, max_elevation);
     }
 
     elevation_map_out
 }
 
+// Elevation of a single column, so far away terrain can be sampled sparsely
+pub fn elevation_at([world_x, world_z]: [usize; 2], min_elevation: u16, max_elevation: u16) -> u16 {
+    let noise = NOISE_GENERATOR.get_or_init(|| noise::OpenSimplex::new().set_seed(WORLD_SEED));
+
+    let max_height = max_elevation - min_elevation;
+
+    let nx: f64 = ((world_x as f64) / (CHUNK_XZ_SIZE as f64)) * BASE_FREQUENCY;
+    let nz: f64 = ((world_z as f64) / (CHUNK_XZ_SIZE as f64)) * BASE_FREQUENCY;
+
+    let mut elevation = 0.0_f64;
+    let mut sum_of_amplitudes = 0.0_f64;
+
+    for i in 0..NUM_OCTAVES {
+        let octave = i32::pow(2, i as u32) as f64;
+        let amplitude = 1.0 / octave;
+
+        // Normalize [-1.0, 1.0] to [0.0, 1.0]
+        let noise_normalized = (noise.get([octave * nx, octave * nz]) + 1.0) / 2.0;
+        elevation += amplitude * noise_normalized;
+        sum_of_amplitudes += amplitude;
+    }
+
+    elevation /= sum_of_amplitudes;
+    elevation = f64::powf(elevation, 1.4);
+
+    (elevation * max_height as f64).floor() as u16 - min_elevation
+}
+
 pub fn save_elevation_to_file(elevation_map: ChunkElevationMap, filepath: &str) {
     let mut img = Image::new(CHUNK_XZ_SIZE as u32, CHUNK_XZ_SIZE as u32);
 
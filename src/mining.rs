@@ -0,0 +1,142 @@
+use crate::atlas::Tile;
+use crate::instance::InstanceRaw;
+use crate::world::{BlockType, Face, NO_AMBIENT_OCCLUSION};
+
+// Frames of `Tile::DestroyStages`, from the first crack to nearly broken
+pub const BREAK_STAGE_COUNT: u32 = 8;
+// Seconds per point of hardness to break a block by hand, same as Minecraft
+const BREAK_SECS_PER_HARDNESS: f32 = 1.5;
+// Pause after breaking a block before the next one starts, so holding the button doesn't mow
+// through blocks that break instantly
+const BREAK_COOLDOWN_SECS: f32 = 0.25;
+// The crack overlay sits just outside the block's faces, so it doesn't z-fight with them
+const BREAK_OVERLAY_OFFSET: f32 = 0.002;
+
+// What blocks are mined with, bare hands unless a tool is selected in the hotbar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    Hand,
+    WoodenPickaxe,
+    StonePickaxe,
+    WoodenShovel,
+    WoodenAxe,
+}
+
+impl Tool {
+    // How many times faster than by hand `block_type` breaks. Tools only help on the blocks
+    // they're made for.
+    pub fn mining_speed(self, block_type: BlockType) -> f32 {
+        match (self, block_type) {
+            (Tool::WoodenPickaxe, BlockType::Stone | BlockType::Lantern) => 2.0,
+            (Tool::StonePickaxe, BlockType::Stone | BlockType::Lantern) => 4.0,
+            (Tool::WoodenShovel, BlockType::Dirt | BlockType::Grass | BlockType::Sand) => 2.0,
+            (Tool::WoodenAxe, BlockType::Tree | BlockType::OakPlank) => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    // Health points a hit on an entity takes. Axes hit hardest, pickaxes a bit less.
+    pub fn attack_damage(self) -> f32 {
+        match self {
+            Tool::Hand => 1.0,
+            Tool::WoodenShovel => 2.5,
+            Tool::WoodenPickaxe => 3.0,
+            Tool::StonePickaxe => 4.0,
+            Tool::WoodenAxe => 4.0,
+        }
+    }
+
+    // `None` if the block can't be broken
+    pub fn break_time_secs(self, block_type: BlockType) -> Option<f32> {
+        let hardness = block_type.hardness()?;
+        Some(hardness * BREAK_SECS_PER_HARDNESS / self.mining_speed(block_type))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MiningProgress {
+    Idle,
+    Mining {
+        block_pos: [usize; 3],
+        break_stage: u32,
+    },
+    Broken {
+        block_pos: [usize; 3],
+    },
+}
+
+// Progress on breaking the targeted block while the break button is held
+#[derive(Debug, Default)]
+pub struct Mining {
+    target: Option<[usize; 3]>,
+    // Fraction of the target's break time mined so far
+    progress: f32,
+    cooldown_secs: f32,
+}
+
+impl Mining {
+    // Mines for one fixed tick of `tick_secs`. `target` is the targeted block while the break
+    // button is held, progress starts over whenever it changes.
+    pub fn tick(
+        &mut self,
+        target: Option<([usize; 3], BlockType)>,
+        tool: Tool,
+        tick_secs: f32,
+    ) -> MiningProgress {
+        self.cooldown_secs = (self.cooldown_secs - tick_secs).max(0.0);
+
+        let (block_pos, block_type) = match target {
+            Some(target) => target,
+            None => {
+                self.target = None;
+                self.progress = 0.0;
+                return MiningProgress::Idle;
+            }
+        };
+        if self.target != Some(block_pos) {
+            self.target = Some(block_pos);
+            self.progress = 0.0;
+        }
+        let break_time_secs = match tool.break_time_secs(block_type) {
+            Some(break_time_secs) if self.cooldown_secs == 0.0 => break_time_secs,
+            _ => return MiningProgress::Idle,
+        };
+
+        // Blocks with no hardness break on the first tick
+        self.progress += tick_secs / break_time_secs;
+        if self.progress >= 1.0 {
+            self.target = None;
+            self.progress = 0.0;
+            self.cooldown_secs = BREAK_COOLDOWN_SECS;
+            return MiningProgress::Broken { block_pos };
+        }
+        MiningProgress::Mining {
+            block_pos,
+            break_stage: (self.progress * BREAK_STAGE_COUNT as f32) as u32,
+        }
+    }
+}
+
+// Cracks over every face of the block at `block_pos`, drawn with the translucent faces
+pub fn break_overlay_instances(block_pos: [usize; 3], break_stage: u32) -> Vec<InstanceRaw> {
+    let position = cgmath::Vector3::new(
+        block_pos[0] as f32,
+        block_pos[1] as f32,
+        block_pos[2] as f32,
+    );
+    Face::ALL
+        .into_iter()
+        .map(|face| {
+            let [normal_x, normal_y, normal_z] = face.offset().map(|n| n as f32);
+            let offset = cgmath::Vector3::new(normal_x, normal_y, normal_z) * BREAK_OVERLAY_OFFSET;
+            InstanceRaw::new(
+                position + face.instance_offset() + offset,
+                face.rotation(),
+                Tile::DestroyStages.frame_atlas_offset(break_stage),
+                [1.0, 1.0, 1.0, 1.0],
+                0,
+                NO_AMBIENT_OCCLUSION,
+            )
+        })
+        .collect()
+}
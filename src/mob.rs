@@ -0,0 +1,659 @@
+use crate::atlas::Tile;
+use crate::combat::{Health, Melee};
+use crate::entity::{Ai, Entities, Entity, EntityId, ModelPart, Physics, Renderable};
+use crate::pathfinding::{self, PathSearch, SearchStatus};
+use crate::physics::Collider;
+use crate::world::{BlockType, WorldState, CHARACTER_HALF_HEIGHT, CHARACTER_RADIUS, CHUNK_Y_SIZE};
+
+// Mobs stop spawning around the character once there are this many passive or hostile ones
+pub const MAX_PASSIVE_MOBS: usize = 10;
+pub const MAX_HOSTILE_MOBS: usize = 8;
+const SPAWN_ATTEMPTS_PER_SEC: f32 = 0.5;
+// Mobs spawn out of the way of the character, but close enough to run into
+const MIN_SPAWN_DISTANCE: f32 = 16.0;
+const MAX_SPAWN_DISTANCE: f32 = 40.0;
+// Mobs that wander further from the character than this despawn
+const DESPAWN_DISTANCE: f32 = 96.0;
+// Chance per second that a hostile mob out in the light despawns, so they thin out at dawn
+const LIGHT_DESPAWN_CHANCE_PER_SEC: f32 = 0.05;
+
+const MOB_PHYSICS: Physics = Physics {
+    gravity: 20.0,
+    terminal_velocity: 30.0,
+    // Mobs steer by setting their velocity, this stops them soon after they stop walking
+    ground_friction: 0.01,
+};
+// High enough to jump up one block, see `MOB_PHYSICS`
+const JUMP_VELOCITY: f32 = 7.0;
+// In blocks per second
+const WALK_SPEED: f32 = 1.5;
+const FLEE_SPEED: f32 = 4.0;
+const CHASE_SPEED: f32 = 3.0;
+
+// Blocks a wandering mob picks its next goal within, along each axis
+const WANDER_DISTANCE: f32 = 8.0;
+// Goals are picked at most this many blocks above or below the mob
+const MAX_GOAL_HEIGHT_CHANGE: usize = 4;
+const MIN_IDLE_SECS: f32 = 2.0;
+const MAX_IDLE_SECS: f32 = 8.0;
+// How long a mob runs off for after it's hit, and how far it tries to get each time
+const FLEE_SECS: f32 = 5.0;
+const FLEE_DISTANCE: f32 = 10.0;
+// Hostile mobs go after the character once it's this close
+const CHASE_DISTANCE: f32 = 24.0;
+// Close enough to the character to walk straight at it rather than along a path
+const DIRECT_CHASE_DISTANCE: f32 = 1.5;
+// A chasing mob looks for a new path this often, as the character moves
+const REPATH_SECS: f32 = 1.0;
+// Path search nodes each mob expands per tick, which bounds the time all mobs spend searching
+const SEARCH_NODES_PER_TICK: usize = 32;
+// Horizontal distance from the middle of a waypoint's block to count it as reached
+const WAYPOINT_REACHED_DISTANCE: f32 = 0.3;
+// A mob gives up on its path when it doesn't reach the next waypoint in time
+const STUCK_SECS: f32 = 3.0;
+
+// Models face +Z, the entity's yaw turns them to where they're heading. Parts are positioned
+// relative to the middle of the collider.
+const PIG_RADIUS: f32 = 0.45;
+const PIG_HALF_HEIGHT: f32 = 0.45;
+const PIG_MAX_HEALTH: f32 = 10.0;
+const PIG_SKIN: [Tile; 3] = [Tile::Pig; 3];
+const PIG_LEG_SIZE: glam::Vec3 = glam::Vec3::new(0.2, 0.25, 0.2);
+const PIG_MODEL: [ModelPart; 7] = [
+    // Body
+    ModelPart {
+        offset: glam::Vec3::new(0.0, 0.05, 0.0),
+        size: glam::Vec3::new(0.6, 0.5, 0.9),
+        tiles: PIG_SKIN,
+    },
+    // Head
+    ModelPart {
+        offset: glam::Vec3::new(0.0, 0.15, 0.6),
+        size: glam::Vec3::new(0.5, 0.5, 0.4),
+        tiles: PIG_SKIN,
+    },
+    // Snout
+    ModelPart {
+        offset: glam::Vec3::new(0.0, 0.05, 0.825),
+        size: glam::Vec3::new(0.25, 0.15, 0.05),
+        tiles: [Tile::PigSnout; 3],
+    },
+    // Legs
+    ModelPart {
+        offset: glam::Vec3::new(0.175, -0.325, 0.3),
+        size: PIG_LEG_SIZE,
+        tiles: PIG_SKIN,
+    },
+    ModelPart {
+        offset: glam::Vec3::new(-0.175, -0.325, 0.3),
+        size: PIG_LEG_SIZE,
+        tiles: PIG_SKIN,
+    },
+    ModelPart {
+        offset: glam::Vec3::new(0.175, -0.325, -0.3),
+        size: PIG_LEG_SIZE,
+        tiles: PIG_SKIN,
+    },
+    ModelPart {
+        offset: glam::Vec3::new(-0.175, -0.325, -0.3),
+        size: PIG_LEG_SIZE,
+        tiles: PIG_SKIN,
+    },
+];
+
+// A bit under the character's size, so it fits through the same gaps
+const ZOMBIE_RADIUS: f32 = 0.3;
+const ZOMBIE_HALF_HEIGHT: f32 = 0.95;
+const ZOMBIE_MAX_HEALTH: f32 = 20.0;
+const ZOMBIE_MELEE: Melee = Melee {
+    damage: 3.0,
+    reach: 0.5,
+    cooldown_secs: 1.0,
+    cooldown_secs_left: 0.0,
+};
+const ZOMBIE_SKIN: [Tile; 3] = [Tile::ZombieSkin; 3];
+const ZOMBIE_PANTS: [Tile; 3] = [Tile::ZombiePants; 3];
+const ZOMBIE_LEG_SIZE: glam::Vec3 = glam::Vec3::new(0.25, 0.75, 0.25);
+const ZOMBIE_ARM_SIZE: glam::Vec3 = glam::Vec3::new(0.2, 0.2, 0.7);
+const ZOMBIE_MODEL: [ModelPart; 6] = [
+    // Head
+    ModelPart {
+        offset: glam::Vec3::new(0.0, 0.75, 0.0),
+        size: glam::Vec3::new(0.4, 0.4, 0.4),
+        tiles: ZOMBIE_SKIN,
+    },
+    // Body
+    ModelPart {
+        offset: glam::Vec3::new(0.0, 0.175, 0.0),
+        size: glam::Vec3::new(0.5, 0.75, 0.25),
+        tiles: [Tile::ZombieShirt; 3],
+    },
+    // Arms, held out in front
+    ModelPart {
+        offset: glam::Vec3::new(0.35, 0.45, 0.3),
+        size: ZOMBIE_ARM_SIZE,
+        tiles: ZOMBIE_SKIN,
+    },
+    ModelPart {
+        offset: glam::Vec3::new(-0.35, 0.45, 0.3),
+        size: ZOMBIE_ARM_SIZE,
+        tiles: ZOMBIE_SKIN,
+    },
+    // Legs
+    ModelPart {
+        offset: glam::Vec3::new(0.125, -0.575, 0.0),
+        size: ZOMBIE_LEG_SIZE,
+        tiles: ZOMBIE_PANTS,
+    },
+    ModelPart {
+        offset: glam::Vec3::new(-0.125, -0.575, 0.0),
+        size: ZOMBIE_LEG_SIZE,
+        tiles: ZOMBIE_PANTS,
+    },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MobKind {
+    Pig,
+    Zombie,
+}
+
+impl MobKind {
+    pub const ALL: [MobKind; 2] = [MobKind::Pig, MobKind::Zombie];
+
+    // Hostile mobs spawn in the dark and go after the character
+    pub fn is_hostile(self) -> bool {
+        match self {
+            MobKind::Pig => false,
+            MobKind::Zombie => true,
+        }
+    }
+
+    pub fn collider(self) -> Collider {
+        let (radius, half_height) = match self {
+            MobKind::Pig => (PIG_RADIUS, PIG_HALF_HEIGHT),
+            MobKind::Zombie => (ZOMBIE_RADIUS, ZOMBIE_HALF_HEIGHT),
+        };
+        Collider::Cylinder {
+            radius,
+            half_height,
+        }
+    }
+
+    pub fn model(self) -> &'static [ModelPart] {
+        match self {
+            MobKind::Pig => &PIG_MODEL,
+            MobKind::Zombie => &ZOMBIE_MODEL,
+        }
+    }
+
+    pub fn max_health(self) -> f32 {
+        match self {
+            MobKind::Pig => PIG_MAX_HEALTH,
+            MobKind::Zombie => ZOMBIE_MAX_HEALTH,
+        }
+    }
+
+    pub fn melee(self) -> Option<Melee> {
+        match self {
+            MobKind::Pig => None,
+            MobKind::Zombie => Some(ZOMBIE_MELEE),
+        }
+    }
+
+    // Whether the mob spawns standing on `block_type`
+    pub fn spawns_on(self, block_type: BlockType) -> bool {
+        match self {
+            MobKind::Pig => block_type == BlockType::Grass,
+            MobKind::Zombie => matches!(
+                block_type,
+                BlockType::Grass | BlockType::Dirt | BlockType::Sand | BlockType::Stone
+            ),
+        }
+    }
+
+    fn ai(self) -> Box<dyn Ai> {
+        match self {
+            MobKind::Pig => Box::new(PassiveMobAi::new()),
+            MobKind::Zombie => Box::new(HostileMobAi::new()),
+        }
+    }
+}
+
+// Spawns a mob standing with its feet in the block at `feet_pos`, facing a random direction
+pub fn spawn(entities: &mut Entities, kind: MobKind, feet_pos: [usize; 3]) -> EntityId {
+    let collider = kind.collider();
+    let [x, y, z] = feet_pos.map(|n| n as f32);
+    let mut entity = Entity::new(
+        glam::Vec3::new(x + 0.5, y + collider.half_extents().y, z + 0.5),
+        collider,
+    );
+    entity.yaw = rand::random::<f32>() * std::f32::consts::TAU;
+    entity.physics = Some(MOB_PHYSICS);
+    entity.renderable = Some(Renderable::Model(kind.model()));
+    entity.mob = Some(kind);
+    entity.health = Some(Health::new(kind.max_health()));
+    entity.melee = kind.melee();
+    entity.ai = Some(kind.ai());
+    entities.spawn(entity)
+}
+
+// Now and then spawns mobs on the surface somewhere around the character, when the top block
+// there is one they spawn on. Hostile mobs only spawn where `is_dark`, and now and then despawn
+// where it isn't. Despawns the mobs that wandered too far off, so new ones spawn closer.
+// `block_at` gives the block type at a position, empty where the world isn't generated.
+pub fn spawn_mobs(
+    entities: &mut Entities,
+    block_at: impl Fn([usize; 3]) -> BlockType,
+    is_dark: impl Fn([usize; 3]) -> bool,
+    character_position: glam::Vec3,
+    tick_secs: f32,
+) {
+    let horizontal_distance = |position: glam::Vec3| {
+        glam::Vec2::new(
+            position.x - character_position.x,
+            position.z - character_position.z,
+        )
+        .length()
+    };
+    entities.retain(|entity| {
+        let Some(kind) = entity.mob else {
+            return true;
+        };
+        let is_fading = kind.is_hostile()
+            && !is_dark(feet_block(entity))
+            && rand::random::<f32>() < LIGHT_DESPAWN_CHANCE_PER_SEC * tick_secs;
+        horizontal_distance(entity.position) <= DESPAWN_DISTANCE && !is_fading
+    });
+
+    for kind in MobKind::ALL {
+        let max_count = if kind.is_hostile() {
+            MAX_HOSTILE_MOBS
+        } else {
+            MAX_PASSIVE_MOBS
+        };
+        let count = entities
+            .iter()
+            .filter(|(_, entity)| {
+                entity
+                    .mob
+                    .is_some_and(|mob| mob.is_hostile() == kind.is_hostile())
+            })
+            .count();
+        if count >= max_count || rand::random::<f32>() >= SPAWN_ATTEMPTS_PER_SEC * tick_secs {
+            continue;
+        }
+
+        let angle = rand::random::<f32>() * std::f32::consts::TAU;
+        let distance =
+            MIN_SPAWN_DISTANCE + rand::random::<f32>() * (MAX_SPAWN_DISTANCE - MIN_SPAWN_DISTANCE);
+        let x = character_position.x + angle.cos() * distance;
+        let z = character_position.z + angle.sin() * distance;
+        if x < 0.0 || z < 0.0 {
+            continue;
+        }
+        let [x, z] = [x as usize, z as usize];
+
+        // On top of the highest solid block, under any flowers
+        let Some(ground_y) = (0..CHUNK_Y_SIZE)
+            .rev()
+            .find(|&y| block_at([x, y, z]).is_collidable())
+        else {
+            continue;
+        };
+        let feet_pos = [x, ground_y + 1, z];
+        if kind.spawns_on(block_at([x, ground_y, z]))
+            && pathfinding::is_standable(&block_at, feet_pos)
+            && (!kind.is_hostile() || is_dark(feet_pos))
+        {
+            spawn(entities, kind, feet_pos);
+        }
+    }
+}
+
+// The block the entity's feet are in
+fn feet_block(entity: &Entity) -> [usize; 3] {
+    // Entities resting on the ground sink into it a tiny bit, see `physics::move_and_collide`
+    let feet_y = entity.position.y - entity.collider.half_extents().y + 0.1;
+    [entity.position.x, feet_y, entity.position.z].map(|n| n.max(0.0) as usize)
+}
+
+fn random_idle_secs() -> f32 {
+    MIN_IDLE_SECS + rand::random::<f32>() * (MAX_IDLE_SECS - MIN_IDLE_SECS)
+}
+
+// The highest standable block in the column at `block_pos`, at most `MAX_GOAL_HEIGHT_CHANGE`
+// blocks above or below it
+fn standable_near(
+    block_at: &impl Fn([usize; 3]) -> BlockType,
+    [x, y, z]: [usize; 3],
+) -> Option<[usize; 3]> {
+    (y.saturating_sub(MAX_GOAL_HEIGHT_CHANGE)..=y + MAX_GOAL_HEIGHT_CHANGE)
+        .rev()
+        .map(|y| [x, y, z])
+        .find(|&goal| pathfinding::is_standable(block_at, goal))
+}
+
+// A standable block to walk to from `feet_pos`, picked at random nearby or, when fleeing, roughly
+// `FLEE_DISTANCE` blocks off in the `flee_direction`
+fn pick_goal(
+    block_at: &impl Fn([usize; 3]) -> BlockType,
+    feet_pos: [usize; 3],
+    flee_direction: Option<glam::Vec2>,
+) -> Option<[usize; 3]> {
+    let random_offset = glam::Vec2::new(
+        rand::random::<f32>() * 2.0 - 1.0,
+        rand::random::<f32>() * 2.0 - 1.0,
+    ) * WANDER_DISTANCE;
+    let offset = match flee_direction {
+        Some(direction) => direction * FLEE_DISTANCE + random_offset / 2.0,
+        None => random_offset,
+    };
+    let x = (feet_pos[0] as f32 + offset.x).max(0.0) as usize;
+    let z = (feet_pos[2] as f32 + offset.y).max(0.0) as usize;
+    standable_near(block_at, [x, feet_pos[1], z])
+}
+
+// Walks towards `target` at `speed` and faces it, slowing down to not overshoot it. Mobs can't
+// steer while they're hurt, so they get knocked back.
+fn steer_towards(entity: &mut Entity, target: glam::Vec3, speed: f32, tick_secs: f32) {
+    if entity.health.is_some_and(|health| health.is_hurt()) {
+        return;
+    }
+    let offset = glam::Vec2::new(target.x - entity.position.x, target.z - entity.position.z);
+    let velocity = offset.normalize_or_zero() * speed.min(offset.length() / tick_secs);
+    entity.velocity.x = velocity.x;
+    entity.velocity.z = velocity.y;
+    if offset != glam::Vec2::ZERO {
+        entity.yaw = offset.x.atan2(offset.y);
+    }
+}
+
+#[derive(Debug, Default)]
+enum Route {
+    #[default]
+    None,
+    // Runs a few nodes of the search every tick, see `SEARCH_NODES_PER_TICK`
+    Searching(PathSearch),
+    Following {
+        path: Vec<[usize; 3]>,
+        next_idx: usize,
+        secs_on_waypoint: f32,
+    },
+}
+
+// Finds paths for a mob and walks it along them
+#[derive(Debug, Default)]
+struct Navigator {
+    route: Route,
+    // A new path searched for while the mob keeps following its current one, see `repath`. Starts
+    // from the waypoint the mob was heading for.
+    next_search: Option<([usize; 3], PathSearch)>,
+}
+
+impl Navigator {
+    fn is_idle(&self) -> bool {
+        matches!(self.route, Route::None)
+    }
+
+    fn go_to(&mut self, entity: &Entity, goal: [usize; 3]) {
+        self.route = Route::Searching(PathSearch::new(feet_block(entity), goal));
+        self.next_search = None;
+    }
+
+    // Like `go_to`, but a mob that's following a path keeps walking along it until the new one's
+    // found, rather than standing still while it's searched for
+    fn repath(&mut self, entity: &Entity, goal: [usize; 3]) {
+        match &self.route {
+            Route::Following { path, next_idx, .. } if *next_idx < path.len() => {
+                let start = path[*next_idx];
+                self.next_search = Some((start, PathSearch::new(start, goal)));
+            }
+            _ => self.go_to(entity, goal),
+        }
+    }
+
+    fn stop(&mut self) {
+        self.route = Route::None;
+        self.next_search = None;
+    }
+
+    // Searches on for the path or walks along it at `speed`. Goes idle once the goal is reached,
+    // when there's no path to it, or when the mob gets stuck on the way.
+    fn tick(
+        &mut self,
+        entity: &mut Entity,
+        block_at: impl Fn([usize; 3]) -> BlockType,
+        speed: f32,
+        tick_secs: f32,
+    ) {
+        if let Some((start, search)) = &mut self.next_search {
+            match search.step(&block_at, SEARCH_NODES_PER_TICK) {
+                SearchStatus::InProgress => {}
+                SearchStatus::Found(path) => {
+                    // Still heading for the same waypoint, so it stays stuck just as long
+                    let secs_on_waypoint = match self.route {
+                        Route::Following {
+                            secs_on_waypoint, ..
+                        } => secs_on_waypoint,
+                        _ => 0.0,
+                    };
+                    self.route = Route::Following {
+                        path: std::iter::once(*start).chain(path).collect(),
+                        next_idx: 0,
+                        secs_on_waypoint,
+                    };
+                    self.next_search = None;
+                }
+                // The current path is still the best it has
+                SearchStatus::NotFound => self.next_search = None,
+            }
+        }
+        let next_route = match &mut self.route {
+            Route::None => None,
+            Route::Searching(search) => match search.step(block_at, SEARCH_NODES_PER_TICK) {
+                SearchStatus::InProgress => None,
+                SearchStatus::Found(path) => Some(Route::Following {
+                    path,
+                    next_idx: 0,
+                    secs_on_waypoint: 0.0,
+                }),
+                SearchStatus::NotFound => Some(Route::None),
+            },
+            Route::Following {
+                path,
+                next_idx,
+                secs_on_waypoint,
+            } => {
+                let feet_y = entity.position.y - entity.collider.half_extents().y;
+                let center = |[x, y, z]: [usize; 3]| {
+                    glam::Vec3::new(x as f32 + 0.5, y as f32, z as f32 + 0.5)
+                };
+                while let Some(&waypoint) = path.get(*next_idx) {
+                    let offset = center(waypoint) - entity.position;
+                    if glam::Vec2::new(offset.x, offset.z).length() >= WAYPOINT_REACHED_DISTANCE
+                        || (feet_y - waypoint[1] as f32).abs() >= 0.5
+                    {
+                        break;
+                    }
+                    *next_idx += 1;
+                    *secs_on_waypoint = 0.0;
+                }
+                *secs_on_waypoint += tick_secs;
+
+                match path.get(*next_idx) {
+                    Some(&waypoint) if *secs_on_waypoint <= STUCK_SECS => {
+                        steer_towards(entity, center(waypoint), speed, tick_secs);
+                        if waypoint[1] > feet_block(entity)[1] && entity.is_on_ground {
+                            entity.velocity.y = JUMP_VELOCITY;
+                        }
+                        None
+                    }
+                    _ => Some(Route::None),
+                }
+            }
+        };
+        if let Some(next_route) = next_route {
+            self.route = next_route;
+        }
+    }
+}
+
+// Once the mob's been idle for a while, sends it off to a goal from `pick_goal`. Fleeing mobs
+// look for the next place to run to right away.
+fn wander(
+    navigator: &mut Navigator,
+    idle_secs_left: &mut f32,
+    entity: &Entity,
+    block_at: &impl Fn([usize; 3]) -> BlockType,
+    flee_direction: Option<glam::Vec2>,
+    tick_secs: f32,
+) {
+    if !navigator.is_idle() {
+        return;
+    }
+    *idle_secs_left -= tick_secs;
+    if *idle_secs_left > 0.0 || !entity.is_on_ground {
+        return;
+    }
+    if let Some(goal) = pick_goal(block_at, feet_block(entity), flee_direction) {
+        navigator.go_to(entity, goal);
+    }
+    *idle_secs_left = if flee_direction.is_some() {
+        0.0
+    } else {
+        random_idle_secs()
+    };
+}
+
+// Animals that wander around, walking to random nearby blocks, and run off when they're hit
+#[derive(Debug)]
+pub struct PassiveMobAi {
+    navigator: Navigator,
+    idle_secs_left: f32,
+    // Where it was last hit from, it keeps running away from there for `flee_secs_left`
+    flee_from: glam::Vec3,
+    flee_secs_left: f32,
+}
+
+impl PassiveMobAi {
+    pub fn new() -> Self {
+        PassiveMobAi {
+            navigator: Navigator::default(),
+            idle_secs_left: random_idle_secs(),
+            flee_from: glam::Vec3::ZERO,
+            flee_secs_left: 0.0,
+        }
+    }
+}
+
+impl Default for PassiveMobAi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ai for PassiveMobAi {
+    fn tick(&mut self, entity: &mut Entity, world: &WorldState, tick_secs: f32) {
+        self.flee_secs_left = (self.flee_secs_left - tick_secs).max(0.0);
+        let is_fleeing = self.flee_secs_left > 0.0;
+        let block_at = |block_pos| world.block_type_at(block_pos);
+
+        let flee_direction = is_fleeing.then(|| {
+            let away = entity.position - self.flee_from;
+            glam::Vec2::new(away.x, away.z).normalize_or_zero()
+        });
+        wander(
+            &mut self.navigator,
+            &mut self.idle_secs_left,
+            entity,
+            &block_at,
+            flee_direction,
+            tick_secs,
+        );
+        let speed = if is_fleeing { FLEE_SPEED } else { WALK_SPEED };
+        self.navigator.tick(entity, block_at, speed, tick_secs);
+    }
+
+    fn on_hit(&mut self, _entity: &mut Entity, attacker_position: glam::Vec3) {
+        self.flee_from = attacker_position;
+        self.flee_secs_left = FLEE_SECS;
+        self.idle_secs_left = 0.0;
+        self.navigator.stop();
+    }
+}
+
+// Monsters that wander around like animals until the character comes close, then chase it down.
+// The entity's `Melee` does the attacking, see `combat::melee_tick`.
+#[derive(Debug)]
+pub struct HostileMobAi {
+    navigator: Navigator,
+    idle_secs_left: f32,
+    repath_secs_left: f32,
+}
+
+impl HostileMobAi {
+    pub fn new() -> Self {
+        HostileMobAi {
+            navigator: Navigator::default(),
+            idle_secs_left: random_idle_secs(),
+            repath_secs_left: 0.0,
+        }
+    }
+}
+
+impl Default for HostileMobAi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ai for HostileMobAi {
+    fn tick(&mut self, entity: &mut Entity, world: &WorldState, tick_secs: f32) {
+        let block_at = |block_pos| world.block_type_at(block_pos);
+        let character_position = world.character_entity.position;
+        let offset = character_position - entity.position;
+
+        if offset.length() > CHASE_DISTANCE {
+            wander(
+                &mut self.navigator,
+                &mut self.idle_secs_left,
+                entity,
+                &block_at,
+                None,
+                tick_secs,
+            );
+            self.navigator.tick(entity, block_at, WALK_SPEED, tick_secs);
+            return;
+        }
+
+        let horizontal_offset = glam::Vec2::new(offset.x, offset.z);
+        if horizontal_offset.length() < DIRECT_CHASE_DISTANCE
+            && offset.y.abs() < CHARACTER_HALF_HEIGHT
+        {
+            self.navigator.stop();
+            // Stops where the colliders touch, the character is in reach from there
+            let touching_distance = entity.collider.half_extents().x + CHARACTER_RADIUS;
+            let stop_offset = horizontal_offset.normalize_or_zero() * touching_distance;
+            let target = character_position - glam::Vec3::new(stop_offset.x, 0.0, stop_offset.y);
+            steer_towards(entity, target, CHASE_SPEED, tick_secs);
+            return;
+        }
+
+        self.repath_secs_left -= tick_secs;
+        if (self.navigator.is_idle() || self.repath_secs_left <= 0.0) && entity.is_on_ground {
+            self.repath_secs_left = REPATH_SECS;
+            let character_feet = [
+                character_position.x,
+                character_position.y - CHARACTER_HALF_HEIGHT + 0.1,
+                character_position.z,
+            ]
+            .map(|n| n.max(0.0) as usize);
+            if let Some(goal) = standable_near(&block_at, character_feet) {
+                self.navigator.repath(entity, goal);
+            }
+        }
+        self.navigator
+            .tick(entity, block_at, CHASE_SPEED, tick_secs);
+    }
+}
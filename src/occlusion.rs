@@ -0,0 +1,327 @@
+use crate::byte_codec::{ByteReader, ByteWriter};
+use crate::camera::Camera;
+use crate::world::{Face, CHUNK_XZ_SIZE, CHUNK_Y_SIZE};
+use cgmath::Point3;
+use collision::{Aabb3, Relation};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// Chunks are split into cubic sections along Y for occlusion culling
+pub const SECTION_SIZE: usize = CHUNK_XZ_SIZE;
+pub const NUM_SECTIONS: usize = CHUNK_Y_SIZE / SECTION_SIZE;
+const NUM_BLOCKS_IN_SECTION: usize = SECTION_SIZE * SECTION_SIZE * SECTION_SIZE;
+
+// Which pairs of faces of a section can see each other through non-opaque blocks
+//
+// https://tomcc.github.io/2014/08/31/visibility-1.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SectionConnectivity {
+    // Bit `from * 6 + to` is set if face `from` connects to face `to`
+    bitmap: u64,
+}
+
+impl SectionConnectivity {
+    pub const NONE: Self = Self { bitmap: 0 };
+    pub const ALL: Self = Self {
+        bitmap: (1 << 36) - 1,
+    };
+
+    pub fn connects(self, from: Face, to: Face) -> bool {
+        self.bitmap & (1 << (from as usize * 6 + to as usize)) != 0
+    }
+
+    // Connects every pair of faces in `faces`, a bitmap indexed by `Face`
+    fn connect_all(&mut self, faces: u8) {
+        for (from, to) in iproduct!(0..6, 0..6) {
+            if faces & (1 << from) != 0 && faces & (1 << to) != 0 {
+                self.bitmap |= 1 << (from * 6 + to);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChunkConnectivity {
+    // Bottom to top
+    pub sections: [SectionConnectivity; NUM_SECTIONS],
+}
+
+impl ChunkConnectivity {
+    // For chunks we don't know anything about yet, so they never hide anything
+    pub const ALL: Self = Self {
+        sections: [SectionConnectivity::ALL; NUM_SECTIONS],
+    };
+
+    // `is_opaque` takes chunk relative block coordinates
+    pub fn compute(is_opaque: impl Fn([usize; 3]) -> bool) -> Self {
+        let mut sections = [SectionConnectivity::NONE; NUM_SECTIONS];
+
+        // Flood fill the non-opaque blocks of each section. Blocks in the same fill can see each
+        // other, so all of the section faces a fill touches can see each other too.
+        let mut is_visited = vec![false; NUM_BLOCKS_IN_SECTION];
+        let mut stack = Vec::<[usize; 3]>::with_capacity(NUM_BLOCKS_IN_SECTION);
+        let section_idx = |[x, y, z]: [usize; 3]| (y * SECTION_SIZE + z) * SECTION_SIZE + x;
+
+        for (section_y, connectivity) in sections.iter_mut().enumerate() {
+            let y_offset = section_y * SECTION_SIZE;
+            let mut num_opaque = 0;
+            for (x, y, z) in iproduct!(0..SECTION_SIZE, 0..SECTION_SIZE, 0..SECTION_SIZE) {
+                let is_block_opaque = is_opaque([x, y + y_offset, z]);
+                is_visited[section_idx([x, y, z])] = is_block_opaque;
+                num_opaque += is_block_opaque as usize;
+            }
+
+            // Skip the fills for the common cases of sky and solid ground
+            if num_opaque == 0 {
+                *connectivity = SectionConnectivity::ALL;
+                continue;
+            }
+            if num_opaque == NUM_BLOCKS_IN_SECTION {
+                continue;
+            }
+
+            for start in iproduct!(0..SECTION_SIZE, 0..SECTION_SIZE, 0..SECTION_SIZE) {
+                let start = [start.0, start.1, start.2];
+                if is_visited[section_idx(start)] {
+                    continue;
+                }
+                is_visited[section_idx(start)] = true;
+                stack.push(start);
+
+                let mut touched_faces = 0u8;
+                while let Some(pos) = stack.pop() {
+                    for face in Face::ALL {
+                        let neighbor_pos = [0, 1, 2].map(|i| pos[i] as isize + face.offset()[i]);
+                        if neighbor_pos
+                            .iter()
+                            .any(|&p| p < 0 || p >= SECTION_SIZE as isize)
+                        {
+                            touched_faces |= 1 << face as usize;
+                            continue;
+                        }
+
+                        let neighbor_pos = neighbor_pos.map(|p| p as usize);
+                        if !is_visited[section_idx(neighbor_pos)] {
+                            is_visited[section_idx(neighbor_pos)] = true;
+                            stack.push(neighbor_pos);
+                        }
+                    }
+                }
+                connectivity.connect_all(touched_faces);
+            }
+        }
+
+        Self { sections }
+    }
+
+    pub fn encode(&self, writer: &mut ByteWriter) {
+        for section in self.sections.iter() {
+            writer.write_u64(section.bitmap);
+        }
+    }
+
+    pub fn decode(reader: &mut ByteReader) -> Option<Self> {
+        let mut sections = [SectionConnectivity::NONE; NUM_SECTIONS];
+        for section in sections.iter_mut() {
+            section.bitmap = reader.read_u64()?;
+        }
+        Some(Self { sections })
+    }
+}
+
+// Section position in world coordinates: chunk x, section y, chunk z
+type SectionPos = [usize; 3];
+
+fn section_aabb([chunk_x, section_y, chunk_z]: SectionPos) -> Aabb3<f32> {
+    let min = Point3::new(
+        (chunk_x * CHUNK_XZ_SIZE) as f32,
+        (section_y * SECTION_SIZE) as f32,
+        (chunk_z * CHUNK_XZ_SIZE) as f32,
+    );
+    Aabb3::new(
+        min,
+        Point3::new(
+            min.x + SECTION_SIZE as f32,
+            min.y + SECTION_SIZE as f32,
+            min.z + SECTION_SIZE as f32,
+        ),
+    )
+}
+
+// Finds the chunks that might be visible from the camera, by walking from the camera's section
+// through connected section faces. Walks only away from the camera and only through sections in
+// the view frustum. `chunk_connectivity` is looked up for chunks in `visible_chunks`.
+pub fn find_visible_chunks<'a>(
+    camera: &Camera,
+    visible_chunks: &HashSet<[usize; 2]>,
+    chunk_connectivity: impl Fn([usize; 2]) -> &'a ChunkConnectivity,
+) -> HashSet<[usize; 2]> {
+    let camera_chunk = [
+        (camera.eye.x / CHUNK_XZ_SIZE as f32) as usize,
+        (camera.eye.z / CHUNK_XZ_SIZE as f32) as usize,
+    ];
+    let mut result = HashSet::new();
+    if !visible_chunks.contains(&camera_chunk) {
+        return result;
+    }
+
+    let camera_section_y =
+        ((camera.eye.y.max(0.0) / SECTION_SIZE as f32) as usize).min(NUM_SECTIONS - 1);
+    let camera_section = [camera_chunk[0], camera_section_y, camera_chunk[1]];
+
+    let mut connectivity_cache = HashMap::<[usize; 2], &ChunkConnectivity>::new();
+    let mut visited_sections = HashSet::<SectionPos>::from([camera_section]);
+    // Section, the face we entered it through, and the directions walked so far as a bitmap
+    // indexed by `Face`
+    let mut queue = VecDeque::<(SectionPos, Option<Face>, u8)>::new();
+    queue.push_back((camera_section, None, 0));
+
+    while let Some((section_pos, entered_through, walked_directions)) = queue.pop_front() {
+        let [chunk_x, section_y, chunk_z] = section_pos;
+        result.insert([chunk_x, chunk_z]);
+
+        let section_connectivity = connectivity_cache
+            .entry([chunk_x, chunk_z])
+            .or_insert_with(|| chunk_connectivity([chunk_x, chunk_z]))
+            .sections[section_y];
+
+        for face in Face::ALL {
+            // Going back towards the camera can't reveal anything new
+            if walked_directions & (1 << face.opposite() as usize) != 0 {
+                continue;
+            }
+            if let Some(entered_through) = entered_through {
+                if !section_connectivity.connects(entered_through, face) {
+                    continue;
+                }
+            }
+
+            let offset = face.offset();
+            let neighbor_pos = [0, 1, 2].map(|i| section_pos[i] as isize + offset[i]);
+            if neighbor_pos[1] < 0 || neighbor_pos[1] >= NUM_SECTIONS as isize {
+                continue;
+            }
+            let neighbor_pos = neighbor_pos.map(|p| p as usize);
+            if !visible_chunks.contains(&[neighbor_pos[0], neighbor_pos[2]])
+                || visited_sections.contains(&neighbor_pos)
+                || camera.frustum.contains(&section_aabb(neighbor_pos)) == Relation::Out
+            {
+                continue;
+            }
+
+            visited_sections.insert(neighbor_pos);
+            queue.push_back((
+                neighbor_pos,
+                Some(face.opposite()),
+                walked_directions | (1 << face as usize),
+            ));
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Middle of the chunk's section 8, see `tunnel`
+    const TUNNEL_Y: usize = 8 * SECTION_SIZE + 8;
+    const TUNNEL_Z: usize = 8;
+
+    // Solid, but for a tunnel along X through the middle of section 8
+    fn tunnel([_, y, z]: [usize; 3]) -> bool {
+        !(y == TUNNEL_Y && z == TUNNEL_Z)
+    }
+
+    #[test]
+    fn empty_and_solid_chunks() {
+        let empty = ChunkConnectivity::compute(|_| false);
+        assert_eq!(empty.sections, [SectionConnectivity::ALL; NUM_SECTIONS]);
+        let solid = ChunkConnectivity::compute(|_| true);
+        assert_eq!(solid.sections, [SectionConnectivity::NONE; NUM_SECTIONS]);
+    }
+
+    #[test]
+    fn wall_blocks_the_faces_on_either_side() {
+        let connectivity = ChunkConnectivity::compute(|[x, _, _]| x == SECTION_SIZE / 2);
+        for section in connectivity.sections {
+            assert!(!section.connects(Face::Left, Face::Right));
+            assert!(!section.connects(Face::Right, Face::Left));
+            // Either side of the wall still touches the rest of the faces
+            assert!(section.connects(Face::Left, Face::Top));
+            assert!(section.connects(Face::Right, Face::Front));
+            assert!(section.connects(Face::Top, Face::Bottom));
+            assert!(section.connects(Face::Front, Face::Back));
+        }
+    }
+
+    #[test]
+    fn tunnel_connects_opposite_faces() {
+        let connectivity = ChunkConnectivity::compute(tunnel);
+        for (section_y, section) in connectivity.sections.into_iter().enumerate() {
+            if section_y == TUNNEL_Y / SECTION_SIZE {
+                assert!(section.connects(Face::Left, Face::Right));
+                assert!(section.connects(Face::Right, Face::Left));
+                assert!(!section.connects(Face::Left, Face::Top));
+                assert!(!section.connects(Face::Front, Face::Back));
+            } else {
+                assert_eq!(section, SectionConnectivity::NONE);
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_behind_a_wall_are_culled() {
+        // Looking along +X down a row of chunks, from inside the second one
+        let eye = Point3::new(24.5, TUNNEL_Y as f32 + 0.5, TUNNEL_Z as f32 + 16.5);
+        let camera = Camera::new(
+            eye,
+            Point3::new(eye.x + 1.0, eye.y, eye.z),
+            cgmath::Vector3::unit_y(),
+            cgmath::Vector3::unit_y(),
+            1.0,
+            70.0,
+            0.1,
+            200.0,
+        );
+        let visible_chunks: HashSet<[usize; 2]> =
+            iproduct!(0..6, 0..3).map(|(x, z)| [x, z]).collect();
+        let open = ChunkConnectivity::ALL;
+        let solid = ChunkConnectivity::compute(|_| true);
+        let tunnel = ChunkConnectivity::compute(tunnel);
+
+        // Nothing in the way
+        let chunks = find_visible_chunks(&camera, &visible_chunks, |_| &open);
+        assert!(chunks.contains(&[1, 1]));
+        assert!(chunks.contains(&[5, 1]));
+        // Behind the camera
+        assert!(!chunks.contains(&[0, 1]));
+
+        // A solid wall of chunks across the row hides everything past it
+        let wall_x = 3;
+        let chunks = find_visible_chunks(&camera, &visible_chunks, |[x, _]| {
+            if x == wall_x {
+                &solid
+            } else {
+                &open
+            }
+        });
+        assert!(chunks.contains(&[2, 1]));
+        assert!(chunks.contains(&[wall_x, 1]));
+        assert!(!chunks.contains(&[4, 1]));
+        assert!(!chunks.contains(&[5, 1]));
+
+        // Until there's a tunnel through it in front of the camera
+        let chunks = find_visible_chunks(&camera, &visible_chunks, |[x, z]| {
+            if x != wall_x {
+                &open
+            } else if z == 1 {
+                &tunnel
+            } else {
+                &solid
+            }
+        });
+        assert!(chunks.contains(&[4, 1]));
+        assert!(chunks.contains(&[5, 1]));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::world::BlockType;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+// Nodes a search expands before giving up, which keeps searches for unreachable goals short
+pub const MAX_SEARCH_NODES: usize = 1024;
+// Blocks of room a mob needs above the ground to walk, as tall as the character
+pub const CLEARANCE_HEIGHT: usize = 2;
+// Costs are in tenths of a block walked
+const WALK_COST: u32 = 10;
+// Jumping up a step or dropping down one costs a bit more than walking
+const STEP_COST: u32 = 5;
+
+// Whether a mob can walk through the block, it stays out of water
+fn is_passable(block_at: &impl Fn([usize; 3]) -> BlockType, block_pos: [usize; 3]) -> bool {
+    let block_type = block_at(block_pos);
+    !block_type.is_collidable() && block_type != BlockType::Water
+}
+
+// Whether a mob can stand with its feet in the block at `block_pos`: on solid ground, with
+// `CLEARANCE_HEIGHT` passable blocks from its feet up. `block_at` gives the block type at a
+// position, empty outside the world.
+pub fn is_standable(block_at: &impl Fn([usize; 3]) -> BlockType, [x, y, z]: [usize; 3]) -> bool {
+    y > 0
+        && block_at([x, y - 1, z]).is_collidable()
+        && (0..CLEARANCE_HEIGHT).all(|dy| is_passable(block_at, [x, y + dy, z]))
+}
+
+// Where a mob can go from `node` in one step, and what it costs. Mobs walk along the axes, jump
+// up steps of one block and drop down at most one block, so they don't walk off cliffs.
+fn neighbors(
+    block_at: &impl Fn([usize; 3]) -> BlockType,
+    [x, y, z]: [usize; 3],
+) -> Vec<([usize; 3], u32)> {
+    let mut neighbors = vec![];
+    for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+        let (Some(nx), Some(nz)) = (x.checked_add_signed(dx), z.checked_add_signed(dz)) else {
+            continue;
+        };
+        if is_standable(block_at, [nx, y, nz]) {
+            neighbors.push(([nx, y, nz], WALK_COST));
+        } else if is_standable(block_at, [nx, y + 1, nz])
+            // Room above the head to jump before moving over
+            && is_passable(block_at, [x, y + CLEARANCE_HEIGHT, z])
+        {
+            neighbors.push(([nx, y + 1, nz], WALK_COST + STEP_COST));
+        } else if y > 1
+            && is_standable(block_at, [nx, y - 1, nz])
+            // Room for the head while moving over, before dropping down
+            && is_passable(block_at, [nx, y + CLEARANCE_HEIGHT - 1, nz])
+        {
+            neighbors.push(([nx, y - 1, nz], WALK_COST + STEP_COST));
+        }
+    }
+    neighbors
+}
+
+// Never more than the cost of the cheapest path, so the first path found is a cheapest one
+fn estimated_cost(from: [usize; 3], to: [usize; 3]) -> u32 {
+    let distance = |axis: usize| from[axis].abs_diff(to[axis]) as u32;
+    WALK_COST * (distance(0) + distance(2)) + STEP_COST * distance(1)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SearchStatus {
+    InProgress,
+    // The nodes after the start, up to and including the goal
+    Found(Vec<[usize; 3]>),
+    NotFound,
+}
+
+// An A* search for a walkable path between standable blocks, see `is_standable`. It can be run a
+// few nodes at a time, so many mobs searching at once don't hold up a tick.
+#[derive(Debug)]
+pub struct PathSearch {
+    goal: [usize; 3],
+    // Nodes to expand, cheapest estimated total cost first. Of those, the ones furthest along go
+    // first, so open ground doesn't get searched in every direction.
+    open: BinaryHeap<(Reverse<u32>, u32, [usize; 3])>,
+    // Cheapest known cost to each node seen so far, and the node it's reached from
+    came_from: HashMap<[usize; 3], (u32, Option<[usize; 3]>)>,
+    expanded_count: usize,
+}
+
+impl PathSearch {
+    pub fn new(start: [usize; 3], goal: [usize; 3]) -> Self {
+        PathSearch {
+            goal,
+            open: BinaryHeap::from([(Reverse(estimated_cost(start, goal)), 0, start)]),
+            came_from: HashMap::from([(start, (0, None))]),
+            expanded_count: 0,
+        }
+    }
+
+    // Expands up to `max_expansions` more nodes. Gives up once `MAX_SEARCH_NODES` have been
+    // expanded in total.
+    pub fn step(
+        &mut self,
+        block_at: impl Fn([usize; 3]) -> BlockType,
+        max_expansions: usize,
+    ) -> SearchStatus {
+        for _ in 0..max_expansions {
+            let Some((_, cost, node)) = self.open.pop() else {
+                return SearchStatus::NotFound;
+            };
+            if node == self.goal {
+                return SearchStatus::Found(self.path_to(node));
+            }
+            if cost > self.came_from[&node].0 {
+                // Reached more cheaply since it was queued, and expanded from there already
+                continue;
+            }
+            if self.expanded_count >= MAX_SEARCH_NODES {
+                return SearchStatus::NotFound;
+            }
+            self.expanded_count += 1;
+
+            for (neighbor, step_cost) in neighbors(&block_at, node) {
+                let neighbor_cost = cost + step_cost;
+                if self
+                    .came_from
+                    .get(&neighbor)
+                    .is_some_and(|&(known_cost, _)| known_cost <= neighbor_cost)
+                {
+                    continue;
+                }
+                self.came_from.insert(neighbor, (neighbor_cost, Some(node)));
+                self.open.push((
+                    Reverse(neighbor_cost + estimated_cost(neighbor, self.goal)),
+                    neighbor_cost,
+                    neighbor,
+                ));
+            }
+        }
+        SearchStatus::InProgress
+    }
+
+    fn path_to(&self, mut node: [usize; 3]) -> Vec<[usize; 3]> {
+        let mut path = vec![];
+        while let Some(&(_, Some(previous))) = self.came_from.get(&node) {
+            path.push(node);
+            node = previous;
+        }
+        path.reverse();
+        path
+    }
+}
+
+// Runs a whole search at once. `None` if there's no path within `MAX_SEARCH_NODES`.
+pub fn find_path(
+    block_at: impl Fn([usize; 3]) -> BlockType,
+    start: [usize; 3],
+    goal: [usize; 3],
+) -> Option<Vec<[usize; 3]>> {
+    let mut search = PathSearch::new(start, goal);
+    loop {
+        match search.step(&block_at, MAX_SEARCH_NODES) {
+            SearchStatus::InProgress => continue,
+            SearchStatus::Found(path) => return Some(path),
+            SearchStatus::NotFound => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 16x16 patch of stone ground at y = 0, so mobs stand at y = 1, with `blocks` added on top.
+    // There's no ground outside of the patch.
+    fn terrain(
+        blocks: impl IntoIterator<Item = ([usize; 3], BlockType)>,
+    ) -> impl Fn([usize; 3]) -> BlockType {
+        let blocks = blocks.into_iter().collect::<HashMap<_, _>>();
+        move |[x, y, z]| match blocks.get(&[x, y, z]) {
+            Some(&block_type) => block_type,
+            None if y == 0 && x < 16 && z < 16 => BlockType::Stone,
+            None => BlockType::Empty,
+        }
+    }
+
+    // Blocks filling `ys` on every column from `min_x` to the edge of the patch
+    fn raised(min_x: usize, ys: &[usize], block_type: BlockType) -> Vec<([usize; 3], BlockType)> {
+        iproduct!(min_x..16, ys.iter().copied(), 0..16)
+            .map(|(x, y, z)| ([x, y, z], block_type))
+            .collect()
+    }
+
+    // Every step of the path moves one block along x or z, and at most one block up or down
+    fn assert_walkable(start: [usize; 3], path: &[[usize; 3]]) {
+        let mut previous = start;
+        for &node in path {
+            assert_eq!(
+                previous[0].abs_diff(node[0]) + previous[2].abs_diff(node[2]),
+                1,
+                "{:?} to {:?}",
+                previous,
+                node
+            );
+            assert!(
+                previous[1].abs_diff(node[1]) <= 1,
+                "{:?} to {:?}",
+                previous,
+                node
+            );
+            previous = node;
+        }
+    }
+
+    #[test]
+    fn steps_up_one_block() {
+        let block_at = terrain(raised(3, &[1], BlockType::Stone));
+        let path = find_path(&block_at, [0, 1, 5], [6, 2, 5]).unwrap();
+
+        assert_walkable([0, 1, 5], &path);
+        assert_eq!(
+            path,
+            [
+                [1, 1, 5],
+                [2, 1, 5],
+                [3, 2, 5],
+                [4, 2, 5],
+                [5, 2, 5],
+                [6, 2, 5]
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_down_one_block() {
+        let block_at = terrain(raised(3, &[1], BlockType::Stone));
+        let path = find_path(&block_at, [6, 2, 5], [0, 1, 5]).unwrap();
+
+        assert_walkable([6, 2, 5], &path);
+        assert_eq!(path.last(), Some(&[0, 1, 5]));
+    }
+
+    #[test]
+    fn doesnt_climb_or_drop_two_blocks() {
+        let block_at = terrain(raised(3, &[1, 2], BlockType::Stone));
+
+        assert_eq!(find_path(&block_at, [0, 1, 5], [6, 3, 5]), None);
+        assert_eq!(find_path(&block_at, [6, 3, 5], [0, 1, 5]), None);
+    }
+
+    #[test]
+    fn stays_out_of_water() {
+        let mut blocks = raised(3, &[1], BlockType::Water);
+        blocks.retain(|&([x, _, _], _)| x == 3);
+        let block_at = terrain(blocks);
+
+        assert!(!is_standable(&block_at, [3, 1, 5]));
+        assert_eq!(find_path(&block_at, [0, 1, 5], [6, 1, 5]), None);
+    }
+
+    #[test]
+    fn needs_two_blocks_of_clearance() {
+        let ceiling_at = |y: usize| {
+            let mut blocks = raised(3, &[y], BlockType::Stone);
+            blocks.retain(|&([x, _, _], _)| x == 3);
+            terrain(blocks)
+        };
+
+        let low_ceiling = ceiling_at(2);
+        assert!(!is_standable(&low_ceiling, [3, 1, 5]));
+        assert_eq!(find_path(&low_ceiling, [0, 1, 5], [6, 1, 5]), None);
+
+        let high_ceiling = ceiling_at(3);
+        assert!(is_standable(&high_ceiling, [3, 1, 5]));
+        assert!(find_path(&high_ceiling, [0, 1, 5], [6, 1, 5]).is_some());
+    }
+
+    #[test]
+    fn gives_up_after_max_search_nodes() {
+        // Endless ground, with the goal floating in the air
+        let block_at = |[_, y, _]: [usize; 3]| {
+            if y == 0 {
+                BlockType::Stone
+            } else {
+                BlockType::Empty
+            }
+        };
+        let mut search = PathSearch::new([5000, 1, 5000], [5000, 10, 5000]);
+
+        assert_eq!(search.step(block_at, usize::MAX), SearchStatus::NotFound);
+        assert_eq!(search.expanded_count, MAX_SEARCH_NODES);
+    }
+
+    #[test]
+    fn stepping_a_few_nodes_at_a_time_finds_the_same_path() {
+        // A wall with a gap at the far end, and a step up behind it
+        let mut blocks = raised(4, &[1, 2], BlockType::Stone);
+        blocks.retain(|&([x, _, z], _)| x == 4 && z < 14);
+        blocks.extend(raised(8, &[1], BlockType::Stone));
+        let block_at = terrain(blocks);
+        let (start, goal) = ([1, 1, 2], [10, 2, 3]);
+
+        let mut search = PathSearch::new(start, goal);
+        let mut step_count = 1;
+        let path = loop {
+            match search.step(&block_at, 4) {
+                SearchStatus::InProgress => step_count += 1,
+                SearchStatus::Found(path) => break path,
+                SearchStatus::NotFound => panic!("no path found"),
+            }
+        };
+
+        assert!(step_count > 1);
+        assert_walkable(start, &path);
+        assert_eq!(Some(path), find_path(&block_at, start, goal));
+    }
+}
@@ -0,0 +1,206 @@
+use nalgebra as na;
+use parry3d::shape::{Cuboid, Cylinder, Shape};
+
+pub const FLOOR_CONTACT_TOLERANCE: f32 = 0.001;
+const WALL_CONTACT_TOLERANCE: f32 = 0.01;
+// Contacts are found up to this far apart
+const CONTACT_PREDICTION: f32 = 0.01;
+
+// Shape entities collide with blocks as, centered on their position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Collider {
+    // Upright, like the character
+    Cylinder { radius: f32, half_height: f32 },
+    Cuboid { half_extents: glam::Vec3 },
+}
+
+impl Collider {
+    // Half the size of the collider's bounding box along each axis
+    pub fn half_extents(&self) -> glam::Vec3 {
+        match *self {
+            Collider::Cylinder {
+                radius,
+                half_height,
+            } => glam::Vec3::new(radius, half_height, radius),
+            Collider::Cuboid { half_extents } => half_extents,
+        }
+    }
+
+    fn shape(&self) -> Box<dyn Shape> {
+        match *self {
+            Collider::Cylinder {
+                radius,
+                half_height,
+            } => Box::new(Cylinder::new(half_height, radius)),
+            Collider::Cuboid { half_extents } => Box::new(Cuboid::new(na::vector![
+                half_extents.x,
+                half_extents.y,
+                half_extents.z
+            ])),
+        }
+    }
+}
+
+pub struct MoveResult {
+    pub position: glam::Vec3,
+    // Axes the collider was stopped along, its velocity along them should be zeroed
+    pub blocked: [bool; 3],
+}
+
+fn isometry(position: glam::Vec3) -> na::Isometry3<f32> {
+    na::Isometry3::new(na::vector![position.x, position.y, position.z], na::zero())
+}
+
+// The first contact with one of `blocks` that pushes back against moving in `direction`
+fn check_collision_in_direction(
+    position: &na::Isometry3<f32>,
+    shape: &dyn Shape,
+    direction: glam::Vec3,
+    blocks: &[[usize; 3]],
+    contact_tolerance: f32,
+) -> Option<parry3d::query::Contact> {
+    for block_pos in blocks {
+        let block_collider = Cuboid::new(na::vector![0.5, 0.5, 0.5]);
+        let block_pos = na::Isometry3::new(
+            na::vector![
+                block_pos[0] as f32 + 0.5,
+                block_pos[1] as f32 + 0.5,
+                block_pos[2] as f32 + 0.5
+            ],
+            na::zero(),
+        );
+
+        if let Some(contact) = parry3d::query::contact(
+            position,
+            shape,
+            &block_pos,
+            &block_collider,
+            CONTACT_PREDICTION,
+        )
+        .unwrap()
+        {
+            let contact_normal =
+                glam::Vec3::new(contact.normal1.x, contact.normal1.y, contact.normal1.z);
+
+            // Project the normal onto the plane perpendicular to the direction
+            let normal_on_plane = contact_normal - direction * contact_normal.dot(direction);
+
+            // If true, the normal does not have significant components in directions other than `direction`
+            let is_normal_mostly_parallel_to_direction = normal_on_plane.length() < 0.5;
+
+            if is_normal_mostly_parallel_to_direction && contact.dist.abs() > contact_tolerance {
+                return Some(contact);
+            }
+        }
+    }
+    None
+}
+
+// Whether the bottom of the collider at `position` touches a block. `is_collidable` tells
+// whether the block at a position can be collided with.
+pub fn is_on_floor(
+    is_collidable: impl Fn([usize; 3]) -> bool,
+    collider: &Collider,
+    position: glam::Vec3,
+) -> bool {
+    let feet_y = position.y - collider.half_extents().y;
+    let floor_blocks: Vec<[usize; 3]> = iproduct!(-1..=1, -1..=1)
+        .map(|(dx, dz)| {
+            [
+                (position.x + dx as f32).floor() as usize,
+                feet_y.floor() as usize,
+                (position.z + dz as f32).floor() as usize,
+            ]
+        })
+        .filter(|&block_pos| is_collidable(block_pos))
+        .collect();
+
+    check_collision_in_direction(
+        &isometry(position),
+        collider.shape().as_ref(),
+        -glam::Vec3::Y,
+        &floor_blocks,
+        FLOOR_CONTACT_TOLERANCE / 4.0, // lower tolerance
+    )
+    .is_some()
+}
+
+// Moves the collider by `displacement`, stopping it along each axis where it runs into a block.
+// Long moves are split into steps no longer than the collider is thick, so it can't pass
+// through blocks.
+pub fn move_and_collide(
+    is_collidable: impl Fn([usize; 3]) -> bool,
+    collider: &Collider,
+    mut position: glam::Vec3,
+    mut displacement: glam::Vec3,
+) -> MoveResult {
+    let shape = collider.shape();
+    let half_extents = collider.half_extents();
+    let max_step_length = half_extents.min_element();
+    let step_count = (displacement.abs().max_element() / max_step_length)
+        .ceil()
+        .max(1.0) as usize;
+    displacement /= step_count as f32;
+
+    let mut blocked = [false; 3];
+    for _ in 0..step_count {
+        let mut potential_new_pos = position + displacement;
+        let next_pos = isometry(potential_new_pos);
+
+        // Collect the collidable blocks around the collider's next position
+        let min = (potential_new_pos - half_extents).floor();
+        let max = (potential_new_pos + half_extents).ceil();
+        let mut blocks_to_check_collision: Vec<[usize; 3]> = vec![];
+        for (x, y, z) in iproduct!(
+            min.x as isize..=max.x as isize,
+            min.y as isize..=max.y as isize,
+            min.z as isize..=max.z as isize
+        ) {
+            if x < 0 || y < 0 || z < 0 {
+                // There are no blocks at negative coordinates
+                continue;
+            }
+            let block_pos = [x as usize, y as usize, z as usize];
+            if is_collidable(block_pos) {
+                blocks_to_check_collision.push(block_pos);
+            }
+        }
+
+        for axis in 0..3 {
+            let mut direction = glam::Vec3::ZERO;
+            direction[axis] = if displacement[axis] > 0.0 { 1.0 } else { -1.0 };
+            let contact_tolerance = if axis == 1 {
+                FLOOR_CONTACT_TOLERANCE
+            } else {
+                WALL_CONTACT_TOLERANCE
+            };
+            let contact = match check_collision_in_direction(
+                &next_pos,
+                shape.as_ref(),
+                direction,
+                &blocks_to_check_collision,
+                contact_tolerance,
+            ) {
+                Some(contact) => contact,
+                None => continue,
+            };
+
+            blocked[axis] = true;
+            let adjust_vec =
+                glam::Vec3::new(contact.normal1.x, contact.normal1.y, contact.normal1.z)
+                    * contact.dist;
+            if axis == 1 {
+                // HACK: Keep the collider slightly colliding with the floor so it's still found
+                // to be on the floor next tick
+                potential_new_pos.y +=
+                    adjust_vec.y + (FLOOR_CONTACT_TOLERANCE / 2.0 * -adjust_vec.y.signum());
+            } else {
+                potential_new_pos[axis] += adjust_vec[axis];
+            }
+            displacement[axis] = 0.0;
+        }
+        position = potential_new_pos;
+    }
+
+    MoveResult { position, blocked }
+}
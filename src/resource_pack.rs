@@ -0,0 +1,191 @@
+use crate::atlas::{self, Tile};
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+// Searched for packs to cycle through, relative to the working directory
+#[cfg(not(target_arch = "wasm32"))]
+pub const RESOURCE_PACKS_DIR: &str = "resource_packs";
+// Path of the pack to start with, a directory or a zip archive
+#[cfg(not(target_arch = "wasm32"))]
+pub const RESOURCE_PACK_ENV_VAR: &str = "MINECRUST_RESOURCE_PACK";
+
+#[derive(Debug)]
+pub enum ResourcePackError {
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    Image(image::ImageError),
+    InvalidManifest(String),
+}
+
+impl std::fmt::Display for ResourcePackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourcePackError::Io(err) => write!(f, "{}", err),
+            ResourcePackError::Zip(err) => write!(f, "{}", err),
+            ResourcePackError::Image(err) => write!(f, "{}", err),
+            ResourcePackError::InvalidManifest(reason) => write!(f, "invalid manifest: {}", reason),
+        }
+    }
+}
+
+impl From<std::io::Error> for ResourcePackError {
+    fn from(err: std::io::Error) -> Self {
+        ResourcePackError::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for ResourcePackError {
+    fn from(err: zip::result::ZipError) -> Self {
+        ResourcePackError::Zip(err)
+    }
+}
+
+// Block textures that replace the built-in ones, from a directory or a zip archive with a
+// manifest.json:
+//
+//     { "name": "Faithful", "textures": { "stone": "blocks/stone.png", ... } }
+//
+// Texture names are the file names in assets/blocks, see `Tile::name`, and image paths are
+// relative to the manifest. Textures the pack leaves out or that fail to load stay built-in.
+// Animated textures stack their frames the same way as the built-in ones, see `TileAnimation`.
+pub struct ResourcePack {
+    pub name: String,
+    // Frames of each tile the pack replaces
+    tiles: HashMap<Tile, Vec<RgbaImage>>,
+}
+
+impl ResourcePack {
+    pub fn builtin() -> Self {
+        ResourcePack {
+            name: "Built-in".to_string(),
+            tiles: HashMap::new(),
+        }
+    }
+
+    // `read_file` reads a file of the pack by its path relative to the manifest
+    pub fn from_files(
+        mut read_file: impl FnMut(&str) -> Result<Vec<u8>, ResourcePackError>,
+    ) -> Result<Self, ResourcePackError> {
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&read_file(MANIFEST_FILE_NAME)?)
+                .map_err(|err| ResourcePackError::InvalidManifest(err.to_string()))?;
+        let name = manifest["name"].as_str().unwrap_or("Unnamed").to_string();
+        let textures = manifest["textures"].as_object().ok_or_else(|| {
+            ResourcePackError::InvalidManifest("expected a \"textures\" object".to_string())
+        })?;
+
+        let mut tiles = HashMap::new();
+        for (texture_name, path) in textures {
+            let (tile, path) = match (Tile::from_name(texture_name), path.as_str()) {
+                (Some(tile), Some(path)) => (tile, path),
+                (None, _) => {
+                    log::warn!("{}: unknown texture {}", name, texture_name);
+                    continue;
+                }
+                (_, None) => {
+                    log::warn!("{}: expected a path for texture {}", name, texture_name);
+                    continue;
+                }
+            };
+            let image = read_file(path).and_then(|png_bytes| {
+                atlas::decode_tile(&png_bytes, tile.frame_count()).map_err(ResourcePackError::Image)
+            });
+            match image {
+                Ok(frames) => {
+                    tiles.insert(tile, frames);
+                }
+                Err(err) => log::warn!(
+                    "{}: using the built-in {} texture, failed to load {}: {}",
+                    name,
+                    texture_name,
+                    path,
+                    err
+                ),
+            }
+        }
+
+        Ok(ResourcePack { name, tiles })
+    }
+
+    // The manifest may be at the root of the archive or inside a single top-level directory, as
+    // when a pack's directory is zipped as a whole
+    pub fn from_zip(reader: impl Read + Seek) -> Result<Self, ResourcePackError> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let manifest_path = archive
+            .file_names()
+            .filter(|path| {
+                path.rsplit('/').next() == Some(MANIFEST_FILE_NAME)
+                    && path.matches('/').count() <= 1
+            })
+            .min_by_key(|path| path.len())
+            .ok_or(zip::result::ZipError::FileNotFound)?
+            .to_string();
+        let root = &manifest_path[..manifest_path.len() - MANIFEST_FILE_NAME.len()];
+
+        Self::from_files(|path| {
+            let mut file = archive.by_name(&format!("{}{}", root, path))?;
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            Ok(bytes)
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_dir(dir: &Path) -> Result<Self, ResourcePackError> {
+        Self::from_files(|path| Ok(std::fs::read(dir.join(path))?))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &Path) -> Result<Self, ResourcePackError> {
+        if path.is_dir() {
+            Self::from_dir(path)
+        } else {
+            Self::from_zip(std::io::BufReader::new(std::fs::File::open(path)?))
+        }
+    }
+
+    // Falls back to the built-in textures altogether if the pack can't be read
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_or_builtin(path: &Path) -> Self {
+        Self::load(path).unwrap_or_else(|err| {
+            log::error!("Failed to load resource pack {}: {}", path.display(), err);
+            Self::builtin()
+        })
+    }
+
+    pub fn tile_frames(&self, tile: Tile) -> Vec<RgbaImage> {
+        match self.tiles.get(&tile) {
+            Some(frames) => frames.clone(),
+            None => atlas::decode_tile(tile.builtin_png(), tile.frame_count())
+                .expect("Failed to decode block texture"),
+        }
+    }
+
+    // Mip levels of the atlas, see `atlas::build_atlas`
+    pub fn build_atlas(&self) -> Vec<RgbaImage> {
+        atlas::build_atlas(|tile| self.tile_frames(tile))
+    }
+}
+
+// Pack directories and zip archives in `RESOURCE_PACKS_DIR`, by file name
+#[cfg(not(target_arch = "wasm32"))]
+pub fn available_pack_paths() -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(RESOURCE_PACKS_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.join(MANIFEST_FILE_NAME).is_file()
+                || path.extension().is_some_and(|extension| extension == "zip")
+        })
+        .collect();
+    paths.sort();
+    paths
+}
@@ -54,12 +54,18 @@ struct VertexOutput {
     @location(2) color_adjust: vec4<f32>,
     @location(3) world_position: vec4<f32>,
     @location(6) world_normal: vec3<f32>,
-    @location(7) light_space_position: vec4<f32>,
+    @location(8) @interpolate(flat) flags: u32,
+    @location(9) @interpolate(flat) ambient_occlusion: vec4<f32>,
 }
 
 struct CameraUniform {
     view_proj: mat4x4<f32>,
     eye_position: vec4<f32>,
+    // x: linear fog start, y: linear fog end, z: exponential fog density, 0 for linear fog
+    fog: vec4<f32>,
+    fog_color: vec4<f32>,
+    // x: seconds, loops every hour
+    time: vec4<f32>,
 }
 
 struct InstanceInput {
@@ -67,20 +73,70 @@ struct InstanceInput {
     @location(5) rotation_quaternion: vec4<f32>,
     @location(9) texture_atlas_offset: vec2<f32>,
     @location(10) color_adjust: vec4<f32>,
+    @location(11) flags: u32,
+    @location(12) ambient_occlusion: vec4<f32>,
+    @location(13) scale: vec2<f32>,
 }
 
+// Bits for InstanceInput.flags, must match instance.rs
+let INSTANCE_FLAG_EMISSIVE: u32 = 1u;
+let INSTANCE_FLAG_WATER_SURFACE: u32 = 2u;
+
 @group(1) @binding(0)
 var<uniform> camera_position: CameraUniform;
 
+struct PointLight {
+    position_radius: vec4<f32>,
+    color: vec4<f32>,
+}
+
 struct Light {
+    // Sun or moon, far away in the sky light's direction
     position: vec3<f32>,
+    // Drops at night, see world_clock.rs
+    ambient_strength: f32,
     color: vec3<f32>,
-    is_underwater: u32,
-    light_space_matrix: mat4x4<f32>,
+    _padding: u32,
+    light_space_matrices: array<mat4x4<f32>, 3>, // NUM_SHADOW_CASCADES in light.rs
+    shadow_depth_biases: vec4<f32>,
+    num_point_lights: u32,
+    point_lights: array<PointLight, 16>, // MAX_POINT_LIGHTS in light.rs
 }
 @group(2) @binding(0)
 var<uniform> light: Light;
 
+// Atlas layout in pixels, filled in from atlas.rs
+let ATLAS_TILES_PER_ROW: f32 = {{atlas.tiles_per_row}}.0;
+let ATLAS_TILE_SIZE: f32 = {{atlas.tile_size}}.0;
+let ATLAS_TILE_PADDING: f32 = {{atlas.tile_padding}}.0;
+
+// Where `tile_coord`, from 0 to 1 across a tile, lands in the atlas. `atlas_offset` is the tile's
+// cell, see `Tile::atlas_offset`.
+fn atlas_coords(tile_coord: vec2<f32>, atlas_offset: vec2<f32>) -> vec2<f32> {
+    let cell_size = ATLAS_TILE_SIZE + 2.0 * ATLAS_TILE_PADDING;
+    return (atlas_offset + (ATLAS_TILE_PADDING + tile_coord * ATLAS_TILE_SIZE) / cell_size) / ATLAS_TILES_PER_ROW;
+}
+
+// Seconds per frame and number of frames of the animated tile starting at `atlas_offset`, filled in
+// from atlas.rs. A single frame for static tiles.
+fn atlas_animation(atlas_offset: vec2<f32>) -> vec2<f32> {
+{{#each atlas.animations}}
+    if (all(atlas_offset == vec2<f32>({{this.offset}}))) {
+        return vec2<f32>({{this.frame_time_secs}}, {{this.frame_count}});
+    }
+{{/each}}
+    return vec2<f32>(1.0, 1.0);
+}
+
+// Cell of the frame to show at `time_secs`. The frames of an animated tile follow its first cell
+// along the rows of the atlas.
+fn animated_atlas_offset(atlas_offset: vec2<f32>, time_secs: f32) -> vec2<f32> {
+    let animation = atlas_animation(atlas_offset);
+    let frame = floor(time_secs / animation.x) % animation.y;
+    let cell = atlas_offset.y * ATLAS_TILES_PER_ROW + atlas_offset.x + frame;
+    return vec2<f32>(cell % ATLAS_TILES_PER_ROW, floor(cell / ATLAS_TILES_PER_ROW));
+}
+
 @vertex
 fn vs_main(
     vertex: VertexInput,
@@ -91,20 +147,22 @@ fn vs_main(
 
     var translate_matrix = mat4_from_position(translated_instance_pos) * mat4_from_quaternion(instance.rotation_quaternion);
 
+    // Merged faces are stretched over several blocks and tile their texture
+    let scaled_position = vec4<f32>(vertex.position.x * instance.scale.x, vertex.position.y, vertex.position.z * instance.scale.y, vertex.position.w);
+
     var out: VertexOutput;
-    out.tex_coord = vertex.tex_coord;
-    out.world_position = translate_matrix * vertex.position;
+    out.tex_coord = vertex.tex_coord * instance.scale;
+    out.world_position = translate_matrix * scaled_position;
     out.clip_position = camera_position.view_proj * out.world_position;
-    // out.clip_position = light.light_space_matrix * out.world_position;
-    out.texture_atlas_offset = instance.texture_atlas_offset;
+    // out.clip_position = light.light_space_matrices[0] * out.world_position;
+    out.texture_atlas_offset = animated_atlas_offset(instance.texture_atlas_offset, camera_position.time.x);
     out.color_adjust = instance.color_adjust;
+    out.flags = instance.flags;
+    out.ambient_occlusion = instance.ambient_occlusion;
 
     // All faces are rotated from bottom face, so we can hardcode the normal
     var bottom_face_normal = vec3<f32>(0.0, -1.0, 0.0);
     out.world_normal = mat3_from_quaternion(instance.rotation_quaternion) * bottom_face_normal;
-
-    out.light_space_position = light.light_space_matrix * out.world_position;
-
     return out;
 }
 
@@ -121,9 +179,6 @@ fn vs_camera_translate_no_instancing(
     out.tex_coord = vertex.tex_coord;
     out.world_position = translate_matrix * vertex.position;
     out.clip_position = camera_position.view_proj * out.world_position;
-
-    out.light_space_position = light.light_space_matrix * out.world_position;
-
     return out;
 }
 
@@ -135,9 +190,6 @@ fn vs_wire_no_instancing(
     out.tex_coord = vertex.tex_coord;
     out.world_position = vertex.position;
     out.clip_position = camera_position.view_proj * out.world_position;
-
-    out.light_space_position = light.light_space_matrix * out.world_position;
-
     return out;
 }
 
@@ -145,13 +197,29 @@ fn vs_wire_no_instancing(
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+
 @group(0) @binding(2)
-var t_shadow_map: texture_2d<f32>;
+var t_shadow_map: texture_2d_array<f32>;
 @group(0) @binding(3)
 var s_shadow_map: sampler;
 
-fn sample_shadow_map(coords: vec2<f32>) -> f32 {
-    let tex_sample = textureSample(t_shadow_map, s_shadow_map, coords).r;
+let NUM_SHADOW_CASCADES: i32 = 3;
+
+// The first (i.e. sharpest) shadow cascade that covers the position, -1 if none of them do
+fn shadow_cascade_for(world_position: vec4<f32>) -> i32 {
+    for (var i = 0; i < NUM_SHADOW_CASCADES; i++) {
+        let light_space_pos = light.light_space_matrices[i] * world_position;
+        let coords = light_space_pos.xyz / light_space_pos.w;
+        if (all(abs(coords) <= vec3<f32>(1.0, 1.0, 1.0))) {
+            return i;
+        }
+    }
+    return -1;
+}
+
+fn sample_shadow_map(coords: vec2<f32>, cascade: i32) -> f32 {
+    // No implicit derivatives here, the cascade can change between neighbouring fragments
+    let tex_sample = textureSampleLevel(t_shadow_map, s_shadow_map, coords, cascade, 0.0).r;
     // Simulates wgpu::AddressMode::ClampToBorder/GL_CLAMP_TO_BORDER + white border color.
     // This mode isn't supported in WebGL
     if (coords.x > 1.0 || coords.x < 0.0 || coords.y > 1.0 || coords.y < 0.0) {
@@ -162,42 +230,46 @@ fn sample_shadow_map(coords: vec2<f32>) -> f32 {
 }
 
 // https://learnopengl.com/Advanced-Lighting/Shadows/Shadow-Mapping
-fn shadow_calculation_naive(fragPosLightSpace: vec4<f32>) -> f32 {
+fn shadow_calculation_naive(world_position: vec4<f32>) -> f32 {
+    let cascade = shadow_cascade_for(world_position);
+    // Points outside of the sunlight volume should not be in shadow
+    if (cascade < 0) {
+        return 0.0;
+    }
+    let fragPosLightSpace = light.light_space_matrices[cascade] * world_position;
     // perform perspective divide ([-1, 1])
     var projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
     // transform to [0,1] range
     projCoords = projCoords * 0.5 + 0.5;
     projCoords.y = 1.0 - projCoords.y;
     // get closest depth value from light's perspective (using [0,1] range fragPosLight as coords)
-    let closestDepth = sample_shadow_map(projCoords.xy);
+    let closestDepth = sample_shadow_map(projCoords.xy, cascade);
     // get depth of current fragment from light's perspective
     let currentDepth = projCoords.z;
 
-    // Points outside of the sunlight volume should not be in shadow
-    if (currentDepth > 1.0) {
-        return 0.0;
-    }
-
-    // NOTE(aleks): smallest bias I can get before things get janky
-    let bias = 0.001;
+    let bias = light.shadow_depth_biases[cascade];
 
     // check whether current frag pos is in shadow
     return select(0.0, 1.0, currentDepth - bias > closestDepth);
 }
 
-fn shadow_calculation_pcf(fragPosLightSpace: vec4<f32>) -> f32 {
+fn shadow_calculation_pcf(world_position: vec4<f32>) -> f32 {
+    let cascade = shadow_cascade_for(world_position);
+    // Points outside of the sunlight volume should not be in shadow
+    if (cascade < 0) {
+        return 0.0;
+    }
+    let fragPosLightSpace = light.light_space_matrices[cascade] * world_position;
     // perform perspective divide ([-1, 1])
     var projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
     // transform to [0,1] range
     projCoords = projCoords * 0.5 + 0.5;
     projCoords.y = 1.0 - projCoords.y;
     // get closest depth value from light's perspective (using [0,1] range fragPosLight as coords)
-    let closestDepth = sample_shadow_map(projCoords.xy); 
     // get depth of current fragment from light's perspective
     let currentDepth = projCoords.z;
 
-    // NOTE(aleks): smallest bias I can get before things get janky
-    let bias = 0.0003;
+    let bias = light.shadow_depth_biases[cascade];
 
     var pcf_shadow: f32 = 0.0;
     let texture_dims = textureDimensions(t_shadow_map, 0);
@@ -206,14 +278,11 @@ fn shadow_calculation_pcf(fragPosLightSpace: vec4<f32>) -> f32 {
         for (var y = -1; y <= 1; y++) {
             let offset = vec2<f32>(f32(x), f32(y));
             let sample_loc: vec2<f32> = projCoords.xy + offset * texel_size;
-            let pcf_depth = sample_shadow_map(sample_loc); 
+            let pcf_depth = sample_shadow_map(sample_loc, cascade);
             pcf_shadow += select(0.0, 1.0, currentDepth - bias > pcf_depth);
         }    
     }
-    pcf_shadow /= 9.0;
-
-    // Points outside of the sunlight volume should not be in shadow
-    return select(pcf_shadow, 0.0, currentDepth > 1.0);
+    return pcf_shadow / 9.0;
 }
 
 let MAX_SHADOW_EDGE_DISTANCE = 8;
@@ -223,10 +292,9 @@ fn revectorize_shadow(relative_distances: vec2<f32>) -> f32 {
     return select(1.0, 0.0, (r.x * r.y > 0.0) && (1.0 - r.x > r.y));
 }
 
-fn shadow_test(shadowmap_depth: f32, real_depth: f32) -> f32 {
+fn shadow_test(shadowmap_depth: f32, real_depth: f32, cascade: i32) -> f32 {
     // real_depth <= shadowmap_depth ? 1.0 : 0.0;
-    // let bias = 0.0003;
-    let bias = 0.0005;
+    let bias = light.shadow_depth_biases[cascade];
     return select(0.0, 1.0, real_depth - bias <= shadowmap_depth);
 }
 
@@ -252,16 +320,16 @@ fn normalize_distance_to_shadow_edge(relative_distance: vec4<f32>) -> vec2<f32>
     );
 }
 
-fn compute_discontinuity(shadowmap_coords: vec3<f32>, texel_size: vec2<f32>) -> vec4<f32> {
-    let left = shadow_test(sample_shadow_map(shadowmap_coords.xy + vec2<f32>(-1.0, 0.0) * texel_size), shadowmap_coords.z); 
-    let right = shadow_test(sample_shadow_map(shadowmap_coords.xy + vec2<f32>(1.0, 0.0) * texel_size), shadowmap_coords.z); 
-    let bottom = shadow_test(sample_shadow_map(shadowmap_coords.xy + vec2<f32>(0.0, 1.0) * texel_size), shadowmap_coords.z);
-    let top = shadow_test(sample_shadow_map(shadowmap_coords.xy + vec2<f32>(0.0, -1.0) * texel_size), shadowmap_coords.z);
+fn compute_discontinuity(shadowmap_coords: vec3<f32>, texel_size: vec2<f32>, cascade: i32) -> vec4<f32> {
+    let left = shadow_test(sample_shadow_map(shadowmap_coords.xy + vec2<f32>(-1.0, 0.0) * texel_size, cascade), shadowmap_coords.z, cascade); 
+    let right = shadow_test(sample_shadow_map(shadowmap_coords.xy + vec2<f32>(1.0, 0.0) * texel_size, cascade), shadowmap_coords.z, cascade); 
+    let bottom = shadow_test(sample_shadow_map(shadowmap_coords.xy + vec2<f32>(0.0, 1.0) * texel_size, cascade), shadowmap_coords.z, cascade);
+    let top = shadow_test(sample_shadow_map(shadowmap_coords.xy + vec2<f32>(0.0, -1.0) * texel_size, cascade), shadowmap_coords.z, cascade);
 
     return abs(vec4<f32>(left, right, bottom, top) - 1.0);
 }
 
-fn traverse_shadow_silhouette(initial_shadowmap_coords: vec3<f32>, texel_size: vec2<f32>, direction: vec2<f32>, sub_coord: f32) -> f32 {
+fn traverse_shadow_silhouette(initial_shadowmap_coords: vec3<f32>, texel_size: vec2<f32>, direction: vec2<f32>, sub_coord: f32, cascade: i32) -> f32 {
     var found_shadow_edge = 0.0;
     var distance = 0.0;
 
@@ -272,14 +340,14 @@ fn traverse_shadow_silhouette(initial_shadowmap_coords: vec3<f32>, texel_size: v
 
     for (var i = 0; i < MAX_SHADOW_EDGE_DISTANCE; i++) {
         let real_depth = current_coords.z;
-        let shadowmap_depth = sample_shadow_map(current_coords.xy);
-        let s = shadow_test(shadowmap_depth, real_depth);
+        let shadowmap_depth = sample_shadow_map(current_coords.xy, cascade);
+        let s = shadow_test(shadowmap_depth, real_depth, cascade);
 
         if (s == 0.0) {
             found_shadow_edge = 1.0;
             break;
         } else {
-            let d = compute_discontinuity(current_coords, texel_size);
+            let d = compute_discontinuity(current_coords, texel_size, cascade);
             if (d.x + d.y + d.z + d.w == 0.0) {
                 break;
             }
@@ -294,30 +362,32 @@ fn traverse_shadow_silhouette(initial_shadowmap_coords: vec3<f32>, texel_size: v
     return mix(-distance, distance, found_shadow_edge);
 }
 
-fn compute_distance_to_shadow_edge(shadowmap_coords: vec3<f32>, texel_size: vec2<f32>, sub_coord: vec2<f32>) -> vec4<f32> {
-    let left = traverse_shadow_silhouette(shadowmap_coords, texel_size, vec2<f32>(-1.0, 0.0), 1.0 - sub_coord.x);
-    let right = traverse_shadow_silhouette(shadowmap_coords, texel_size, vec2<f32>(1.0, 0.0), sub_coord.x);
-    let down = traverse_shadow_silhouette(shadowmap_coords, texel_size, vec2<f32>(0.0, -1.0), 1.0 - sub_coord.y);
-    let up = traverse_shadow_silhouette(shadowmap_coords, texel_size, vec2<f32>(0.0, 1.0), sub_coord.y);
+fn compute_distance_to_shadow_edge(shadowmap_coords: vec3<f32>, texel_size: vec2<f32>, sub_coord: vec2<f32>, cascade: i32) -> vec4<f32> {
+    let left = traverse_shadow_silhouette(shadowmap_coords, texel_size, vec2<f32>(-1.0, 0.0), 1.0 - sub_coord.x, cascade);
+    let right = traverse_shadow_silhouette(shadowmap_coords, texel_size, vec2<f32>(1.0, 0.0), sub_coord.x, cascade);
+    let down = traverse_shadow_silhouette(shadowmap_coords, texel_size, vec2<f32>(0.0, -1.0), 1.0 - sub_coord.y, cascade);
+    let up = traverse_shadow_silhouette(shadowmap_coords, texel_size, vec2<f32>(0.0, 1.0), sub_coord.y, cascade);
 
     return vec4<f32>(left, right, down, up);
 }
 
 // https://arxiv.org/pdf/1711.07793.pdf
 // https://github.com/MarcioCerqueira/GlobalIllumination/blob/master/ShadowMapping/Shaders/RBSM/ConservativeSMSR.frag
-fn shadow_calculation_rbsm(light_space_pos: vec4<f32>) -> vec4<f32> {
+fn shadow_calculation_rbsm(world_position: vec4<f32>) -> vec4<f32> {
+    let cascade = shadow_cascade_for(world_position);
+    if (cascade < 0) {
+        // Beyond the last shadow cascade
+        return vec4<f32>(0.0, 0.0, 1.0, -1.0);
+    }
+    let light_space_pos = light.light_space_matrices[cascade] * world_position;
     var shadowmap_coords = light_space_pos.xyz / light_space_pos.w;
     shadowmap_coords = shadowmap_coords * 0.5 + 0.5;
     shadowmap_coords.y = 1.0 - shadowmap_coords.y;
 
-    let shadowmap_depth = textureSample(t_shadow_map, s_shadow_map, shadowmap_coords.xy).r; 
+    let shadowmap_depth = sample_shadow_map(shadowmap_coords.xy, cascade);
     let real_depth = shadowmap_coords.z;
-    if (real_depth > 1.0) {
-        // Beyond zfar for sunlight volume
-        return vec4<f32>(0.0, 0.0, 1.0, -1.0);
-    }
 
-    let shadow_val = shadow_test(shadowmap_depth, real_depth);
+    let shadow_val = shadow_test(shadowmap_depth, real_depth, cascade);
     if (shadow_val == 0.0) {
         // Discard shadowed fragments from computation
         return vec4<f32>(0.0, 0.0, 0.0, -1.0);
@@ -328,7 +398,7 @@ fn shadow_calculation_rbsm(light_space_pos: vec4<f32>) -> vec4<f32> {
 
     let sub_coord = fract(vec2<f32>(shadowmap_coords.x * f32(texture_dims.x), shadowmap_coords.y * f32(texture_dims.y)));
 
-    let discontinuity = compute_discontinuity(shadowmap_coords, texel_size);
+    let discontinuity = compute_discontinuity(shadowmap_coords, texel_size, cascade);
     if (discontinuity.x + discontinuity.y + discontinuity.z + discontinuity.w == 0.0) {
         return vec4<f32>(0.0, 0.0, 1.0, -1.0);
     }
@@ -336,78 +406,142 @@ fn shadow_calculation_rbsm(light_space_pos: vec4<f32>) -> vec4<f32> {
         return vec4<f32>(0.0, 0.0, 0.0, -1.0);
     }
 
-    let relative_distance = compute_distance_to_shadow_edge(shadowmap_coords, texel_size, sub_coord);
+    let relative_distance = compute_distance_to_shadow_edge(shadowmap_coords, texel_size, sub_coord, cascade);
     let normalized_relative_distance = normalize_distance_to_shadow_edge(relative_distance);
     let s = revectorize_shadow(normalized_relative_distance);
     return vec4<f32>(normalized_relative_distance, s, -1.0);
 }
 
+// 1.0 where the sun is blocked, 0.0 where it isn't. The filter is picked with
+// `light::ShadowSettings`.
+fn shadow_calculation(world_position: vec4<f32>) -> f32 {
+{{#if (eq shadow_filter "rbsm")}}
+    return 1.0 - shadow_calculation_rbsm(world_position).z;
+{{else}}{{#if (eq shadow_filter "pcf")}}
+    return shadow_calculation_pcf(world_position);
+{{else}}
+    return shadow_calculation_naive(world_position);
+{{/if}}{{/if}}
+}
 
-let SHADOW_INTENSITY = 0.66;
+// Light from nearby emissive blocks (torches, glowstone, ...). Falls off to zero at the light's radius.
+fn point_light_calculation(world_position: vec3<f32>, world_normal: vec3<f32>) -> vec3<f32> {
+    var point_light_color = vec3<f32>(0.0, 0.0, 0.0);
+    for (var i = 0u; i < light.num_point_lights; i++) {
+        let point_light = light.point_lights[i];
+        let to_light = point_light.position_radius.xyz - world_position;
+        let falloff = clamp(1.0 - length(to_light) / point_light.position_radius.w, 0.0, 1.0);
+        let diffuse_strength = max(dot(world_normal, normalize(to_light)), 0.0);
+        // Faces turned away from the light still get some of it, otherwise caves look too harsh
+        point_light_color += point_light.color.rgb * falloff * falloff * (0.35 + 0.65 * diffuse_strength);
+    }
+    return point_light_color;
+}
 
 struct FragmentOutput {
   @builtin(frag_depth) depth: f32,
   @location(0) color: vec4<f32>,
 }
 
+// How much of a fragment `distance` blocks from the camera the fog hides, from 0 to 1
+fn fog_amount(distance: f32) -> f32 {
+    let fog = camera_position.fog;
+    if (fog.z > 0.0) {
+        return 1.0 - exp(-fog.z * distance);
+    }
+    return clamp((distance - fog.x) / (fog.y - fog.x), 0.0, 1.0);
+}
+
+// Periods divide an hour, so the water doesn't jump when `camera_position.time` loops
+let WATER_SCROLL_PERIOD: f32 = 12.0;
+let WATER_WAVE_PERIODS: vec2<f32> = vec2<f32>(3.0, 5.0);
+let WATER_WAVE_STEEPNESS: f32 = 0.08;
+
+// Ripples on the water surface, from two waves crossing each other. `world_xz` isn't camera
+// relative, so the ripples stay put when the camera moves.
+fn water_surface_normal(world_xz: vec2<f32>) -> vec3<f32> {
+    let phases = camera_position.time.x * 6.2831853 / WATER_WAVE_PERIODS;
+    let slope_a = cos(dot(world_xz, vec2<f32>(0.8, 0.6)) * 1.7 + phases.x);
+    let slope_b = cos(dot(world_xz, vec2<f32>(-0.5, 0.9)) * 2.3 + phases.y);
+    let slope = (vec2<f32>(0.8, 0.6) * slope_a + vec2<f32>(-0.5, 0.9) * slope_b) * WATER_WAVE_STEEPNESS;
+    return normalize(vec3<f32>(slope.x, 1.0, slope.y));
+}
+
 @fragment
 fn fs_main(vertex: VertexOutput) -> FragmentOutput {
-    var unit_offset: f32 = 1.0 / 32.0;
-    var atlas_scaled_coords = vertex.tex_coord / 32.0;
-    var offset_coords = atlas_scaled_coords + (unit_offset * vertex.texture_atlas_offset);
-    var base_color = textureSample(t_diffuse, s_diffuse, offset_coords);
-
-    var distance_from_camera = length(vertex.world_position);
-    var zfar: f32 = {{z_far}}; // Handlerbars template
-    var z_fade_start: f32 = {{z_fade_start}}; // Handlerbars template
-    var distance_alpha_adjust: f32 = max(0.0, distance_from_camera - z_fade_start) / (zfar - z_fade_start);
+    let is_water_surface = (vertex.flags & INSTANCE_FLAG_WATER_SURFACE) != 0u;
+    // Water surfaces drift along diagonally, a full block every `WATER_SCROLL_PERIOD` seconds
+    let water_scroll = fract(camera_position.time.x / WATER_SCROLL_PERIOD);
+    let scroll = select(vec2<f32>(0.0, 0.0), vec2<f32>(water_scroll, water_scroll), is_water_surface);
+    // Coordinates within a single block of a (possibly merged) face
+    let tile_coord = fract(vertex.tex_coord + scroll);
+    // The mip level comes from the unwrapped coordinates, `fract` would jump to the smallest level
+    // at every block edge of a merged face
+    let atlas_scale = ATLAS_TILE_SIZE / (ATLAS_TILE_SIZE + 2.0 * ATLAS_TILE_PADDING) / ATLAS_TILES_PER_ROW;
+    let base_color = textureSampleGrad(
+        t_diffuse,
+        s_diffuse,
+        atlas_coords(tile_coord, vertex.texture_atlas_offset),
+        dpdx(vertex.tex_coord) * atlas_scale,
+        dpdy(vertex.tex_coord) * atlas_scale
+    );
 
     var color = base_color * vertex.color_adjust;
     // var color = base_color * vec4(1.0, 1.0, 1.0, vertex.color_adjust.a);
-    color.a -= distance_alpha_adjust; // fog effect: fade distant vertices
     color.a = max(0.01, color.a); // prevent alpha from going to 0.0 which breaks in the browser
 
-    // We don't need (or want) much ambient light, so 0.1 is fine
-    let ambient_strength = 0.3;
-    let ambient_color = light.color * ambient_strength;
+    // Not tinted by the light color, which fades out when the sun or moon is low
+    let ambient_color = vec3<f32>(1.0, 1.0, 1.0) * light.ambient_strength;
+
+    var normal = vertex.world_normal;
+    if (is_water_surface) {
+        normal = water_surface_normal(vertex.world_position.xz + camera_position.eye_position.xz);
+    }
 
     let light_dir = normalize(light.position - vertex.world_position.xyz);
-    let diffuse_strength = max(dot(vertex.world_normal, light_dir), 0.0);
+    let diffuse_strength = max(dot(normal, light_dir), 0.0);
     let diffuse_color = light.color * diffuse_strength;
 
     let view_dir = normalize(vec3<f32>(0.0, 0.0, 0.0) - vertex.world_position.xyz);
     let half_dir = normalize(view_dir + light_dir);
-    let specular_strength = pow(max(dot(vertex.world_normal, half_dir), 0.0), 32.0);
+    let specular_strength = pow(max(dot(normal, half_dir), 0.0), 32.0);
     let specular_color = light.color * specular_strength;
 
     var frag_out: FragmentOutput;
 
-    // Basic shadow
-    let shadow = shadow_calculation_naive(vertex.light_space_position);
-    let lighted_color = (ambient_color + (1.0 - shadow) * (diffuse_color + specular_color)) * color.xyz; 
-    frag_out.depth = select(vertex.clip_position.z, 1.1, base_color.a == 0.0);
-    frag_out.color = vec4<f32>(lighted_color, color.a);
+    let point_light_color = point_light_calculation(vertex.world_position.xyz, normal);
 
-    // Make things more dark blue underwater
-    if (light.is_underwater != 0u) {
-        frag_out.color = frag_out.color * vec4<f32>(0.3, 0.3, 0.8, 1.0);
-    }
+    let shadow = shadow_calculation(vertex.world_position);
+    var lighted_color = (ambient_color + (1.0 - shadow) * (diffuse_color + specular_color) + point_light_color) * color.xyz;
 
-    return frag_out;
+    // Ambient occlusion is given per face corner, interpolate it across each block of the face
+    let ao = vertex.ambient_occlusion;
+    lighted_color *= mix(mix(ao.x, ao.y, tile_coord.x), mix(ao.z, ao.w, tile_coord.x), tile_coord.y);
 
-    // var shadow_debug = shadow_calculation_rbsm(vertex.light_space_position);
-    // var shadow = 1.0 - shadow_debug.z;
-    // shadow = select(0.0, SHADOW_INTENSITY, shadow == 1.0);
+    // Emissive blocks light themselves
+    let is_emissive = (vertex.flags & INSTANCE_FLAG_EMISSIVE) != 0u;
+    lighted_color = select(lighted_color, color.xyz, is_emissive);
+    frag_out.depth = select(vertex.clip_position.z, 1.1, base_color.a == 0.0);
 
-    // let lighted_color = (ambient_color + (1.0 - shadow) * (diffuse_color + specular_color)) * color.xyz; 
+    // Water reflects the sky at grazing angles and is see-through when looked straight into
+    // (Schlick's approximation). The fog color stands in for the sky at the horizon.
+    var alpha = color.a;
+    if (is_water_surface) {
+        let facing = abs(dot(view_dir, normal));
+        let fresnel = 0.02 + 0.98 * pow(1.0 - facing, 5.0);
+        lighted_color = mix(lighted_color, camera_position.fog_color.rgb, fresnel);
+        alpha = mix(alpha, 1.0, fresnel);
+    }
 
-    // frag_out.depth = select(vertex.clip_position.z, 1.1, base_color.a == 0.0);
-    // frag_out.color = vec4<f32>(lighted_color, color.a);
+    // Distant things fade into the fog color, which matches the sky at the horizon
+    let fog = fog_amount(length(vertex.world_position.xyz));
+    lighted_color = mix(lighted_color, camera_position.fog_color.rgb, fog);
+    frag_out.color = vec4<f32>(lighted_color, alpha);
 
-    // return frag_out;
+    return frag_out;
 
     // DEBUG
-    // var shadow_debug = shadow_calculation_rbsm(vertex.light_space_position);
+    // var shadow_debug = shadow_calculation_rbsm(vertex.world_position);
     // if (shadow_debug.w == -1.0) {
     //     return vec4<f32>(shadow_debug.x, 0.0, shadow_debug.y, 1.0);
     //     // return vec4<f32>(0.0, shadow_debug.z, 0.0, 1.0);
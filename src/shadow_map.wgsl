@@ -41,29 +41,68 @@ struct VertexOutput {
     @location(0) tex_coord: vec2<f32>,
     @builtin(position) clip_position: vec4<f32>,
     @location(1) texture_atlas_offset: vec2<f32>,
+    @location(2) @interpolate(flat) flags: u32,
 }
 
 struct InstanceInput {
     @location(4) instance_position: vec4<f32>,
     @location(5) rotation_quaternion: vec4<f32>,
     @location(9) texture_atlas_offset: vec2<f32>,
+    @location(11) flags: u32,
+    @location(13) scale: vec2<f32>,
 }
 
+// Bits for InstanceInput.flags, must match instance.rs
+let INSTANCE_FLAG_EMISSIVE: u32 = 1u;
+
 struct CameraUniform {
     view_proj: mat4x4<f32>,
     eye_position: vec4<f32>,
+    fog: vec4<f32>,
+    fog_color: vec4<f32>,
+    time: vec4<f32>,
 }
 @group(0) @binding(0)
 var<uniform> camera_position: CameraUniform;
 
-struct Light {
-    position: vec3<f32>,
-    color: vec3<f32>,
-    is_underwater: u32,
+// One of the shadow cascades, see light.rs
+struct ShadowCascade {
     light_space_matrix: mat4x4<f32>,
 }
 @group(1) @binding(0)
-var<uniform> light: Light;
+var<uniform> cascade: ShadowCascade;
+
+// Atlas layout in pixels, filled in from atlas.rs
+let ATLAS_TILES_PER_ROW: f32 = {{atlas.tiles_per_row}}.0;
+let ATLAS_TILE_SIZE: f32 = {{atlas.tile_size}}.0;
+let ATLAS_TILE_PADDING: f32 = {{atlas.tile_padding}}.0;
+
+// Where `tile_coord`, from 0 to 1 across a tile, lands in the atlas. `atlas_offset` is the tile's
+// cell, see `Tile::atlas_offset`.
+fn atlas_coords(tile_coord: vec2<f32>, atlas_offset: vec2<f32>) -> vec2<f32> {
+    let cell_size = ATLAS_TILE_SIZE + 2.0 * ATLAS_TILE_PADDING;
+    return (atlas_offset + (ATLAS_TILE_PADDING + tile_coord * ATLAS_TILE_SIZE) / cell_size) / ATLAS_TILES_PER_ROW;
+}
+
+// Seconds per frame and number of frames of the animated tile starting at `atlas_offset`, filled in
+// from atlas.rs. A single frame for static tiles.
+fn atlas_animation(atlas_offset: vec2<f32>) -> vec2<f32> {
+{{#each atlas.animations}}
+    if (all(atlas_offset == vec2<f32>({{this.offset}}))) {
+        return vec2<f32>({{this.frame_time_secs}}, {{this.frame_count}});
+    }
+{{/each}}
+    return vec2<f32>(1.0, 1.0);
+}
+
+// Cell of the frame to show at `time_secs`. The frames of an animated tile follow its first cell
+// along the rows of the atlas.
+fn animated_atlas_offset(atlas_offset: vec2<f32>, time_secs: f32) -> vec2<f32> {
+    let animation = atlas_animation(atlas_offset);
+    let frame = floor(time_secs / animation.x) % animation.y;
+    let cell = atlas_offset.y * ATLAS_TILES_PER_ROW + atlas_offset.x + frame;
+    return vec2<f32>(cell % ATLAS_TILES_PER_ROW, floor(cell / ATLAS_TILES_PER_ROW));
+}
 
 @vertex
 fn vs_main(
@@ -75,11 +114,15 @@ fn vs_main(
 
     var translate_matrix = mat4_from_position(translated_instance_pos) * mat4_from_quaternion(instance.rotation_quaternion);
 
+    // Merged faces are stretched over several blocks and tile their texture
+    let scaled_position = vec4<f32>(vertex.position.x * instance.scale.x, vertex.position.y, vertex.position.z * instance.scale.y, vertex.position.w);
+
     var out: VertexOutput;
-    let world_position = translate_matrix * vertex.position;
-    out.tex_coord = vertex.tex_coord;
-    out.clip_position = light.light_space_matrix * world_position;
-    out.texture_atlas_offset = instance.texture_atlas_offset;
+    let world_position = translate_matrix * scaled_position;
+    out.tex_coord = vertex.tex_coord * instance.scale;
+    out.clip_position = cascade.light_space_matrix * world_position;
+    out.texture_atlas_offset = animated_atlas_offset(instance.texture_atlas_offset, camera_position.time.x);
+    out.flags = instance.flags;
 
     // From here:
     // https://github.com/gfx-rs/wgpu/pull/71/files#diff-f91eefe904403aab76f6354857e063ff33ad277b5f046091ae1a92d9e18f8276R16-R17
@@ -99,12 +142,14 @@ struct FragmentOutput {
 
 @fragment
 fn fs_main(vertex: VertexOutput) -> FragmentOutput {
-    var unit_offset: f32 = 1.0 / 32.0;
-    var atlas_scaled_coords = vertex.tex_coord / 32.0;
-    var offset_coords = atlas_scaled_coords + (unit_offset * vertex.texture_atlas_offset);
-    var base_color = textureSample(t_diffuse, s_diffuse, offset_coords);
+    // Full resolution, so see-through pixels cast no shadow at any distance
+    let coords = atlas_coords(fract(vertex.tex_coord), vertex.texture_atlas_offset);
+    let base_color = textureSampleLevel(t_diffuse, s_diffuse, coords, 0.0);
+
+    // Emissive blocks don't cast shadows, they're the light source
+    let is_emissive = (vertex.flags & INSTANCE_FLAG_EMISSIVE) != 0u;
 
     var frag_out: FragmentOutput;
-    frag_out.depth = select(vertex.clip_position.z, 1.1, base_color.a == 0.0);
+    frag_out.depth = select(vertex.clip_position.z, 1.1, base_color.a == 0.0 || is_emissive);
     return frag_out;
 }
\ No newline at end of file
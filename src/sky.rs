@@ -0,0 +1,147 @@
+use crate::camera::Camera;
+use crate::texture;
+use crate::world_clock::WorldClock;
+use bytemuck::Zeroable;
+use cgmath::SquareMatrix;
+use noise::{NoiseFn, Seedable};
+
+// Clouds are a flat layer of square cells at a fixed height, see sky.wgsl
+pub const CLOUD_ALTITUDE: f32 = 128.0;
+// Blocks along each side of a cloud cell
+pub const CLOUD_CELL_SIZE: f32 = 12.0;
+// Cells along each side of the cloud map. The map repeats past its edges.
+pub const CLOUD_MAP_SIZE: usize = 256;
+// Portion of the sky covered by clouds
+const CLOUD_COVERAGE: f64 = 0.35;
+// Noise features per cloud map side, so clouds are a few cells across
+const CLOUD_NOISE_FREQUENCY: f64 = 16.0;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    // Takes clip space positions back to camera relative ones, to find each pixel's view direction
+    inverse_view_proj: [[f32; 4]; 4],
+    // w is how visible the stars are
+    sun_direction: [f32; 4],
+    zenith_color: [f32; 4],
+    horizon_color: [f32; 4],
+    // x: how far the clouds have drifted along x in blocks, y: cloud altitude, z: cloud brightness
+    clouds: [f32; 4],
+}
+
+impl SkyUniform {
+    pub fn new(camera: &Camera, world_clock: &WorldClock) -> Self {
+        let mut sky_uniform = Self::zeroed();
+        sky_uniform.update(camera, world_clock);
+        sky_uniform
+    }
+
+    pub fn update(&mut self, camera: &Camera, world_clock: &WorldClock) {
+        self.inverse_view_proj = camera
+            .build_view_projection_matrix()
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity)
+            .into();
+        self.sun_direction = world_clock
+            .sun_direction()
+            .extend(world_clock.star_visibility())
+            .into();
+        let [r, g, b] = world_clock.zenith_color();
+        self.zenith_color = [r as f32, g as f32, b as f32, 1.0];
+        let [r, g, b] = world_clock.sky_color();
+        self.horizon_color = [r as f32, g as f32, b as f32, 1.0];
+
+        // The clouds drift across the whole map once a day, so they line up again at midnight
+        let cloud_map_width = CLOUD_MAP_SIZE as f32 * CLOUD_CELL_SIZE;
+        let cloud_drift = world_clock.time_of_day() * cloud_map_width;
+        // Clouds are lit by the sky, they'd be too dark with just the ambient light at night
+        let cloud_brightness = 0.15 + 0.85 * (1.0 - world_clock.star_visibility());
+        self.clouds = [cloud_drift, CLOUD_ALTITUDE, cloud_brightness, 0.0];
+    }
+}
+
+// Which cells of the cloud map have a cloud, one byte per cell with 255 for clouds. Made from
+// noise that tiles across the map's edges, so the same seed always gives the same clouds.
+pub fn generate_cloud_map(seed: u32) -> Vec<u8> {
+    let noise = noise::OpenSimplex::new().set_seed(seed);
+
+    // Walk a circle in two of the noise's dimensions for each map axis, so both axes wrap around
+    let radius = CLOUD_NOISE_FREQUENCY / std::f64::consts::TAU;
+    let values = iproduct!(0..CLOUD_MAP_SIZE, 0..CLOUD_MAP_SIZE)
+        .map(|(z, x)| {
+            let angle_x = x as f64 / CLOUD_MAP_SIZE as f64 * std::f64::consts::TAU;
+            let angle_z = z as f64 / CLOUD_MAP_SIZE as f64 * std::f64::consts::TAU;
+            noise.get([
+                radius * angle_x.cos(),
+                radius * angle_x.sin(),
+                radius * angle_z.cos(),
+                radius * angle_z.sin(),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    // Threshold at the value that gives the wanted coverage
+    let mut sorted_values = values.clone();
+    sorted_values.sort_by(|a, b| a.total_cmp(b));
+    let threshold =
+        sorted_values[((1.0 - CLOUD_COVERAGE) * (sorted_values.len() - 1) as f64) as usize];
+
+    values
+        .iter()
+        .map(|&value| if value > threshold { 255 } else { 0 })
+        .collect()
+}
+
+pub fn create_cloud_map_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    seed: u32,
+) -> texture::Texture {
+    let size = wgpu::Extent3d {
+        width: CLOUD_MAP_SIZE as u32,
+        height: CLOUD_MAP_SIZE as u32,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Cloud Map"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        &generate_cloud_map(seed),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(CLOUD_MAP_SIZE as u32),
+            rows_per_image: std::num::NonZeroU32::new(CLOUD_MAP_SIZE as u32),
+        },
+        size,
+    );
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    // Nearest filtering keeps the cells square, and the map repeats across the sky
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        address_mode_w: wgpu::AddressMode::Repeat,
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+
+    texture::Texture {
+        texture,
+        view,
+        sampler,
+    }
+}
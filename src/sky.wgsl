@@ -0,0 +1,142 @@
+// Drawn behind everything else, see sky.rs
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    eye_position: vec4<f32>,
+    // x: linear fog start, y: linear fog end, z: exponential fog density, 0 for linear fog
+    fog: vec4<f32>,
+    fog_color: vec4<f32>,
+    time: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SkyUniform {
+    inverse_view_proj: mat4x4<f32>,
+    // w: how visible the stars are
+    sun_direction: vec4<f32>,
+    zenith_color: vec4<f32>,
+    horizon_color: vec4<f32>,
+    // x: cloud drift along x, y: cloud altitude, z: cloud brightness
+    clouds: vec4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> sky: SkyUniform;
+@group(1) @binding(1)
+var t_cloud_map: texture_2d<f32>;
+@group(1) @binding(2)
+var s_cloud_map: sampler;
+
+// Must match sky.rs
+let CLOUD_CELL_SIZE: f32 = 12.0;
+let CLOUD_MAP_SIZE: f32 = 256.0;
+let CLOUD_OPACITY: f32 = 0.8;
+
+// Half the width of the sun and moon squares, in the plane facing them
+let SUN_SIZE: f32 = 0.06;
+let MOON_SIZE: f32 = 0.04;
+// Star cells per unit of view direction
+let STAR_DENSITY: f32 = 120.0;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// A single triangle that covers the screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    var out: VertexOutput;
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 1.0, 1.0);
+    return out;
+}
+
+fn hash(p: vec3<f32>) -> f32 {
+    return fract(sin(dot(p, vec3<f32>(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+// How much `view_dir` falls within a square of half width `size` facing `direction`
+fn celestial_square(view_dir: vec3<f32>, direction: vec3<f32>, size: f32) -> f32 {
+    let facing = dot(view_dir, direction);
+    if (facing <= 0.0) {
+        return 0.0;
+    }
+    // The sun and moon go around the z axis, keep the squares' edges along their path
+    let right = normalize(cross(vec3<f32>(0.0, 0.0, 1.0), direction));
+    let up = cross(direction, right);
+    let local = vec2<f32>(dot(view_dir, right), dot(view_dir, up)) / facing;
+    return select(0.0, 1.0, max(abs(local.x), abs(local.y)) < size);
+}
+
+// Same as in shader.wgsl
+fn fog_amount(distance: f32) -> f32 {
+    let fog = camera.fog;
+    if (fog.z > 0.0) {
+        return 1.0 - exp(-fog.z * distance);
+    }
+    return clamp((distance - fog.x) / (fog.y - fog.x), 0.0, 1.0);
+}
+
+struct FragmentOutput {
+    @builtin(frag_depth) depth: f32,
+    @location(0) color: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let far_point = sky.inverse_view_proj * vec4<f32>(in.ndc, 0.5, 1.0);
+    let view_dir = normalize(far_point.xyz / far_point.w);
+    let sun_direction = sky.sun_direction.xyz;
+    let star_visibility = sky.sun_direction.w;
+
+    // Gradient dome, darker below the horizon
+    let height = view_dir.y;
+    var color = mix(sky.horizon_color.rgb, sky.zenith_color.rgb, sqrt(clamp(height, 0.0, 1.0)));
+    color = mix(color, sky.horizon_color.rgb * 0.6, clamp(-height * 4.0, 0.0, 1.0));
+
+    // Stars, scattered on a grid of cells around the camera
+    let star_cell = floor(view_dir * STAR_DENSITY);
+    let star = smoothstep(0.997, 1.0, hash(star_cell)) * star_visibility * clamp(height * 8.0, 0.0, 1.0);
+    color += vec3<f32>(star, star, star);
+
+    // Sun with a glow around it, and the moon across from it
+    let sun_glow = pow(max(dot(view_dir, sun_direction), 0.0), 48.0) * 0.4 * (1.0 - star_visibility);
+    color += vec3<f32>(1.0, 0.8, 0.5) * sun_glow;
+    color = mix(color, vec3<f32>(1.0, 0.95, 0.8) * 4.0, celestial_square(view_dir, sun_direction, SUN_SIZE));
+    color = mix(color, vec3<f32>(0.85, 0.88, 0.95), celestial_square(view_dir, -sun_direction, MOON_SIZE));
+
+    // Exponential fog never clears, so it hides the sky completely, e.g. under water
+    if (camera.fog.z > 0.0) {
+        color = camera.fog_color.rgb;
+    }
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(color, 1.0);
+    out.depth = 1.0;
+
+    // Clouds, where the view ray crosses the cloud layer
+    let eye = camera.eye_position.xyz;
+    let cloud_distance = (sky.clouds.y - eye.y) / view_dir.y;
+    if (cloud_distance > 0.0) {
+        let cloud_position = view_dir * cloud_distance;
+        let map_position = (cloud_position.xz + eye.xz + vec2<f32>(sky.clouds.x, 0.0)) / (CLOUD_CELL_SIZE * CLOUD_MAP_SIZE);
+        // No implicit derivatives here, this branch differs between neighbouring pixels
+        let is_cloud = textureSampleLevel(t_cloud_map, s_cloud_map, map_position, 0.0).r > 0.5;
+
+        // Fade into the sky like terrain does
+        let fog = fog_amount(cloud_distance);
+        let opacity = select(0.0, CLOUD_OPACITY * (1.0 - fog), is_cloud);
+        let cloud_color = mix(vec3<f32>(1.0, 1.0, 1.0), sky.horizon_color.rgb, 0.25) * sky.clouds.z;
+        out.color = vec4<f32>(mix(color, cloud_color, opacity), 1.0);
+
+        // Mostly opaque clouds hide terrain behind them, e.g. when flying above them
+        if (opacity > 0.5) {
+            let clip_position = camera.view_proj * vec4<f32>(cloud_position, 1.0);
+            out.depth = clip_position.z / clip_position.w;
+        }
+    }
+
+    return out;
+}
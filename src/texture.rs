@@ -41,45 +41,98 @@ impl Texture {
         }
     }
 
-    pub fn create_pixel_art_image_texture(
-        image_file_raw_bytes: &[u8],
+    // Depth texture with several layers, sampled as a texture array. `layer_views` are for
+    // rendering into a single layer.
+    pub fn create_depth_texture_array(
+        label: &str,
+        device: &wgpu::Device,
+        [width, height]: [u32; 2],
+        num_layers: u32,
+        sampler_descriptor: &wgpu::SamplerDescriptor,
+    ) -> (Self, Vec<wgpu::TextureView>) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: num_layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..num_layers)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let sampler = device.create_sampler(sampler_descriptor);
+
+        (
+            Self {
+                texture,
+                view,
+                sampler,
+            },
+            layer_views,
+        )
+    }
+
+    // Pixel art with its mip levels, largest first. Each level is half the size of the previous one.
+    pub fn create_pixel_art_texture(
+        mip_levels: &[image::RgbaImage],
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         label: &str,
     ) -> Self {
-        let dyn_image = image::load_from_memory(image_file_raw_bytes).unwrap();
-        let image_buf_rgba = dyn_image.to_rgba8();
-        let dimensions = image_buf_rgba.dimensions();
-
-        let texture_extent = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
-        };
+        let (width, height) = mip_levels[0].dimensions();
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some(label),
-            size: texture_extent,
-            mip_level_count: 1,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mip_levels.len() as u32,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
         });
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &image_buf_rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * dimensions.0),
-                rows_per_image: std::num::NonZeroU32::new(dimensions.1),
-            },
-            texture_extent,
-        );
+        for (mip_level, image) in mip_levels.iter().enumerate() {
+            let (width, height) = image.dimensions();
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                image,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(4 * width),
+                    rows_per_image: std::num::NonZeroU32::new(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -88,7 +141,8 @@ impl Texture {
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Nearest,
             min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            // Blends between mip levels, so distant textures don't shimmer or pop
+            mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
 
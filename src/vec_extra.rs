@@ -64,6 +64,11 @@ impl<T, DO: DimOrder> Vec3d<T, DO> {
         self.vec.as_mut_ptr().add(self.dim_order.array_index(x, y, z))
     }
 
+    // Elements in the dimension order's layout
+    pub fn as_slice(&self) -> &[T] {
+        &self.vec
+    }
+
     pub fn dims(&self) -> &[usize; 3] {
         DO::dims()
     }
@@ -32,6 +32,26 @@ extern "C" {
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen(js_namespace = window)]
     fn handleGameReady(_: &JsValue);
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_namespace = window)]
+    fn handleTimeOfDayChanged(timeOfDay: &JsValue);
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_namespace = window)]
+    fn handleInventoryChanged(inventoryJson: &JsValue);
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_namespace = window)]
+    fn handleHealthChanged(points: &JsValue, maxPoints: &JsValue);
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_namespace = window)]
+    fn handleRenderDistanceChanged(visibleChunkWidth: &JsValue);
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_namespace = window, catch)]
+    fn createChunkWorker() -> Result<web_sys::Worker, JsValue>;
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -42,4 +62,33 @@ pub fn js_update_game_state_load_progress(load_progress: f64) {
 #[cfg(target_arch = "wasm32")]
 pub fn js_handle_game_ready() {
     handleGameReady(&JsValue::UNDEFINED);
-}
\ No newline at end of file
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn js_handle_time_of_day_changed(time_of_day: f32) {
+    handleTimeOfDayChanged(&JsValue::from_f64(time_of_day as f64));
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn js_handle_inventory_changed(inventory_json: &str) {
+    handleInventoryChanged(&JsValue::from_str(inventory_json));
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn js_handle_health_changed(points: f32, max_points: f32) {
+    handleHealthChanged(
+        &JsValue::from_f64(points as f64),
+        &JsValue::from_f64(max_points as f64),
+    );
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn js_handle_render_distance_changed(visible_chunk_width: usize) {
+    handleRenderDistanceChanged(&JsValue::from_f64(visible_chunk_width as f64));
+}
+
+// `None` if the page can't start Web Workers, see `jobs::JobPool`
+#[cfg(target_arch = "wasm32")]
+pub fn js_create_chunk_worker() -> Option<web_sys::Worker> {
+    createChunkWorker().ok()
+}
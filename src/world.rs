@@ -1,17 +1,29 @@
+use crate::atlas::Tile;
+use crate::byte_codec::{ByteReader, ByteWriter};
 use crate::camera::Camera;
+use crate::combat::{self, Health};
+use crate::crafting::RecipeBook;
+use crate::dropped_item;
+use crate::entity::{Entities, EntityId};
 use crate::game_loop::GameLoop;
+use crate::inventory::{Inventory, ItemStack};
+use crate::item::Item;
+use crate::light::{PointLight, MAX_POINT_LIGHTS};
 use crate::map_generation::{self};
-use crate::vec_extra::{self, Vec2d, Vec3d};
+use crate::mining::{Mining, MiningProgress, Tool};
+use crate::mob;
+use crate::occlusion::ChunkConnectivity;
+use crate::physics::{self, Collider};
+use crate::vec_extra::{self, DimOrder, Vec2d, Vec3d};
 use crate::vertex::{CuboidCoords, QuadListRenderData, Vertex};
+use crate::world_clock::{WorldClock, DEFAULT_TIME_OF_DAY};
 use crate::DomControlsUserEvent;
 use bitmaps::Bitmap;
 use rand::prelude::SliceRandom;
-use winit::event::{ElementState, VirtualKeyCode, WindowEvent};
+use winit::event::{ElementState, MouseScrollDelta, VirtualKeyCode, WindowEvent};
 
-use nalgebra as na;
-use parry3d::shape::{Cuboid, Cylinder};
-
-use super::instance::InstanceRaw;
+use super::greedy_mesh::GreedyMesher;
+use super::instance::{self, InstanceRaw};
 #[cfg(target_arch = "wasm32")]
 use crate::dom_controls;
 use cgmath::{prelude::*, MetricSpace, Point3, Vector3};
@@ -50,6 +62,9 @@ pub enum BlockType {
     TreeLeaves4,
     RedFlower,
     OakPlank,
+    Torch,
+    Glowstone,
+    Lantern,
 }
 
 impl fmt::Display for BlockType {
@@ -70,12 +85,50 @@ impl fmt::Display for BlockType {
             BlockType::TreeLeaves4 => write!(f, "TreeLeaves4"),
             BlockType::RedFlower => write!(f, "RedFlower"),
             BlockType::OakPlank => write!(f, "OakPlank"),
+            BlockType::Torch => write!(f, "Torch"),
+            BlockType::Glowstone => write!(f, "Glowstone"),
+            BlockType::Lantern => write!(f, "Lantern"),
         }
     }
 }
 
 impl BlockType {
-    pub const DEFAULT_PLACE_BLOCK_TYPE: BlockType = BlockType::Stone;
+    pub const ALL: [BlockType; 18] = [
+        BlockType::Empty,
+        BlockType::Debug,
+        BlockType::Dirt,
+        BlockType::Grass,
+        BlockType::Sand,
+        BlockType::Stone,
+        BlockType::Water,
+        BlockType::Glass,
+        BlockType::Tree,
+        BlockType::TreeLeaves1,
+        BlockType::TreeLeaves2,
+        BlockType::TreeLeaves3,
+        BlockType::TreeLeaves4,
+        BlockType::RedFlower,
+        BlockType::OakPlank,
+        BlockType::Torch,
+        BlockType::Glowstone,
+        BlockType::Lantern,
+    ];
+
+    // Inverse of `to_string`
+    pub fn from_name(name: &str) -> Option<BlockType> {
+        BlockType::ALL
+            .into_iter()
+            .find(|block_type| block_type.to_string() == name)
+    }
+
+    // What ends up in the inventory when the block is broken
+    pub fn dropped_item(&self) -> Option<BlockType> {
+        match *self {
+            BlockType::Empty | BlockType::Water | BlockType::Debug => None,
+            BlockType::Grass => Some(BlockType::Dirt),
+            _ => Some(*self),
+        }
+    }
 
     pub fn is_semi_translucent(&self) -> bool {
         match *self {
@@ -84,6 +137,8 @@ impl BlockType {
             BlockType::TreeLeaves3 => true,
             BlockType::TreeLeaves4 => true,
             BlockType::RedFlower => true,
+            BlockType::Torch => true,
+            BlockType::Lantern => true,
             _ => false,
         }
     }
@@ -103,11 +158,32 @@ impl BlockType {
         }
     }
 
+    // How long the block takes to break, see `Tool::break_time_secs`. Same scale as Minecraft,
+    // `None` for blocks that can't be broken.
+    pub fn hardness(&self) -> Option<f32> {
+        match *self {
+            BlockType::Empty | BlockType::Water => None,
+            BlockType::Grass => Some(0.6),
+            BlockType::Dirt | BlockType::Sand => Some(0.5),
+            BlockType::Stone => Some(1.5),
+            BlockType::OakPlank | BlockType::Tree => Some(2.0),
+            BlockType::Glass | BlockType::Glowstone => Some(0.3),
+            BlockType::TreeLeaves1
+            | BlockType::TreeLeaves2
+            | BlockType::TreeLeaves3
+            | BlockType::TreeLeaves4 => Some(0.2),
+            BlockType::Lantern => Some(3.5),
+            BlockType::RedFlower | BlockType::Torch | BlockType::Debug => Some(0.0),
+        }
+    }
+
     pub fn is_collidable(&self) -> bool {
         match *self {
             BlockType::Empty => false,
             BlockType::Water => false,
             BlockType::RedFlower => false,
+            BlockType::Torch => false,
+            BlockType::Lantern => false,
             _ => true,
         }
     }
@@ -115,10 +191,43 @@ impl BlockType {
     pub fn is_sprite(&self) -> bool {
         match *self {
             BlockType::RedFlower => true,
+            BlockType::Torch => true,
+            BlockType::Lantern => true,
             _ => false,
         }
     }
 
+    // Light emitted by this block, if any. Emissive blocks are drawn fully lit and don't cast
+    // shadows.
+    pub fn light_emission(&self) -> Option<LightEmission> {
+        match *self {
+            BlockType::Torch => Some(LightEmission {
+                level: 12,
+                color: [1.0, 0.75, 0.45],
+            }),
+            BlockType::Glowstone => Some(LightEmission {
+                level: 15,
+                color: [1.0, 0.9, 0.6],
+            }),
+            BlockType::Lantern => Some(LightEmission {
+                level: 14,
+                color: [1.0, 0.8, 0.5],
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.light_emission().is_some()
+    }
+
+    // Whether this block darkens the corners of faces next to it
+    pub fn is_ambient_occluder(&self) -> bool {
+        !self.is_sprite()
+            && !self.is_emissive()
+            && !matches!(self, BlockType::Empty | BlockType::Water | BlockType::Glass)
+    }
+
     pub fn random_tree_leaf() -> BlockType {
         *[
             Self::TreeLeaves1,
@@ -131,30 +240,129 @@ impl BlockType {
     }
 
     // top, bottom, sides
-    fn texture_atlas_offsets(&self) -> [[f32; 2]; 3] {
+    pub fn texture_tiles(&self) -> [Tile; 3] {
         match self {
-            BlockType::Grass => [[1.0, 0.0], [2.0, 0.0], [0.0, 0.0]],
-            BlockType::Dirt => [[2.0, 0.0], [2.0, 0.0], [2.0, 0.0]],
-            BlockType::Debug => [[3.0, 0.0], [3.0, 0.0], [3.0, 0.0]],
-            BlockType::Sand => [[0.0, 1.0], [0.0, 1.0], [0.0, 1.0]],
-            BlockType::Stone => [[2.0, 3.0], [2.0, 3.0], [2.0, 3.0]],
-            BlockType::OakPlank => [[2.0, 4.0], [2.0, 4.0], [2.0, 4.0]],
-            BlockType::Water => [[1.0, 1.0], [1.0, 1.0], [1.0, 1.0]],
-            BlockType::Glass => [[2.0, 1.0], [2.0, 1.0], [2.0, 1.0]],
-            BlockType::Tree => [[1.0, 2.0], [1.0, 2.0], [0.0, 2.0]],
-            BlockType::TreeLeaves1 => [[0.0, 3.0], [0.0, 3.0], [0.0, 3.0]],
-            BlockType::TreeLeaves2 => [[1.0, 3.0], [1.0, 3.0], [1.0, 3.0]],
-            BlockType::TreeLeaves3 => [[0.0, 4.0], [0.0, 4.0], [0.0, 4.0]],
-            BlockType::TreeLeaves4 => [[1.0, 3.0], [1.0, 3.0], [1.0, 3.0]],
-            BlockType::RedFlower => [[2.0, 2.0], [2.0, 2.0], [2.0, 2.0]],
-            _ => [[0.0, 0.0], [0.0, 0.0], [0.0, 0.0]],
+            BlockType::Grass => [Tile::GrassBlockTop, Tile::Dirt, Tile::GrassBlockSide],
+            BlockType::Dirt => [Tile::Dirt; 3],
+            BlockType::Debug => [Tile::Debug; 3],
+            BlockType::Sand => [Tile::Sand; 3],
+            BlockType::Stone => [Tile::Stone; 3],
+            BlockType::OakPlank => [Tile::OakPlanks; 3],
+            BlockType::Water => [Tile::Water; 3],
+            BlockType::Glass => [Tile::Glass; 3],
+            BlockType::Tree => [Tile::OakLogTop, Tile::OakLogTop, Tile::OakLog],
+            BlockType::TreeLeaves1 => [Tile::OakLeaves1; 3],
+            BlockType::TreeLeaves2 => [Tile::OakLeaves2; 3],
+            BlockType::TreeLeaves3 => [Tile::OakLeaves3; 3],
+            BlockType::TreeLeaves4 => [Tile::OakLeaves2; 3],
+            BlockType::RedFlower => [Tile::RedFlower; 3],
+            BlockType::Torch => [Tile::Torch; 3],
+            BlockType::Glowstone => [Tile::Glowstone; 3],
+            BlockType::Lantern => [Tile::Lantern; 3],
+            BlockType::Empty => [Tile::Debug; 3],
+        }
+    }
+
+    pub fn texture_atlas_offsets(&self) -> [[f32; 2]; 3] {
+        self.texture_tiles().map(Tile::atlas_offset)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LightEmission {
+    // Light level in blocks, same scale as Minecraft (0-15). Light reaches zero at this distance.
+    pub level: u8,
+    pub color: [f32; 3],
+}
+
+pub const NO_AMBIENT_OCCLUSION: [f32; 4] = [1.0; 4];
+
+// Classic voxel ambient occlusion for one face corner: 0 = darkest, 3 = unoccluded.
+// https://0fps.net/2013/07/03/ambient-occlusion-for-minecraft-like-worlds/
+pub fn vertex_ambient_occlusion_level(side1: bool, side2: bool, corner: bool) -> u8 {
+    if side1 && side2 {
+        0
+    } else {
+        3 - (side1 as u8 + side2 as u8 + corner as u8)
+    }
+}
+
+fn ambient_occlusion_brightness(level: u8) -> f32 {
+    [0.45, 0.65, 0.82, 1.0][level as usize]
+}
+
+// World-space corners of a cube face, relative to the block it belongs to. Corners are ordered by
+// the face's texture coordinates: (0, 0), (1, 0), (0, 1), (1, 1), which is what the shader expects.
+pub struct FaceCorners {
+    normal: [isize; 3],
+    // The two axes the face lies along
+    tangent_axes: [usize; 2],
+    corners: [[isize; 3]; 4],
+}
+
+impl FaceCorners {
+    // `offset` and `rotation` are the same ones used to place the face instance
+    pub fn new(offset: Vector3<f32>, rotation: cgmath::Quaternion<f32>) -> Self {
+        let round = |v: Vector3<f32>| {
+            [
+                v.x.round() as isize,
+                v.y.round() as isize,
+                v.z.round() as isize,
+            ]
+        };
+
+        // The face quad lies on the bottom of the unit cube, see face.rs
+        let normal = round(rotation.rotate_vector(Vector3::new(0.0, -1.0, 0.0)));
+        let tangent_axes = match normal.iter().position(|&n| n != 0) {
+            Some(0) => [1, 2],
+            Some(1) => [0, 2],
+            _ => [0, 1],
+        };
+        // Texture coordinates of the corners, in the order the shader expects
+        let tex_coords = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)];
+        let corners = tex_coords
+            .map(|(u, v)| round(offset + rotation.rotate_vector(Vector3::new(1.0 - u, 0.0, v))));
+
+        Self {
+            normal,
+            tangent_axes,
+            corners,
+        }
+    }
+
+    // Brightness of each corner of this face on the block at `block_pos`. `is_occluder` is queried
+    // for the blocks in the layer in front of the face.
+    pub fn ambient_occlusion(
+        &self,
+        block_pos: [isize; 3],
+        is_occluder: impl Fn([isize; 3]) -> bool,
+    ) -> [f32; 4] {
+        let front = [0, 1, 2].map(|i| block_pos[i] + self.normal[i]);
+        let [axis1, axis2] = self.tangent_axes;
+
+        let mut result = NO_AMBIENT_OCCLUSION;
+        for (brightness, corner) in result.iter_mut().zip(self.corners.iter()) {
+            // Step from the block in front of the face towards this corner along each face axis
+            let mut side1 = front;
+            side1[axis1] += corner[axis1] * 2 - 1;
+            let mut side2 = front;
+            side2[axis2] += corner[axis2] * 2 - 1;
+            let mut diagonal = side1;
+            diagonal[axis2] = side2[axis2];
+
+            *brightness = ambient_occlusion_brightness(vertex_ambient_occlusion_level(
+                is_occluder(side1),
+                is_occluder(side2),
+                is_occluder(diagonal),
+            ));
         }
+        result
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(usize)]
-enum Face {
+pub enum Face {
     Top = 0,
     Bottom = 1,
     Left = 2,
@@ -163,12 +371,96 @@ enum Face {
     Back = 5,
 }
 
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Top,
+        Face::Bottom,
+        Face::Left,
+        Face::Right,
+        Face::Front,
+        Face::Back,
+    ];
+
+    // Direction the face points in, see `Block::neighbors`
+    pub fn offset(self) -> [isize; 3] {
+        match self {
+            Face::Top => [0, 1, 0],
+            Face::Bottom => [0, -1, 0],
+            Face::Left => [1, 0, 0],
+            Face::Right => [-1, 0, 0],
+            Face::Front => [0, 0, 1],
+            Face::Back => [0, 0, -1],
+        }
+    }
+
+    // Rotation of the face's instance, the face quad itself lies on the bottom of the unit cube
+    pub fn rotation(self) -> cgmath::Quaternion<f32> {
+        use cgmath::{Deg, Quaternion};
+
+        match self {
+            Face::Top => Quaternion::from_axis_angle(Vector3::unit_x(), Deg(180.0)),
+            Face::Bottom => Quaternion::from_axis_angle(Vector3::unit_y(), Deg(0.0)),
+            Face::Left => {
+                Quaternion::from_axis_angle(Vector3::unit_z(), Deg(90.0))
+                    * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(-90.0))
+            }
+            Face::Right => {
+                Quaternion::from_axis_angle(Vector3::unit_z(), Deg(-90.0))
+                    * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(90.0))
+            }
+            Face::Front => {
+                Quaternion::from_axis_angle(Vector3::unit_x(), Deg(-90.0))
+                    * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(180.0))
+            }
+            Face::Back => Quaternion::from_axis_angle(Vector3::unit_x(), Deg(90.0)),
+        }
+    }
+
+    // Position of the face's instance relative to its block
+    pub fn instance_offset(self) -> Vector3<f32> {
+        match self {
+            Face::Top => Vector3::new(0.0, 1.0, 1.0),
+            Face::Bottom => Vector3::new(0.0, 0.0, 0.0),
+            Face::Left => Vector3::new(1.0, 1.0, 0.0),
+            Face::Right => Vector3::new(0.0, 1.0, 1.0),
+            Face::Front => Vector3::new(1.0, 1.0, 1.0),
+            Face::Back => Vector3::new(0.0, 1.0, 0.0),
+        }
+    }
+
+    pub fn opposite(self) -> Face {
+        match self {
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+            Face::Left => Face::Right,
+            Face::Right => Face::Left,
+            Face::Front => Face::Back,
+            Face::Back => Face::Front,
+        }
+    }
+}
+
 pub struct BlockCollision {
     distance: f32,
     block_pos: cgmath::Point3<usize>,
     collision_point: cgmath::Point3<f32>,
 }
 
+// What the crosshair ray hits first, see `WorldState::get_colliding_block`
+pub enum RaycastHit {
+    Block(BlockCollision),
+    Entity { id: EntityId, distance: f32 },
+}
+
+impl RaycastHit {
+    fn block(self) -> Option<BlockCollision> {
+        match self {
+            RaycastHit::Block(collision) => Some(collision),
+            RaycastHit::Entity { .. } => None,
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 struct NeighborBitmap {
     bitmap: Bitmap<8>,
@@ -188,6 +480,23 @@ impl NeighborBitmap {
     pub fn set(&mut self, face: Face, value: bool) -> bool {
         self.bitmap.set(face as usize, value)
     }
+
+    pub fn are_all_faces_hidden(&self) -> bool {
+        self.bitmap.len() == 6
+    }
+}
+
+// Whether a block's face is hidden by the block next to it. Same rules as `WorldState::set_block`.
+fn is_face_hidden(block_type: BlockType, neighbor_type: BlockType) -> bool {
+    match (block_type, neighbor_type) {
+        (BlockType::Water, BlockType::Water) => true,
+        (_, BlockType::Water) => false,
+        // Water shows through the gaps in leaves and sprites
+        (BlockType::Water, neighbor_type) => {
+            neighbor_type != BlockType::Empty && !neighbor_type.is_semi_translucent()
+        }
+        (_, neighbor_type) => !neighbor_type.is_translucent(),
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -210,32 +519,31 @@ cfg_if::cfg_if! {
     if #[cfg(target_arch = "wasm32")] {
 // The largest the world can be in xz dimension
 pub const MAX_CHUNK_WORLD_WIDTH: usize = 1024;
-// How many chunks are visible in xz dimension
-pub const VISIBLE_CHUNK_WIDTH: usize = 10;
-// Estimate of farthest z distance that is rendered
-pub const Z_FAR: f32 = 78.0;
-pub const Z_FADE_START: f32 = 66.0;
+// How many chunks are visible in xz dimension by default, see `WorldState::visible_chunk_width`
+pub const DEFAULT_VISIBLE_CHUNK_WIDTH: usize = 10;
     } else {
 // The largest the world can be in xz dimension
 pub const MAX_CHUNK_WORLD_WIDTH: usize = 1024;
-// How many chunks are visible in xz dimension
-pub const VISIBLE_CHUNK_WIDTH: usize = 32;
-// Estimate of farthest z distance that is rendered
-pub const Z_FAR: f32 = 250.0;
-pub const Z_FADE_START: f32 = 220.0;
+// How many chunks are visible in xz dimension by default, see `WorldState::visible_chunk_width`
+pub const DEFAULT_VISIBLE_CHUNK_WIDTH: usize = 32;
     }
 }
 
+// Bounds for the render distance setting. Widths are kept even, so the camera chunk sits in the
+// middle of the visible chunks.
+pub const MIN_VISIBLE_CHUNK_WIDTH: usize = 4;
+pub const MAX_VISIBLE_CHUNK_WIDTH: usize = 64;
+
 const CHUNK_DOES_NOT_EXIST_VALUE: u32 = u32::max_value();
 pub const NO_RENDER_DESCRIPTOR_INDEX: usize = usize::max_value();
 
-const MIN_HEIGHT: u16 = 2;
-const MAX_HEIGHT: u16 = 80;
-const WATER_HEIGHT: u16 = 26;
+pub const MIN_HEIGHT: u16 = 2;
+pub const MAX_HEIGHT: u16 = 80;
+pub const WATER_HEIGHT: u16 = 26;
 
 const MAX_BREAK_DISTANCE: usize = 6;
 
-const WATER_BLOCK_Y_HEIGHT: f32 = 0.8;
+pub const WATER_BLOCK_Y_HEIGHT: f32 = 0.8;
 
 // Goal: infinite world generation
 
@@ -284,6 +592,12 @@ pub struct ChunkData {
     // TODO: find a better name
     pub camera_relative_position: [usize; 2],
     pub typed_instances_vec: Vec<TypedInstances>,
+    // Lowest and highest Y covered by the chunk's visible blocks, used to cull the chunk. Both are
+    // 0 if nothing is visible.
+    pub y_bounds: [f32; 2],
+    pub connectivity: ChunkConnectivity,
+    // Camera position the translucent and semi-translucent instances were sorted back to front for
+    pub translucent_sorted_from: Point3<f32>,
 }
 
 pub struct Chunk {
@@ -343,10 +657,24 @@ struct InputState {
     jump_button_state: ButtonState,
     last_joystick_vector: (f64, f64),
     last_translation_joystick_vector: (f64, f64),
+    // Touchpads scroll by pixels, they're added up until they make a full step through the hotbar
+    hotbar_scroll_pixels: f64,
 }
 
+// Pixels of touchpad scrolling per hotbar slot
+const HOTBAR_SCROLL_STEP_PIXELS: f64 = 50.0;
+
 const DEFAULT_IS_FLYING: bool = false;
 
+// The character is a cylinder 1 voxel wide and 2 voxels tall
+pub const CHARACTER_RADIUS: f32 = 0.5;
+pub const CHARACTER_HALF_HEIGHT: f32 = 1.0;
+const CHARACTER_MAX_HEALTH: f32 = 20.0;
+// Health points the character gets back per second while it's alive
+const CHARACTER_REGENERATION_PER_SEC: f32 = 0.1;
+// Below this much daylight, places away from light sources are dark enough for hostile mobs
+const DARKNESS_MAX_DAYLIGHT: f32 = 0.25;
+
 pub struct WorldState {
     pub chunk_indices: Vec2d<u32>,
     chunks: Vec<Chunk>,
@@ -354,10 +682,28 @@ pub struct WorldState {
     highlighted_block: Option<[usize; 3]>,
 
     pub character_entity: CharacterEntity,
-    pub place_block_type: BlockType,
+    // Where the character starts out and comes back to when it dies
+    spawn_position: glam::Vec3,
+    pub health: Health,
+    pub inventory: Inventory,
+    // Mobs, dropped items and anything else that moves on its own
+    pub entities: Entities,
+    recipe_book: RecipeBook,
+    // How far along breaking the targeted block is, see `held_tool` for what it's broken with
+    mining: Mining,
     input_state: InputState,
 
+    // World positions of all light-emitting blocks
+    light_sources: HashSet<[usize; 3]>,
+
     pub is_flying: bool,
+    pub world_clock: WorldClock,
+    // How many chunks are visible in xz dimension, i.e. the render distance. Terrain further away
+    // is drawn at a lower resolution, see lod.rs.
+    visible_chunk_width: usize,
+    // Merge opaque faces into larger quads when meshing chunks. Only turned off to compare
+    // performance, see benches/meshing.rs
+    pub greedy_meshing: bool,
 }
 
 macro_rules! set_block {
@@ -398,7 +744,12 @@ impl WorldState {
             highlighted_chunk: None,
             highlighted_block: None,
             character_entity,
-            place_block_type: BlockType::DEFAULT_PLACE_BLOCK_TYPE,
+            spawn_position: initial_pos,
+            health: Health::new(CHARACTER_MAX_HEALTH),
+            inventory: Inventory::new(),
+            entities: Entities::default(),
+            recipe_book: RecipeBook::builtin(),
+            mining: Mining::default(),
             input_state: InputState {
                 is_forward_pressed: false,
                 is_backward_pressed: false,
@@ -407,8 +758,13 @@ impl WorldState {
                 jump_button_state: ButtonState::Idle,
                 last_joystick_vector: (0.0, 0.0),
                 last_translation_joystick_vector: (0.0, 0.0),
+                hotbar_scroll_pixels: 0.0,
             },
+            light_sources: HashSet::new(),
             is_flying: DEFAULT_IS_FLYING,
+            world_clock: WorldClock::new(DEFAULT_TIME_OF_DAY),
+            visible_chunk_width: DEFAULT_VISIBLE_CHUNK_WIDTH,
+            greedy_meshing: true,
         }
     }
 
@@ -532,7 +888,7 @@ impl WorldState {
 
             // Special cases:
             // 1. If we're breaking a block next to water, fill this block with water instead
-            // 2. If we're breaking a block with a flower above it, also remove the flower
+            // 2. If we're breaking a block with a sprite (flower, torch) above it, also remove the sprite
             if block_type == BlockType::Empty {
                 for i in 0..6 {
                     if let Some(neighbor) = neighbors[i] {
@@ -541,10 +897,11 @@ impl WorldState {
                         {
                             block_type = BlockType::Water;
                         }
-                        if (*neighbor.block).block_type == BlockType::RedFlower
+                        if (*neighbor.block).block_type.is_sprite()
                             && neighbor.this_shared_face == Face::Top
                         {
                             (*neighbor.block).block_type = BlockType::Empty;
+                            self.light_sources.remove(&[world_x, y + 1, world_z]);
                         }
                     }
                 }
@@ -559,6 +916,11 @@ impl WorldState {
             }
 
             (*this_block).block_type = block_type;
+            if block_type.is_emissive() {
+                self.light_sources.insert([world_x, y, world_z]);
+            } else {
+                self.light_sources.remove(&[world_x, y, world_z]);
+            }
             for i in 0..6 {
                 let neighbor = match neighbors[i] {
                     Some(neighbor) => neighbor,
@@ -611,101 +973,108 @@ impl WorldState {
             .collect::<Vec<_>>()
     }
 
-    pub fn maybe_generate_tree(&mut self, base_location: [usize; 3]) -> bool {
-        const TREE_CHANCE: f32 = 1.0 / 200.0;
-        if rand::thread_rng().gen::<f32>() > TREE_CHANCE {
-            return false;
+    pub fn generate_chunk(&mut self, chunk_idx: [usize; 2]) {
+        self.install_generated_chunk(GeneratedChunk::generate(chunk_idx));
+    }
+
+    // Adds a chunk made by `GeneratedChunk::generate` to the world. Returns the chunks whose
+    // meshes are affected, i.e. the chunk and its generated neighbors.
+    pub fn install_generated_chunk(&mut self, generated: GeneratedChunk) -> Vec<[usize; 2]> {
+        let chunk_idx = generated.chunk_idx;
+        self.allocate_chunk_neighborhood(chunk_idx);
+        if self.get_chunk(chunk_idx).is_generated {
+            return vec![];
         }
 
-        let mut trunk_base = base_location;
-        trunk_base[1] += 1;
+        let [chunk_x, chunk_z] = chunk_idx;
+        let (base_x, base_z) = (chunk_x * CHUNK_XZ_SIZE, chunk_z * CHUNK_XZ_SIZE);
 
-        let [x, y_base, z] = trunk_base;
-        let trunk_top = y_base + 6;
-        for y in y_base..trunk_top {
-            set_block!(self, x, y, z, BlockType::Tree);
+        // Trees from neighboring chunks may have already grown into this chunk, keep them
+        let mut kept_blocks = vec![];
+        let chunk = self.get_chunk_mut(chunk_idx);
+        for (z, y, x) in iproduct!(0..CHUNK_XZ_SIZE, 0..CHUNK_Y_SIZE, 0..CHUNK_XZ_SIZE) {
+            let block = chunk.blocks.get_unchecked(x, y, z);
+            if !block.is_empty() {
+                kept_blocks.push(([base_x + x, y, base_z + z], block.block_type));
+            }
         }
+        chunk.blocks = generated.blocks;
+        chunk.is_generated = true;
+        self.stitch_chunk_borders(chunk_idx);
 
-        // Minecraft Lollipop Spruce Tree
-        let leaf_slice_diameters = [7, 7, 5, 3];
-        let mut leaf_y = trunk_top - 3;
-        for diam in leaf_slice_diameters {
-            let radius = (diam - 1) / 2;
-            for (leaf_x, leaf_z) in
-                iproduct!(x - radius + 1..x + radius, z - radius + 1..z + radius)
-            {
-                // TODO(aleks): need a "set block if empty" primitive
-                if self.get_block(leaf_x, leaf_y, leaf_z).is_empty() {
-                    set_block!(self, leaf_x, leaf_y, leaf_z, BlockType::random_tree_leaf());
-                }
+        for ([x, y, z], block_type) in kept_blocks.into_iter().chain(generated.spilled_blocks) {
+            if self.get_block(x, y, z).is_empty() {
+                set_block!(self, x, y, z, block_type);
             }
-            leaf_y += 1;
         }
-        true
+
+        iproduct!(chunk_x - 1..=chunk_x + 1, chunk_z - 1..=chunk_z + 1)
+            .map(|(x, z)| [x, z])
+            .filter(|&neighbor_idx| self.get_chunk(neighbor_idx).is_generated)
+            .collect()
     }
 
-    pub fn maybe_generate_flower(&mut self, ground_elevation: [usize; 3]) -> bool {
-        const FLOWER_CHANCE: f32 = 1.0 / 100.0;
-        if rand::thread_rng().gen::<f32>() > FLOWER_CHANCE {
-            return false;
+    // Recomputes which faces are hidden along the borders between a chunk and its neighbors
+    fn stitch_chunk_borders(&mut self, chunk_idx: [usize; 2]) {
+        let [chunk_x, chunk_z] = chunk_idx;
+        let last = CHUNK_XZ_SIZE - 1;
+
+        // Neighboring chunk, and the face of this chunk's blocks that it touches
+        let borders = [
+            ([chunk_x + 1, chunk_z], Face::Left),
+            ([chunk_x - 1, chunk_z], Face::Right),
+            ([chunk_x, chunk_z + 1], Face::Front),
+            ([chunk_x, chunk_z - 1], Face::Back),
+        ];
+        for (neighbor_chunk_idx, face) in borders {
+            let this_chunk = self.chunk_indices[chunk_idx] as usize;
+            let neighbor_chunk = self.chunk_indices[neighbor_chunk_idx] as usize;
+
+            for (i, y) in iproduct!(0..CHUNK_XZ_SIZE, 0..CHUNK_Y_SIZE) {
+                // Chunk-relative positions of the two blocks sharing the face
+                let (this_pos, neighbor_pos) = match face {
+                    Face::Left => ([last, y, i], [0, y, i]),
+                    Face::Right => ([0, y, i], [last, y, i]),
+                    Face::Front => ([i, y, last], [i, y, 0]),
+                    _ => ([i, y, 0], [i, y, last]),
+                };
+                let block_type = self.chunks[this_chunk].blocks[this_pos].block_type;
+                let neighbor_type = self.chunks[neighbor_chunk].blocks[neighbor_pos].block_type;
+                self.chunks[this_chunk].blocks[this_pos]
+                    .neighbors
+                    .set(face, is_face_hidden(block_type, neighbor_type));
+                self.chunks[neighbor_chunk].blocks[neighbor_pos]
+                    .neighbors
+                    .set(face.opposite(), is_face_hidden(neighbor_type, block_type));
+            }
         }
-
-        let [x, y_base, z] = ground_elevation;
-        set_block!(self, x, y_base + 1, z, BlockType::RedFlower);
-        true
     }
 
-    pub fn generate_chunk(&mut self, [chunk_x, chunk_z]: [usize; 2]) {
-        let elevation_map = map_generation::generate_chunk_elevation_map(
-            [chunk_x, chunk_z],
-            MIN_HEIGHT,
-            MAX_HEIGHT,
-        );
-        let (base_x, base_z) = (chunk_x * CHUNK_XZ_SIZE, chunk_z * CHUNK_XZ_SIZE);
-        // vprintln!(
-        //     "Took {}ms to generate elevation map",
-        //     func_start.elapsed().as_millis()
-        // );
+    pub fn maybe_allocate_chunk(&mut self, outer_chunk_idx: [usize; 2]) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let func_start = Instant::now();
 
-        for (z, x) in iproduct!(0..CHUNK_XZ_SIZE, 0..CHUNK_XZ_SIZE) {
-            let ground_elevation = elevation_map[x][z] as usize;
-            let (world_x, world_z) = (base_x + x, base_z + z);
-            let top_block_type = if ground_elevation < WATER_HEIGHT as usize {
-                BlockType::Sand
-            } else {
-                BlockType::Grass
-            };
-            set_block!(self, world_x, ground_elevation, world_z, top_block_type);
+        self.allocate_chunk_neighborhood(outer_chunk_idx);
 
-            let min_ground_or_water = core::cmp::min(ground_elevation, WATER_HEIGHT as usize);
-            for y in 0..min_ground_or_water {
-                set_block!(self, world_x, y, world_z, BlockType::Sand);
-            }
-            for y in min_ground_or_water..ground_elevation {
-                set_block!(self, world_x, y, world_z, BlockType::Dirt);
-            }
-            for y in (MIN_HEIGHT as usize)..(WATER_HEIGHT as usize) {
-                if self.get_block(world_x, y, world_z).block_type == BlockType::Empty {
-                    set_block!(self, world_x, y, world_z, BlockType::Water);
-                }
-            }
+        #[cfg(not(target_arch = "wasm32"))]
+        vprintln!(
+            "Took {}ms to allocate memory",
+            func_start.elapsed().as_millis()
+        );
 
-            if top_block_type == BlockType::Grass {
-                let did_generate_tree =
-                    self.maybe_generate_tree([world_x, ground_elevation, world_z]);
-                if !did_generate_tree {
-                    self.maybe_generate_flower([world_x, ground_elevation, world_z]);
-                }
-            }
+        if !self.get_chunk(outer_chunk_idx).is_generated {
+            self.generate_chunk(outer_chunk_idx)
         }
 
-        self.get_chunk_mut([chunk_x, chunk_z]).is_generated = true;
-    }
-
-    pub fn maybe_allocate_chunk(&mut self, outer_chunk_idx: [usize; 2]) {
         #[cfg(not(target_arch = "wasm32"))]
-        let func_start = Instant::now();
+        vprintln!(
+            "Took {}ms to process elevation map",
+            func_start.elapsed().as_millis()
+        );
+    }
 
+    // Allocates (but doesn't generate) a chunk and its neighbors
+    pub fn allocate_chunk_neighborhood(&mut self, outer_chunk_idx: [usize; 2]) {
         let mut allocate_inner = |inner_chunk_idx: [usize; 2]| {
             if self.chunk_indices[inner_chunk_idx] == CHUNK_DOES_NOT_EXIST_VALUE {
                 let new_chunk = Chunk {
@@ -734,22 +1103,11 @@ impl WorldState {
         allocate_inner([chunk_x + 1, chunk_z - 1]);
         allocate_inner([chunk_x + 1, chunk_z]);
         allocate_inner([chunk_x + 1, chunk_z + 1]);
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        vprintln!(
-            "Took {}ms to allocate memory",
-            func_start.elapsed().as_millis()
-        );
-
-        if !self.get_chunk(outer_chunk_idx).is_generated {
-            self.generate_chunk(outer_chunk_idx)
-        }
-
-        #[cfg(not(target_arch = "wasm32"))]
-        vprintln!(
-            "Took {}ms to process elevation map",
-            func_start.elapsed().as_millis()
-        );
+    pub fn is_chunk_generated(&self, chunk_idx: [usize; 2]) -> bool {
+        self.chunk_indices[chunk_idx] != CHUNK_DOES_NOT_EXIST_VALUE
+            && self.get_chunk(chunk_idx).is_generated
     }
 
     pub fn initial_setup(&mut self, camera: &Camera) {
@@ -797,18 +1155,69 @@ impl WorldState {
         chunk_order
     }
 
+    // Closest light-emitting blocks to the camera, bounded by what fits in the light uniform
+    pub fn nearest_point_lights(&self, camera: &Camera) -> Vec<PointLight> {
+        const MAX_POINT_LIGHT_DISTANCE: f32 = 64.0;
+
+        let eye = glam::Vec3::new(camera.eye.x, camera.eye.y, camera.eye.z);
+        let mut point_lights = self
+            .light_sources
+            .iter()
+            .filter_map(|&[x, y, z]| {
+                let emission = self.get_block(x, y, z).block_type.light_emission()?;
+                let center = glam::Vec3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+                let radius = emission.level as f32;
+                if center.distance(eye) - radius > MAX_POINT_LIGHT_DISTANCE {
+                    return None;
+                }
+                Some(PointLight {
+                    position: center - eye,
+                    color: emission.color.into(),
+                    radius,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        point_lights.sort_by(|a, b| {
+            a.position
+                .length_squared()
+                .partial_cmp(&b.position.length_squared())
+                .unwrap()
+        });
+        point_lights.truncate(MAX_POINT_LIGHTS);
+        point_lights
+    }
+
+    pub fn visible_chunk_width(&self) -> usize {
+        self.visible_chunk_width
+    }
+
+    // Clamped to the supported range and rounded down to an even width. Returns whether the width
+    // changed, in which case the visible chunks need to be updated, see
+    // `get_chunk_order_by_distance`.
+    pub fn set_visible_chunk_width(&mut self, visible_chunk_width: usize) -> bool {
+        let visible_chunk_width =
+            visible_chunk_width.clamp(MIN_VISIBLE_CHUNK_WIDTH, MAX_VISIBLE_CHUNK_WIDTH) & !1;
+        if visible_chunk_width == self.visible_chunk_width {
+            return false;
+        }
+        self.visible_chunk_width = visible_chunk_width;
+        true
+    }
+
     fn iter_visible_chunks(&self, camera: &Camera) -> std::vec::IntoIter<[usize; 2]> {
         let (camera_chunk_x, camera_chunk_z) = (
             (camera.eye.x / CHUNK_XZ_SIZE as f32) as usize,
             (camera.eye.z / CHUNK_XZ_SIZE as f32) as usize,
         );
-        let first_chunk_x_index = camera_chunk_x - (VISIBLE_CHUNK_WIDTH / 2);
-        let first_chunk_z_index = camera_chunk_z - (VISIBLE_CHUNK_WIDTH / 2);
+        let visible_chunk_width = self.visible_chunk_width;
+        let first_chunk_x_index = camera_chunk_x - (visible_chunk_width / 2);
+        let first_chunk_z_index = camera_chunk_z - (visible_chunk_width / 2);
 
         let mut chunk_idxs: Vec<[usize; 2]> = vec![];
         for (chunk_x, chunk_z) in iproduct!(
-            first_chunk_x_index..first_chunk_x_index + VISIBLE_CHUNK_WIDTH,
-            first_chunk_z_index..first_chunk_z_index + VISIBLE_CHUNK_WIDTH
+            first_chunk_x_index..first_chunk_x_index + visible_chunk_width,
+            first_chunk_z_index..first_chunk_z_index + visible_chunk_width
         ) {
             chunk_idxs.push([chunk_x, chunk_z]);
         }
@@ -826,8 +1235,8 @@ impl WorldState {
             (camera.eye.x / CHUNK_XZ_SIZE as f32) as usize,
             (camera.eye.z / CHUNK_XZ_SIZE as f32) as usize,
         );
-        let first_chunk_x_index = camera_chunk_x - (VISIBLE_CHUNK_WIDTH / 2);
-        let first_chunk_z_index = camera_chunk_z - (VISIBLE_CHUNK_WIDTH / 2);
+        let first_chunk_x_index = camera_chunk_x - (self.visible_chunk_width / 2);
+        let first_chunk_z_index = camera_chunk_z - (self.visible_chunk_width / 2);
 
         [
             world_chunk_x - first_chunk_x_index,
@@ -839,20 +1248,24 @@ impl WorldState {
         #[cfg(not(target_arch = "wasm32"))]
         let func_start = Instant::now();
 
+        let visible_chunk_width = self.visible_chunk_width;
         let mut all_chunk_data: Vec2d<ChunkData> = Vec2d::new(
             vec![
                 ChunkData {
                     position: [0, 0],
                     camera_relative_position: [0, 0],
                     typed_instances_vec: vec![],
+                    y_bounds: [0.0, 0.0],
+                    connectivity: ChunkConnectivity::ALL,
+                    translucent_sorted_from: camera.eye,
                 };
-                VISIBLE_CHUNK_WIDTH * VISIBLE_CHUNK_WIDTH
+                visible_chunk_width * visible_chunk_width
             ],
-            [VISIBLE_CHUNK_WIDTH, VISIBLE_CHUNK_WIDTH],
+            [visible_chunk_width, visible_chunk_width],
         );
 
         let mut abs_chunk_iter = self.iter_visible_chunks(camera);
-        for (rel_chunk_x, rel_chunk_z) in iproduct!(0..VISIBLE_CHUNK_WIDTH, 0..VISIBLE_CHUNK_WIDTH)
+        for (rel_chunk_x, rel_chunk_z) in iproduct!(0..visible_chunk_width, 0..visible_chunk_width)
         {
             let [abs_chunk_x, abs_chunk_z] = abs_chunk_iter.next().unwrap();
             all_chunk_data[[rel_chunk_x, rel_chunk_z]] =
@@ -873,268 +1286,57 @@ impl WorldState {
 
     pub fn compute_chunk_mesh(&mut self, chunk_idx: [usize; 2], camera: &Camera) -> ChunkData {
         self.maybe_allocate_chunk(chunk_idx);
+        self.chunk_snapshot(chunk_idx, camera).compute_mesh()
+    }
 
-        use cgmath::{Deg, Quaternion};
-
-        let no_rotation: Quaternion<f32> = Quaternion::from_axis_angle(Vector3::unit_y(), Deg(0.0));
-        let flip_to_top: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(180.0));
-        let flip_to_front: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(90.0));
-        let flip_to_back: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(-90.0))
-                * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(180.0));
-        let flip_to_left: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_z(), Deg(90.0))
-                * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(-90.0));
-        let flip_to_right: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_z(), Deg(-90.0))
-                * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(90.0));
-
-        let flip_to_diagonal_right_front: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(90.0))
-                * Quaternion::from_axis_angle(Vector3::unit_z(), Deg(45.0));
-        let flip_to_diagonal_left_front: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(-90.0))
-                * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(180.0))
-                * Quaternion::from_axis_angle(Vector3::unit_z(), Deg(-45.0));
-        let flip_to_diagonal_right_back: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(-90.0))
-                * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(180.0))
-                * Quaternion::from_axis_angle(Vector3::unit_z(), Deg(45.0));
-        let flip_to_diagonal_left_back: Quaternion<f32> =
-            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(90.0))
-                * Quaternion::from_axis_angle(Vector3::unit_z(), Deg(-45.0));
+    // Copies everything needed to mesh a chunk, so meshing can run on another thread
+    pub fn chunk_snapshot(&self, chunk_idx: [usize; 2], camera: &Camera) -> ChunkSnapshot {
+        let [chunk_x, chunk_z] = chunk_idx;
+        let mut blocks = Vec3d::new(vec![
+            Block::default();
+            SNAPSHOT_XZ_SIZE * CHUNK_Y_SIZE * SNAPSHOT_XZ_SIZE
+        ]);
+        for (snapshot_z, snapshot_x) in iproduct!(0..SNAPSHOT_XZ_SIZE, 0..SNAPSHOT_XZ_SIZE) {
+            let world_x = chunk_x * CHUNK_XZ_SIZE + snapshot_x - 1;
+            let world_z = chunk_z * CHUNK_XZ_SIZE + snapshot_z - 1;
+            let chunk = self.get_chunk([world_x / CHUNK_XZ_SIZE, world_z / CHUNK_XZ_SIZE]);
+            let (x, z) = (world_x % CHUNK_XZ_SIZE, world_z % CHUNK_XZ_SIZE);
+            for y in 0..CHUNK_Y_SIZE {
+                blocks[[snapshot_x, y, snapshot_z]] = *chunk.blocks.get_unchecked(x, y, z);
+            }
+        }
 
-        let mut opaque_instances = Vec::<InstanceRaw>::with_capacity(4096);
-        let mut opaque_instance_distances = Vec::<i32>::with_capacity(4096);
+        ChunkSnapshot {
+            chunk_idx,
+            blocks,
+            highlighted_block: self.highlighted_block,
+            greedy_meshing: self.greedy_meshing,
+            camera_eye: camera.eye,
+            camera_relative_position: self
+                .camera_relative_position_from_world_position(chunk_idx, camera),
+        }
+    }
 
-        let mut translucent_instances = Vec::<InstanceRaw>::with_capacity(4096);
-        let mut translucent_instance_distances = Vec::<i32>::with_capacity(4096);
+    pub fn highlight_colliding_block(&mut self, camera: &Camera) -> Vec<[usize; 2]> {
+        let mut modified_chunks: Vec<[usize; 2]> = vec![];
 
-        let mut semi_translucent_instances = Vec::<InstanceRaw>::with_capacity(4096);
-        let mut semi_translucent_instance_distances = Vec::<i32>::with_capacity(4096);
+        let prev_highlighted_chunk = self.highlighted_chunk;
+        if let Some(chunk_idx) = prev_highlighted_chunk {
+            modified_chunks.push(chunk_idx);
+        }
 
-        let chunk = self.get_chunk(chunk_idx);
+        let collision = match self
+            .get_colliding_block(camera, MAX_BREAK_DISTANCE, true)
+            .and_then(RaycastHit::block)
+        {
+            Some(collision) => collision,
+            None => {
+                self.highlighted_chunk = None;
+                self.highlighted_block = None;
 
-        let [chunk_x, chunk_z] = chunk_idx;
-
-        // Don't use !iproduct here to squeeze out a tiny bit of perf
-        for chunk_rel_z in 0..CHUNK_XZ_SIZE {
-            for chunk_rel_x in 0..CHUNK_XZ_SIZE {
-                for y in 0..CHUNK_Y_SIZE {
-                    let world_x = (chunk_x * CHUNK_XZ_SIZE) + chunk_rel_x;
-                    let world_z = (chunk_z * CHUNK_XZ_SIZE) + chunk_rel_z;
-
-                    let position = cgmath::Vector3::new(world_x as f32, y as f32, world_z as f32);
-                    let block = chunk.blocks.get_unchecked(chunk_rel_x, y, chunk_rel_z);
-                    if block.block_type == BlockType::Empty {
-                        continue;
-                    }
-
-                    let mut highlight_adjust = 1.0;
-                    if let Some(highlighted_block) = self.highlighted_block {
-                        if highlighted_block == [world_x, y, world_z] {
-                            highlight_adjust = 1.8;
-                        }
-                    }
-
-                    let [top_offset, bottom_offset, side_offset] =
-                        block.block_type.texture_atlas_offsets();
-                    let alpha_adjust = if block.block_type == BlockType::Water {
-                        0.7
-                    } else {
-                        1.0
-                    };
-
-                    let (instance_vec, distance_vec) = if block.block_type.is_semi_translucent() {
-                        (
-                            &mut semi_translucent_instances,
-                            &mut semi_translucent_instance_distances,
-                        )
-                    } else if block.block_type.is_translucent() {
-                        (
-                            &mut translucent_instances,
-                            &mut translucent_instance_distances,
-                        )
-                    } else {
-                        (&mut opaque_instances, &mut opaque_instance_distances)
-                    };
-
-                    let distance_from_camera = (camera.eye - cgmath::Vector3::new(0.5, 0.5, 0.5))
-                        .distance((world_x as f32, y as f32, world_z as f32).into());
-
-                    let half_diag_shift = (1.0 - (1.0 / 2.0_f32.sqrt())) / 2.0;
-
-                    if block.block_type.is_sprite() {
-                        // left cross, front-face
-                        instance_vec.push(InstanceRaw::new(
-                            position
-                                + cgmath::Vector3::new(1.0 - half_diag_shift, 1.0, half_diag_shift),
-                            flip_to_diagonal_left_front,
-                            side_offset,
-                            (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust) * highlight_adjust)
-                                .into(),
-                        ));
-                        distance_vec.push(-distance_from_camera as i32);
-                        // right cross, front-face
-                        instance_vec.push(InstanceRaw::new(
-                            position + cgmath::Vector3::new(half_diag_shift, 1.0, half_diag_shift),
-                            flip_to_diagonal_right_front,
-                            side_offset,
-                            (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust) * highlight_adjust)
-                                .into(),
-                        ));
-                        distance_vec.push(-distance_from_camera as i32);
-                        // left cross, back-face
-                        instance_vec.push(InstanceRaw::new(
-                            position
-                                + cgmath::Vector3::new(half_diag_shift, 1.0, 1.0 - half_diag_shift),
-                            flip_to_diagonal_left_back,
-                            side_offset,
-                            (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust) * highlight_adjust)
-                                .into(),
-                        ));
-                        distance_vec.push(-distance_from_camera as i32);
-                        // right cross, back-face
-                        instance_vec.push(InstanceRaw::new(
-                            position
-                                + cgmath::Vector3::new(
-                                    1.0 - half_diag_shift,
-                                    1.0,
-                                    1.0 - half_diag_shift,
-                                ),
-                            flip_to_diagonal_right_back,
-                            side_offset,
-                            (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust) * highlight_adjust)
-                                .into(),
-                        ));
-                        distance_vec.push(-distance_from_camera as i32);
-                    } else {
-                        if !block.neighbors.get(Face::Top) {
-                            let y_offset = if block.block_type == BlockType::Water {
-                                WATER_BLOCK_Y_HEIGHT
-                            } else {
-                                1.0
-                            };
-                            instance_vec.push(InstanceRaw::new(
-                                position + cgmath::Vector3::new(0.0, y_offset, 1.0),
-                                flip_to_top,
-                                top_offset,
-                                (cgmath::Vector4::new(1.0, 1.0, 1.0, alpha_adjust)
-                                    * highlight_adjust)
-                                    .into(),
-                            ));
-
-                            // N.B.
-                            // - store negative value because we want further instances to be drawn first
-                            // - lose float precision to gain speed in sorting (I did not benchmark this, could be useless)
-                            distance_vec.push(-distance_from_camera as i32);
-                        }
-                        if !block.neighbors.get(Face::Bottom) {
-                            instance_vec.push(InstanceRaw::new(
-                                position,
-                                no_rotation,
-                                bottom_offset,
-                                (cgmath::Vector4::new(1.0, 1.0, 1.0, alpha_adjust)
-                                    * highlight_adjust)
-                                    .into(),
-                            ));
-                            distance_vec.push(-distance_from_camera as i32);
-                        }
-                        if !block.neighbors.get(Face::Left) {
-                            instance_vec.push(InstanceRaw::new(
-                                position + cgmath::Vector3::new(1.0, 1.0, 0.0),
-                                flip_to_left,
-                                side_offset,
-                                (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust)
-                                    * highlight_adjust)
-                                    .into(),
-                            ));
-                            distance_vec.push(-distance_from_camera as i32);
-                        }
-                        if !block.neighbors.get(Face::Right) {
-                            instance_vec.push(InstanceRaw::new(
-                                position + cgmath::Vector3::new(0.0, 1.0, 1.0),
-                                flip_to_right,
-                                side_offset,
-                                (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust)
-                                    * highlight_adjust)
-                                    .into(),
-                            ));
-                            distance_vec.push(-distance_from_camera as i32);
-                        }
-                        if !block.neighbors.get(Face::Front) {
-                            instance_vec.push(InstanceRaw::new(
-                                position + cgmath::Vector3::new(1.0, 1.0, 1.0),
-                                flip_to_back,
-                                side_offset,
-                                (cgmath::Vector4::new(0.8, 0.8, 0.8, alpha_adjust)
-                                    * highlight_adjust)
-                                    .into(),
-                            ));
-                            distance_vec.push(-distance_from_camera as i32);
-                        }
-                        if !block.neighbors.get(Face::Back) {
-                            instance_vec.push(InstanceRaw::new(
-                                position + cgmath::Vector3::new(0.0, 1.0, 0.0),
-                                flip_to_front,
-                                side_offset,
-                                (cgmath::Vector4::new(0.8, 0.8, 0.8, alpha_adjust)
-                                    * highlight_adjust)
-                                    .into(),
-                            ));
-                            distance_vec.push(-distance_from_camera as i32);
-                        }
-                    }
-                }
-            }
-        }
-
-        permutation::sort(&translucent_instance_distances)
-            .apply_slice_in_place(&mut translucent_instances);
-        permutation::sort(&semi_translucent_instance_distances)
-            .apply_slice_in_place(&mut semi_translucent_instances);
-        permutation::sort(&opaque_instance_distances).apply_slice_in_place(&mut opaque_instances);
-
-        ChunkData {
-            position: chunk_idx,
-            camera_relative_position: self
-                .camera_relative_position_from_world_position(chunk_idx, camera),
-            typed_instances_vec: vec![
-                TypedInstances {
-                    data_type: ChunkDataType::Opaque,
-                    instance_data: opaque_instances,
-                },
-                TypedInstances {
-                    data_type: ChunkDataType::Translucent,
-                    instance_data: translucent_instances,
-                },
-                TypedInstances {
-                    data_type: ChunkDataType::SemiTranslucent,
-                    instance_data: semi_translucent_instances,
-                },
-            ],
-        }
-    }
-
-    pub fn highlight_colliding_block(&mut self, camera: &Camera) -> Vec<[usize; 2]> {
-        let mut modified_chunks: Vec<[usize; 2]> = vec![];
-
-        let prev_highlighted_chunk = self.highlighted_chunk;
-        if let Some(chunk_idx) = prev_highlighted_chunk {
-            modified_chunks.push(chunk_idx);
-        }
-
-        let collision = match self.get_colliding_block(camera, MAX_BREAK_DISTANCE) {
-            Some(collision) => collision,
-            None => {
-                self.highlighted_chunk = None;
-                self.highlighted_block = None;
-
-                return modified_chunks;
-            }
-        };
+                return modified_chunks;
+            }
+        };
 
         let colliding_chunk = [
             (collision.block_pos.x / CHUNK_XZ_SIZE) as usize,
@@ -1168,8 +1370,14 @@ impl WorldState {
     //         only iterate 6 more times  # optimization
     //   pick closest colliding cube to camera eye
     //
-    // Returns colliding cube and colliding point
-    fn get_colliding_block(&self, camera: &Camera, max_distance: usize) -> Option<BlockCollision> {
+    // Returns colliding cube and colliding point, or the living entity with health in front of
+    // it when `hit_entities`
+    fn get_colliding_block(
+        &self,
+        camera: &Camera,
+        max_distance: usize,
+        hit_entities: bool,
+    ) -> Option<RaycastHit> {
         let mut all_candidate_cubes: Vec<Point3<f32>> = vec![];
 
         let camera_eye_cgmath17 = Point3::new(camera.eye.x, camera.eye.y, camera.eye.z);
@@ -1249,11 +1457,49 @@ impl WorldState {
             }
         }
 
-        if hit_first_collision {
-            Some(closest_collider)
-        } else {
-            None
+        let closest_entity = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| hit_entities && entity.health.is_some() && entity.is_alive())
+            .filter_map(|(id, entity)| {
+                let (min, max) = entity.aabb();
+                let entity_box = collision::Aabb3::new(
+                    Point3::new(min.x, min.y, min.z),
+                    Point3::new(max.x, max.y, max.z),
+                );
+                let distance = collision_ray
+                    .intersection(&entity_box)?
+                    .distance(camera_eye_cgmath17);
+                (distance <= max_distance as f32).then_some((id, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        match closest_entity {
+            Some((id, distance)) if distance < closest_collider.distance => {
+                Some(RaycastHit::Entity { id, distance })
+            }
+            _ if hit_first_collision => Some(RaycastHit::Block(closest_collider)),
+            _ => None,
+        }
+    }
+
+    // Whether the chunk holding the block column at `[x, z]` has been generated
+    pub fn is_column_generated(&self, [x, z]: [usize; 2]) -> bool {
+        let chunk_idx = [x / CHUNK_XZ_SIZE, z / CHUNK_XZ_SIZE];
+        chunk_idx.iter().all(|&idx| idx < MAX_CHUNK_WORLD_WIDTH)
+            && self.is_chunk_generated(chunk_idx)
+    }
+
+    // Type of the block at `[x, y, z]`, empty where the world hasn't been generated
+    pub fn block_type_at(&self, [x, y, z]: [usize; 3]) -> BlockType {
+        if y >= CHUNK_Y_SIZE || !self.is_column_generated([x, z]) {
+            return BlockType::Empty;
         }
+        self.get_block(x, y, z).block_type
+    }
+
+    pub fn is_block_collidable(&self, [x, y, z]: [usize; 3]) -> bool {
+        self.get_block(x, y, z).block_type.is_collidable()
     }
 
     pub fn block_collidable_at_point(&self, point: &cgmath::Point3<f32>) -> bool {
@@ -1268,7 +1514,10 @@ impl WorldState {
     ) -> Option<Vector3<f32>> {
         let distance = (next_eye - camera.eye).magnitude().ceil() as usize;
 
-        return match self.get_colliding_block(camera, distance) {
+        return match self
+            .get_colliding_block(camera, distance, false)
+            .and_then(RaycastHit::block)
+        {
             Some(collision) => {
                 let collision_point = collision.collision_point;
                 let block_pos = collision.block_pos;
@@ -1354,30 +1603,273 @@ impl WorldState {
 
     // Returns which chunks were modified
     pub fn break_block(&mut self, camera: &Camera) -> Vec<[usize; 2]> {
-        let maybe_collision = self.get_colliding_block(camera, MAX_BREAK_DISTANCE);
+        let maybe_collision = self
+            .get_colliding_block(camera, MAX_BREAK_DISTANCE, true)
+            .and_then(RaycastHit::block);
         if let Some(ref collision) = maybe_collision {
-            let (collider_x, collider_y, collider_z) = (
-                collision.block_pos.x,
-                collision.block_pos.y,
-                collision.block_pos.z,
-            );
-
             vprintln!(
                 "break_block collision point is {:?}",
                 collision.collision_point
             );
             vprintln!("break_block collision block is {:?}", collision.block_pos);
-            set_block!(self, collider_x, collider_y, collider_z, BlockType::Empty);
-
-            self.get_affected_chunks(&collision.block_pos)
+            let block_pos = collision.block_pos;
+            self.break_block_at([block_pos.x, block_pos.y, block_pos.z])
         } else {
             vec![]
         }
     }
 
+    // Returns which chunks were modified
+    pub fn break_block_at(&mut self, [x, y, z]: [usize; 3]) -> Vec<[usize; 2]> {
+        set_block!(self, x, y, z, BlockType::Empty);
+        self.get_affected_chunks(&cgmath::Point3::new(x, y, z))
+    }
+
+    // Breaks the block and drops what it yields as an item, along with a sprite on top of it that
+    // goes with it. Returns which chunks were modified.
+    pub fn harvest_block_at(&mut self, [x, y, z]: [usize; 3]) -> Vec<[usize; 2]> {
+        let mut harvested_blocks = vec![[x, y, z]];
+        if self.block_type_at([x, y + 1, z]).is_sprite() {
+            harvested_blocks.push([x, y + 1, z]);
+        }
+        for block_pos @ [x, y, z] in harvested_blocks {
+            if let Some(item) = self.get_block(x, y, z).block_type.dropped_item() {
+                dropped_item::spawn(&mut self.entities, item, 1, block_pos);
+            }
+        }
+        self.break_block_at([x, y, z])
+    }
+
+    // Whether hostile mobs can spawn at `block_pos`: at night, and out of reach of every light
+    // source's light
+    pub fn is_dark_at(&self, block_pos: [usize; 3]) -> bool {
+        let position = glam::Vec3::from(block_pos.map(|n| n as f32 + 0.5));
+        self.world_clock.daylight() < DARKNESS_MAX_DAYLIGHT
+            && self.light_sources.iter().all(|&[x, y, z]| {
+                self.get_block(x, y, z)
+                    .block_type
+                    .light_emission()
+                    .is_none_or(|emission| {
+                        let center =
+                            glam::Vec3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+                        center.distance(position) > emission.level as f32
+                    })
+            })
+    }
+
+    // Spawns mobs and runs the entities' AI, attacks and physics for one fixed tick of
+    // `tick_secs`, then picks up the dropped items the character walks into
+    pub fn entities_tick(&mut self, tick_secs: f32) {
+        // Out of the world while they're ticked, so they can look at the rest of it
+        let mut entities = std::mem::take(&mut self.entities);
+        // Nothing is left where the world isn't generated to move against
+        entities.retain(|entity| {
+            let [x, _, z] = entity.position.to_array().map(|n| n.max(0.0) as usize);
+            self.is_column_generated([x, z])
+        });
+        mob::spawn_mobs(
+            &mut entities,
+            |block_pos| self.block_type_at(block_pos),
+            |block_pos| self.is_dark_at(block_pos),
+            self.character_entity.position,
+            tick_secs,
+        );
+        entities.ai_tick(self, tick_secs);
+        let melee_hits = combat::melee_tick(
+            &mut entities,
+            self.character_entity.position,
+            CHARACTER_RADIUS,
+            CHARACTER_HALF_HEIGHT,
+            tick_secs,
+        );
+        entities.physics_tick(
+            |block_pos| self.block_type_at(block_pos).is_collidable(),
+            tick_secs,
+        );
+        dropped_item::merge(&mut entities);
+        let did_pick_up = dropped_item::pick_up(
+            &mut entities,
+            self.character_entity.position,
+            CHARACTER_RADIUS,
+            CHARACTER_HALF_HEIGHT,
+            &mut self.inventory,
+        );
+        self.entities = entities;
+        if did_pick_up {
+            self.inventory_changed();
+        }
+        self.health_tick(&melee_hits, tick_secs);
+    }
+
+    // Hurts and knocks back the character for the `melee_hits` it took this tick, heals it a
+    // little and respawns it once it's dead
+    fn health_tick(&mut self, melee_hits: &[combat::MeleeHit], tick_secs: f32) {
+        let prev_points = self.health.points.ceil();
+        self.health.tick(tick_secs);
+        for hit in melee_hits {
+            if self.health.damage(hit.damage) {
+                // The character's velocity is in blocks per tick
+                self.character_entity.velocity = combat::knockback_velocity(
+                    hit.attacker_position,
+                    self.character_entity.position,
+                ) * tick_secs;
+            }
+        }
+        self.health.heal(CHARACTER_REGENERATION_PER_SEC * tick_secs);
+
+        if self.health.is_dead() {
+            log::info!("Character died, respawning");
+            self.character_entity.position = self.spawn_position;
+            self.character_entity.prev_position = self.spawn_position;
+            self.character_entity.velocity = glam::Vec3::ZERO;
+            self.health = Health::new(CHARACTER_MAX_HEALTH);
+        }
+        if self.health.points.ceil() != prev_points {
+            self.health_changed();
+        }
+    }
+
+    // Lets the page show the character's health
+    fn health_changed(&self) {
+        #[cfg(target_arch = "wasm32")]
+        crate::wasm_utils::js_handle_health_changed(self.health.points, self.health.max_points);
+    }
+
+    // Places a block from the selected hotbar slot. Returns which chunks were modified.
+    pub fn place_selected_block(&mut self, camera: &Camera) -> Vec<[usize; 2]> {
+        let block_type = match self.inventory.selected() {
+            Some(ItemStack {
+                item: Item::Block(block_type),
+                ..
+            }) => block_type,
+            _ => return vec![],
+        };
+        let modified_chunks = self.place_block(camera, block_type);
+        if !modified_chunks.is_empty() {
+            self.inventory.take_selected();
+            self.inventory_changed();
+        }
+        modified_chunks
+    }
+
+    // Crafts the first recipe that uses the selected item, or any recipe if the selected slot is
+    // empty, from whatever is in the inventory
+    pub fn craft_with_selected(&mut self) {
+        let selected_item = self.inventory.selected().map(|item_stack| item_stack.item);
+        let recipe = match self
+            .recipe_book
+            .first_craftable(&self.inventory, selected_item)
+        {
+            Some(recipe) => recipe,
+            None => {
+                log::info!("Nothing to craft with {:?}", selected_item);
+                return;
+            }
+        };
+        recipe.craft(&mut self.inventory);
+        log::info!(
+            "Crafted {} {} from {:?}",
+            recipe.result.count,
+            recipe.result.item,
+            recipe.ingredient_counts()
+        );
+        self.inventory_changed();
+    }
+
+    // The selected tool, or bare hands
+    pub fn held_tool(&self) -> Tool {
+        self.inventory
+            .selected()
+            .and_then(|item_stack| item_stack.item.tool())
+            .unwrap_or(Tool::Hand)
+    }
+
+    // Replaces the inventory with one saved by the page, see `Inventory::to_json`
+    pub fn load_inventory(&mut self, json: &str) {
+        let inventory = serde_json::from_str(json)
+            .ok()
+            .and_then(|json| Inventory::from_json(&json));
+        match inventory {
+            Some(inventory) => {
+                self.inventory = inventory;
+                self.inventory_changed();
+            }
+            None => log::warn!("Failed to load inventory: {}", json),
+        }
+    }
+
+    fn select_hotbar_slot(&mut self, select: impl FnOnce(&mut Inventory)) {
+        let prev_slot = self.inventory.selected_hotbar_slot();
+        select(&mut self.inventory);
+        if self.inventory.selected_hotbar_slot() != prev_slot {
+            log::info!(
+                "Selected hotbar slot {}: {:?}",
+                self.inventory.selected_hotbar_slot() + 1,
+                self.inventory.selected()
+            );
+            self.inventory_changed();
+        }
+    }
+
+    // Saves the inventory, so it can be restored next time. On the web the page saves it and
+    // updates the block preview.
+    fn inventory_changed(&self) {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                dom_controls::place_block_type_changed(self.inventory.selected());
+                crate::wasm_utils::js_handle_inventory_changed(
+                    &self.inventory.to_json().to_string(),
+                );
+            } else {
+                let path = std::path::Path::new(crate::inventory::INVENTORY_SAVE_PATH);
+                if let Err(err) = crate::inventory::save_inventory(path, &self.inventory) {
+                    log::warn!("Failed to save inventory: {}", err);
+                }
+            }
+        }
+    }
+
+    // Mines the targeted block for one fixed tick of `tick_secs` while `is_mining`, i.e. the break
+    // button is held. The caller breaks the block once it's `MiningProgress::Broken`. Targeted
+    // entities are attacked instead, once each time the button is pressed, on `is_attacking`.
+    pub fn mining_tick(
+        &mut self,
+        camera: &Camera,
+        is_mining: bool,
+        is_attacking: bool,
+        tick_secs: f32,
+    ) -> MiningProgress {
+        let hit = if is_mining {
+            self.get_colliding_block(camera, MAX_BREAK_DISTANCE, true)
+        } else {
+            None
+        };
+        let target = match hit {
+            Some(RaycastHit::Block(collision)) => {
+                let block_pos = collision.block_pos;
+                let block_type = self
+                    .get_block(block_pos.x, block_pos.y, block_pos.z)
+                    .block_type;
+                Some(([block_pos.x, block_pos.y, block_pos.z], block_type))
+            }
+            Some(RaycastHit::Entity { id, .. }) => {
+                if is_attacking {
+                    let eye = glam::Vec3::new(camera.eye.x, camera.eye.y, camera.eye.z);
+                    self.entities.hit(id, eye, self.held_tool().attack_damage());
+                }
+                None
+            }
+            None => None,
+        };
+        let tool = self.held_tool();
+        self.mining.tick(target, tool, tick_secs)
+    }
+
     // Returns which chunks were modified
     pub fn place_block(&mut self, camera: &Camera, block_type: BlockType) -> Vec<[usize; 2]> {
-        let maybe_collision = self.get_colliding_block(camera, MAX_BREAK_DISTANCE + 1);
+        let maybe_collision = self
+            .get_colliding_block(camera, MAX_BREAK_DISTANCE + 1, true)
+            .and_then(RaycastHit::block);
         if let Some(ref collision) = maybe_collision {
             vprintln!(
                 "place_block collision point is {:?}",
@@ -1436,105 +1928,17 @@ impl WorldState {
     }
 
     pub fn physics_tick(&mut self, game_loop: &mut GameLoop, camera: &Camera) {
-        let character_half_extent = 0.5; // Assuming the character is 1 voxel wide
-        let character_height = 2.0; // Assuming the character is 2 voxels tall
-        let character_half_height = character_height / 2.0;
-        let character_collider = Cylinder::new(character_half_height, character_half_extent);
-
-        const FLOOR_CONTACT_TOLERANCE: f32 = 0.001;
-        const WALL_CONTACT_TOLERANCE: f32 = 0.01;
-
-        // Define a helper function to check for collisions in a given direction
-        fn check_collision_in_direction(
-            character_pos: &na::Isometry3<f32>,
-            character_collider: &Cylinder,
-            direction: glam::Vec3,
-            blocks: &Vec<[usize; 3]>,
-            contact_tolerance: f32,
-        ) -> Option<parry3d::query::Contact> {
-            for block_pos in blocks {
-                let block_collider = Cuboid::new(na::vector![0.5, 0.5, 0.5]);
-                let block_pos = na::Isometry3::new(
-                    na::vector![
-                        block_pos[0] as f32 + 0.5,
-                        block_pos[1] as f32 + 0.5,
-                        block_pos[2] as f32 + 0.5
-                    ],
-                    na::zero(),
-                );
-
-                if let Some(contact) = parry3d::query::contact(
-                    character_pos,
-                    character_collider,
-                    &block_pos,
-                    &block_collider,
-                    0.01, // tolerance
-                )
-                .unwrap()
-                {
-                    let contact_normal =
-                        glam::Vec3::new(contact.normal1.x, contact.normal1.y, contact.normal1.z);
-
-                    // Project the normal onto the plane perpendicular to the direction
-                    let normal_on_plane =
-                        contact_normal - direction * contact_normal.dot(direction);
-
-                    // If true, the normal does not have significant components in directions other than `direction`
-                    let is_normal_mostly_parallel_to_direction = normal_on_plane.length() < 0.5;
-
-                    if is_normal_mostly_parallel_to_direction
-                        && contact.dist.abs() > contact_tolerance
-                    {
-                        return Some(contact);
-                    }
-                }
-            }
-            None
-        }
+        let character_collider = Collider::Cylinder {
+            radius: CHARACTER_RADIUS,
+            half_height: CHARACTER_HALF_HEIGHT,
+        };
 
         // First, check if the character entity is touching the floor. This determines if we should apply gravity and whether the character can jump.
-        let curr_character_pos = na::Isometry3::new(
-            na::vector![
-                self.character_entity.position.x,
-                self.character_entity.position.y,
-                self.character_entity.position.z
-            ],
-            na::zero(),
-        );
-
-        // Feet of the character entity, a cynlinder. The middle of the cylinder is at the character's feet.
-        let chracter_feet_pos = (
-            self.character_entity.position.x,
-            self.character_entity.position.y - character_half_height,
-            self.character_entity.position.z,
-        );
-
-        let mut floor_blocks_to_check_collision: Vec<[usize; 3]> = vec![];
-        for (dx, dz) in iproduct!(-1..=1, -1..=1) {
-            let block_pos = [
-                (chracter_feet_pos.0 + (dx as f32)).floor() as usize,
-                (chracter_feet_pos.1).floor() as usize,
-                (chracter_feet_pos.2 + (dz as f32)).floor() as usize,
-            ];
-            if self
-                .get_block(block_pos[0], block_pos[1], block_pos[2])
-                .block_type
-                .is_collidable()
-            {
-                floor_blocks_to_check_collision.push(block_pos);
-            }
-        }
-
-        let mut is_contacting_floor = false;
-        if let Some(_contact) = check_collision_in_direction(
-            &curr_character_pos,
+        let is_contacting_floor = physics::is_on_floor(
+            |block_pos| self.is_block_collidable(block_pos),
             &character_collider,
-            -glam::Vec3::Y,
-            &floor_blocks_to_check_collision,
-            FLOOR_CONTACT_TOLERANCE / 4.0, // lower tolerance
-        ) {
-            is_contacting_floor = true;
-        }
+            self.character_entity.position,
+        );
 
         let gravity_y_accel: f32 = (game_loop.fixed_time_step().powi(2) * -9.807) as f32;
 
@@ -1648,128 +2052,21 @@ impl WorldState {
             },
         );
 
-        let mut potential_new_pos = self.character_entity.position + self.character_entity.velocity;
-
-        // Update character_pos with the potential new position for collision checks
-        let next_character_pos = na::Isometry3::new(
-            na::vector![
-                potential_new_pos.x,
-                potential_new_pos.y,
-                potential_new_pos.z
-            ],
-            na::zero(),
+        let move_result = physics::move_and_collide(
+            |block_pos| self.is_block_collidable(block_pos),
+            &character_collider,
+            self.character_entity.position,
+            self.character_entity.velocity,
         );
-
-        // Collect blocks to check for collision in all directions
-        let mut blocks_to_check_collision: Vec<[usize; 3]> = vec![];
-
-        // Calculate the bounds of the character's current and next position
-        let min_x = (potential_new_pos.x - character_half_extent).floor() as isize;
-        let max_x = (potential_new_pos.x + character_half_extent).ceil() as isize;
-        let min_y = (potential_new_pos.y - character_half_height).floor() as isize; // Adjusted for Y-axis
-        let max_y = (potential_new_pos.y + character_half_height).ceil() as isize; // Adjusted for Y-axis
-        let min_z = (potential_new_pos.z - character_half_extent).floor() as isize;
-        let max_z = (potential_new_pos.z + character_half_extent).ceil() as isize;
-
-        // Iterate over the blocks in the range and collect the ones that are collidable
-        for x in min_x..=max_x {
-            for y in min_y..=max_y {
-                for z in min_z..=max_z {
-                    if x < 0 || y < 0 || z < 0 {
-                        // Skip blocks with negative indices, if your world has no blocks at negative coordinates
-                        continue;
-                    }
-                    let block_pos = [x as usize, y as usize, z as usize];
-                    if self
-                        .get_block(block_pos[0], block_pos[1], block_pos[2])
-                        .block_type
-                        .is_collidable()
-                    {
-                        blocks_to_check_collision.push(block_pos);
-                    }
-                }
+        for (axis, is_blocked) in move_result.blocked.into_iter().enumerate() {
+            if is_blocked {
+                self.character_entity.velocity[axis] = 0.0;
             }
         }
 
-        // Check for X-axis collisions
-        let x_direction = if self.character_entity.velocity.x > 0.0 {
-            glam::Vec3::X
-        } else {
-            -glam::Vec3::X
-        };
-        if let Some(contact) = check_collision_in_direction(
-            &next_character_pos,
-            &character_collider,
-            x_direction,
-            &blocks_to_check_collision,
-            WALL_CONTACT_TOLERANCE,
-        ) {
-            // Resolve X-axis collision
-            self.character_entity.velocity.x = 0.0;
-            let adjust_vec =
-                glam::Vec3::new(contact.normal1.x, contact.normal1.y, contact.normal1.z)
-                    * contact.dist;
-            // let prev_pos = potential_new_pos.clone();
-            potential_new_pos.x += adjust_vec.x;
-            // println!(
-            //     "X-axis collision, prev_pos: {:?}, new_pos: {:?}, adjust_vec: {:?}, contact: {:?}",
-            //     prev_pos, potential_new_pos, adjust_vec, contact
-            // );
-        }
-
-        // Check for Y-axis collisions, special case for gravity
-        let y_direction = if self.character_entity.velocity.y > 0.0 {
-            glam::Vec3::Y
-        } else {
-            -glam::Vec3::Y
-        };
-        if let Some(contact) = check_collision_in_direction(
-            &next_character_pos,
-            &character_collider,
-            y_direction,
-            &blocks_to_check_collision,
-            FLOOR_CONTACT_TOLERANCE,
-        ) {
-            // Resolve Y-axis collision
-            self.character_entity.velocity.y = 0.0;
-            let adjust_vec =
-                glam::Vec3::new(contact.normal1.x, contact.normal1.y, contact.normal1.z)
-                    * contact.dist;
-
-            // HACK: Keep the character slightly colliding with the floor so we can jump / apply gravity on next frame
-            potential_new_pos.y +=
-                adjust_vec.y + (FLOOR_CONTACT_TOLERANCE / 2.0 * -adjust_vec.y.signum());
-        }
-
-        // Check for Z-axis collisions
-        let z_direction = if self.character_entity.velocity.z > 0.0 {
-            glam::Vec3::Z
-        } else {
-            -glam::Vec3::Z
-        };
-        if let Some(contact) = check_collision_in_direction(
-            &next_character_pos,
-            &character_collider,
-            z_direction,
-            &blocks_to_check_collision,
-            WALL_CONTACT_TOLERANCE,
-        ) {
-            // Resolve Z-axis collision
-            self.character_entity.velocity.z = 0.0;
-            let adjust_vec =
-                glam::Vec3::new(contact.normal1.x, contact.normal1.y, contact.normal1.z)
-                    * contact.dist;
-            // let prev_pos = potential_new_pos.clone();
-            potential_new_pos.z += adjust_vec.z;
-            // println!(
-            //     "Z-axis collision, prev_pos: {:?}, new_pos: {:?}, adjust_vec: {:?}, contact: {:?}",
-            //     prev_pos, potential_new_pos, adjust_vec, contact
-            // );
-        }
-
         // Apply the final position and velocity to the character
         self.character_entity.prev_position = self.character_entity.position;
-        self.character_entity.position = potential_new_pos.into();
+        self.character_entity.position = move_result.position;
 
         // Update if character is underwater
         const WATER_CHECK_Y_ADJUST: f32 = 0.5 + (1.0 - WATER_BLOCK_Y_HEIGHT); // +0.5 for eye level, -0.2 for water-level adjust
@@ -1841,21 +2138,46 @@ impl WorldState {
                     }
                 }
 
-                #[cfg(target_arch = "wasm32")]
-                let prev_place_block_type = self.place_block_type;
-
-                match input.virtual_keycode {
-                    Some(VirtualKeyCode::Key1) => self.place_block_type = BlockType::Stone,
-                    Some(VirtualKeyCode::Key2) => self.place_block_type = BlockType::Dirt,
-                    Some(VirtualKeyCode::Key3) => self.place_block_type = BlockType::OakPlank,
-                    Some(VirtualKeyCode::Key4) => self.place_block_type = BlockType::Glass,
-                    Some(VirtualKeyCode::Key5) => self.place_block_type = BlockType::Sand,
-                    _ => (),
+                if let (Some(VirtualKeyCode::C), ElementState::Pressed) =
+                    (input.virtual_keycode, input.state)
+                {
+                    self.craft_with_selected();
                 }
 
-                #[cfg(target_arch = "wasm32")]
-                if prev_place_block_type != self.place_block_type {
-                    dom_controls::place_block_type_changed(&self.place_block_type.to_string());
+                // Number keys pick a hotbar slot
+                const HOTBAR_KEYS: [VirtualKeyCode; 9] = [
+                    VirtualKeyCode::Key1,
+                    VirtualKeyCode::Key2,
+                    VirtualKeyCode::Key3,
+                    VirtualKeyCode::Key4,
+                    VirtualKeyCode::Key5,
+                    VirtualKeyCode::Key6,
+                    VirtualKeyCode::Key7,
+                    VirtualKeyCode::Key8,
+                    VirtualKeyCode::Key9,
+                ];
+                let hotbar_slot = HOTBAR_KEYS
+                    .iter()
+                    .position(|&key| Some(key) == input.virtual_keycode);
+                if let (Some(slot), ElementState::Pressed) = (hotbar_slot, input.state) {
+                    self.select_hotbar_slot(|inventory| inventory.select_hotbar_slot(slot));
+                }
+            }
+            // Scrolling down moves to the next hotbar slot
+            WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => -lines.signum() as i32,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        self.input_state.hotbar_scroll_pixels -= position.y;
+                        let steps = (self.input_state.hotbar_scroll_pixels
+                            / HOTBAR_SCROLL_STEP_PIXELS) as i32;
+                        self.input_state.hotbar_scroll_pixels -=
+                            steps as f64 * HOTBAR_SCROLL_STEP_PIXELS;
+                        steps
+                    }
+                };
+                if steps != 0 {
+                    self.select_hotbar_slot(|inventory| inventory.scroll_hotbar(steps));
                 }
             }
             _ => (),
@@ -1863,13 +2185,6 @@ impl WorldState {
     }
 
     pub fn process_web_dom_button_event(&mut self, event: &DomControlsUserEvent) {
-        const BLOCK_ORDER: [BlockType; 5] = [
-            BlockType::Stone,
-            BlockType::Dirt,
-            BlockType::OakPlank,
-            BlockType::Glass,
-            BlockType::Sand,
-        ];
         match event {
             DomControlsUserEvent::PitchYawJoystickMoved { vector } => {
                 const PITCH_YAW_JOYSTICK_SCALE_FACTOR: f64 = 2.5;
@@ -1901,18 +2216,893 @@ impl WorldState {
                     _ => ButtonState::Idle,
                 }
             }
+            // Steps through the hotbar
             DomControlsUserEvent::BlockPreviewPressed => {
-                let current_block_type_idx = BLOCK_ORDER
-                    .iter()
-                    .position(|&block_type| block_type == self.place_block_type)
-                    .unwrap();
-                let next_block_type_idx = (current_block_type_idx + 1) % BLOCK_ORDER.len();
-                let next_block_type = BLOCK_ORDER[next_block_type_idx];
-                self.place_block_type = next_block_type;
-                #[cfg(target_arch = "wasm32")]
-                dom_controls::place_block_type_changed(&self.place_block_type.to_string());
+                self.select_hotbar_slot(|inventory| inventory.scroll_hotbar(1));
             }
             _ => (),
         }
     }
 }
+
+const SNAPSHOT_XZ_SIZE: usize = CHUNK_XZ_SIZE + 2;
+
+// A chunk plus a one block border from its neighbors, which face culling and ambient occlusion
+// look at. Immutable copy of the world, so it can be meshed off the main thread.
+pub struct ChunkSnapshot {
+    pub chunk_idx: [usize; 2],
+    blocks: Vec3d<Block, vec_extra::XYZ<SNAPSHOT_XZ_SIZE, CHUNK_Y_SIZE, SNAPSHOT_XZ_SIZE>>,
+    highlighted_block: Option<[usize; 3]>,
+    greedy_meshing: bool,
+    camera_eye: Point3<f32>,
+    camera_relative_position: [usize; 2],
+}
+
+impl ChunkSnapshot {
+    // Takes world coordinates, which must be within the chunk or its border
+    fn get_block(&self, [x, y, z]: [isize; 3]) -> &Block {
+        let [chunk_x, chunk_z] = self.chunk_idx;
+        let snapshot_x = x + 1 - (chunk_x * CHUNK_XZ_SIZE) as isize;
+        let snapshot_z = z + 1 - (chunk_z * CHUNK_XZ_SIZE) as isize;
+        self.blocks
+            .get_unchecked(snapshot_x as usize, y as usize, snapshot_z as usize)
+    }
+
+    pub fn compute_mesh(&self) -> ChunkData {
+        use cgmath::{Deg, Quaternion};
+
+        let no_rotation = Face::Bottom.rotation();
+        let flip_to_top = Face::Top.rotation();
+        let flip_to_front = Face::Back.rotation();
+        let flip_to_back = Face::Front.rotation();
+        let flip_to_left = Face::Left.rotation();
+        let flip_to_right = Face::Right.rotation();
+
+        let flip_to_diagonal_right_front: Quaternion<f32> =
+            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(90.0))
+                * Quaternion::from_axis_angle(Vector3::unit_z(), Deg(45.0));
+        let flip_to_diagonal_left_front: Quaternion<f32> =
+            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(-90.0))
+                * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(180.0))
+                * Quaternion::from_axis_angle(Vector3::unit_z(), Deg(-45.0));
+        let flip_to_diagonal_right_back: Quaternion<f32> =
+            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(-90.0))
+                * Quaternion::from_axis_angle(Vector3::unit_y(), Deg(180.0))
+                * Quaternion::from_axis_angle(Vector3::unit_z(), Deg(45.0));
+        let flip_to_diagonal_left_back: Quaternion<f32> =
+            Quaternion::from_axis_angle(Vector3::unit_x(), Deg(90.0))
+                * Quaternion::from_axis_angle(Vector3::unit_z(), Deg(-45.0));
+
+        // Corners of each cube face relative to the block, used for ambient occlusion
+        let top_corners = FaceCorners::new(Vector3::new(0.0, 1.0, 1.0), flip_to_top);
+        let bottom_corners = FaceCorners::new(Vector3::new(0.0, 0.0, 0.0), no_rotation);
+        let left_corners = FaceCorners::new(Vector3::new(1.0, 1.0, 0.0), flip_to_left);
+        let right_corners = FaceCorners::new(Vector3::new(0.0, 1.0, 1.0), flip_to_right);
+        let front_corners = FaceCorners::new(Vector3::new(1.0, 1.0, 1.0), flip_to_back);
+        let back_corners = FaceCorners::new(Vector3::new(0.0, 1.0, 0.0), flip_to_front);
+        let is_ambient_occluder = |[x, y, z]: [isize; 3]| {
+            y >= 0
+                && (y as usize) < CHUNK_Y_SIZE
+                && self.get_block([x, y, z]).block_type.is_ambient_occluder()
+        };
+
+        let mut opaque_instances = Vec::<InstanceRaw>::with_capacity(4096);
+        // Indexed by `Face`
+        let mut face_meshers = Face::ALL.map(|face| {
+            GreedyMesher::new(
+                [CHUNK_XZ_SIZE, CHUNK_Y_SIZE, CHUNK_XZ_SIZE],
+                face.rotation(),
+            )
+        });
+
+        let mut translucent_instances = Vec::<InstanceRaw>::with_capacity(4096);
+        let mut semi_translucent_instances = Vec::<InstanceRaw>::with_capacity(4096);
+
+        let [chunk_x, chunk_z] = self.chunk_idx;
+        let (mut min_visible_y, mut max_visible_y) = (CHUNK_Y_SIZE, 0);
+
+        // Don't use !iproduct here to squeeze out a tiny bit of perf
+        for chunk_rel_z in 0..CHUNK_XZ_SIZE {
+            for chunk_rel_x in 0..CHUNK_XZ_SIZE {
+                for y in 0..CHUNK_Y_SIZE {
+                    let world_x = (chunk_x * CHUNK_XZ_SIZE) + chunk_rel_x;
+                    let world_z = (chunk_z * CHUNK_XZ_SIZE) + chunk_rel_z;
+
+                    let position = cgmath::Vector3::new(world_x as f32, y as f32, world_z as f32);
+                    let block = self
+                        .blocks
+                        .get_unchecked(chunk_rel_x + 1, y, chunk_rel_z + 1);
+                    if block.block_type == BlockType::Empty {
+                        continue;
+                    }
+                    if !block.block_type.is_sprite() && block.neighbors.are_all_faces_hidden() {
+                        continue;
+                    }
+                    min_visible_y = min_visible_y.min(y);
+                    max_visible_y = max_visible_y.max(y);
+
+                    let mut highlight_adjust = 1.0;
+                    if let Some(highlighted_block) = self.highlighted_block {
+                        if highlighted_block == [world_x, y, world_z] {
+                            highlight_adjust = 1.8;
+                        }
+                    }
+
+                    let [top_offset, bottom_offset, side_offset] =
+                        block.block_type.texture_atlas_offsets();
+                    let instance_flags = if block.block_type.is_emissive() {
+                        instance::INSTANCE_FLAG_EMISSIVE
+                    } else {
+                        0
+                    };
+                    let alpha_adjust = if block.block_type == BlockType::Water {
+                        0.7
+                    } else {
+                        1.0
+                    };
+
+                    // Opaque faces go through the greedy meshers instead, see `push_face` below
+                    let instance_vec = if block.block_type.is_semi_translucent() {
+                        &mut semi_translucent_instances
+                    } else {
+                        &mut translucent_instances
+                    };
+
+                    let half_diag_shift = (1.0 - (1.0 / 2.0_f32.sqrt())) / 2.0;
+
+                    let block_pos = [world_x as isize, y as isize, world_z as isize];
+                    let use_ambient_occlusion = block.block_type != BlockType::Water;
+                    let face_ambient_occlusion = |face_corners: &FaceCorners| {
+                        if use_ambient_occlusion {
+                            face_corners.ambient_occlusion(block_pos, is_ambient_occluder)
+                        } else {
+                            NO_AMBIENT_OCCLUSION
+                        }
+                    };
+
+                    if block.block_type.is_sprite() {
+                        // left cross, front-face
+                        instance_vec.push(InstanceRaw::new(
+                            position
+                                + cgmath::Vector3::new(1.0 - half_diag_shift, 1.0, half_diag_shift),
+                            flip_to_diagonal_left_front,
+                            side_offset,
+                            (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust) * highlight_adjust)
+                                .into(),
+                            instance_flags,
+                            NO_AMBIENT_OCCLUSION,
+                        ));
+                        // right cross, front-face
+                        instance_vec.push(InstanceRaw::new(
+                            position + cgmath::Vector3::new(half_diag_shift, 1.0, half_diag_shift),
+                            flip_to_diagonal_right_front,
+                            side_offset,
+                            (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust) * highlight_adjust)
+                                .into(),
+                            instance_flags,
+                            NO_AMBIENT_OCCLUSION,
+                        ));
+                        // left cross, back-face
+                        instance_vec.push(InstanceRaw::new(
+                            position
+                                + cgmath::Vector3::new(half_diag_shift, 1.0, 1.0 - half_diag_shift),
+                            flip_to_diagonal_left_back,
+                            side_offset,
+                            (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust) * highlight_adjust)
+                                .into(),
+                            instance_flags,
+                            NO_AMBIENT_OCCLUSION,
+                        ));
+                        // right cross, back-face
+                        instance_vec.push(InstanceRaw::new(
+                            position
+                                + cgmath::Vector3::new(
+                                    1.0 - half_diag_shift,
+                                    1.0,
+                                    1.0 - half_diag_shift,
+                                ),
+                            flip_to_diagonal_right_back,
+                            side_offset,
+                            (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust) * highlight_adjust)
+                                .into(),
+                            instance_flags,
+                            NO_AMBIENT_OCCLUSION,
+                        ));
+                    } else {
+                        let chunk_rel_pos = [chunk_rel_x, y, chunk_rel_z];
+                        let is_opaque = !block.block_type.is_translucent();
+                        let mut push_face = |face: Face, instance: InstanceRaw| {
+                            if is_opaque {
+                                face_meshers[face as usize].add_face(chunk_rel_pos, instance);
+                            } else {
+                                instance_vec.push(instance);
+                            }
+                        };
+
+                        // Uncovered water is lowered, and its sides stop at the surface
+                        let is_water_surface =
+                            block.block_type == BlockType::Water && !block.neighbors.get(Face::Top);
+                        let side_height = if is_water_surface {
+                            WATER_BLOCK_Y_HEIGHT
+                        } else {
+                            1.0
+                        };
+
+                        if !block.neighbors.get(Face::Top) {
+                            let top_flags = if is_water_surface {
+                                instance_flags | instance::INSTANCE_FLAG_WATER_SURFACE
+                            } else {
+                                instance_flags
+                            };
+                            push_face(
+                                Face::Top,
+                                InstanceRaw::new(
+                                    position + cgmath::Vector3::new(0.0, side_height, 1.0),
+                                    flip_to_top,
+                                    top_offset,
+                                    (cgmath::Vector4::new(1.0, 1.0, 1.0, alpha_adjust)
+                                        * highlight_adjust)
+                                        .into(),
+                                    top_flags,
+                                    face_ambient_occlusion(&top_corners),
+                                ),
+                            );
+                        }
+                        if !block.neighbors.get(Face::Bottom) {
+                            push_face(
+                                Face::Bottom,
+                                InstanceRaw::new(
+                                    position,
+                                    no_rotation,
+                                    bottom_offset,
+                                    (cgmath::Vector4::new(1.0, 1.0, 1.0, alpha_adjust)
+                                        * highlight_adjust)
+                                        .into(),
+                                    instance_flags,
+                                    face_ambient_occlusion(&bottom_corners),
+                                ),
+                            );
+                        }
+                        if !block.neighbors.get(Face::Left) {
+                            push_face(
+                                Face::Left,
+                                InstanceRaw::new(
+                                    position + cgmath::Vector3::new(1.0, side_height, 0.0),
+                                    flip_to_left,
+                                    side_offset,
+                                    (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust)
+                                        * highlight_adjust)
+                                        .into(),
+                                    instance_flags,
+                                    face_ambient_occlusion(&left_corners),
+                                )
+                                .with_scale([1.0, side_height]),
+                            );
+                        }
+                        if !block.neighbors.get(Face::Right) {
+                            push_face(
+                                Face::Right,
+                                InstanceRaw::new(
+                                    position + cgmath::Vector3::new(0.0, side_height, 1.0),
+                                    flip_to_right,
+                                    side_offset,
+                                    (cgmath::Vector4::new(0.7, 0.7, 0.7, alpha_adjust)
+                                        * highlight_adjust)
+                                        .into(),
+                                    instance_flags,
+                                    face_ambient_occlusion(&right_corners),
+                                )
+                                .with_scale([1.0, side_height]),
+                            );
+                        }
+                        if !block.neighbors.get(Face::Front) {
+                            push_face(
+                                Face::Front,
+                                InstanceRaw::new(
+                                    position + cgmath::Vector3::new(1.0, side_height, 1.0),
+                                    flip_to_back,
+                                    side_offset,
+                                    (cgmath::Vector4::new(0.8, 0.8, 0.8, alpha_adjust)
+                                        * highlight_adjust)
+                                        .into(),
+                                    instance_flags,
+                                    face_ambient_occlusion(&front_corners),
+                                )
+                                .with_scale([1.0, side_height]),
+                            );
+                        }
+                        if !block.neighbors.get(Face::Back) {
+                            push_face(
+                                Face::Back,
+                                InstanceRaw::new(
+                                    position + cgmath::Vector3::new(0.0, side_height, 0.0),
+                                    flip_to_front,
+                                    side_offset,
+                                    (cgmath::Vector4::new(0.8, 0.8, 0.8, alpha_adjust)
+                                        * highlight_adjust)
+                                        .into(),
+                                    instance_flags,
+                                    face_ambient_occlusion(&back_corners),
+                                )
+                                .with_scale([1.0, side_height]),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        // Sorted again later on as the camera moves, see `ChunkData::translucent_sorted_from`
+        instance::sort_back_to_front(&mut translucent_instances, self.camera_eye);
+        instance::sort_back_to_front(&mut semi_translucent_instances, self.camera_eye);
+        // Opaque faces don't need sorting, the depth buffer takes care of them
+        for mesher in face_meshers.iter_mut() {
+            if self.greedy_meshing {
+                mesher.mesh(&mut opaque_instances);
+            } else {
+                mesher.mesh_unmerged(&mut opaque_instances);
+            }
+        }
+
+        ChunkData {
+            position: self.chunk_idx,
+            camera_relative_position: self.camera_relative_position,
+            typed_instances_vec: vec![
+                TypedInstances {
+                    data_type: ChunkDataType::Opaque,
+                    instance_data: opaque_instances,
+                },
+                TypedInstances {
+                    data_type: ChunkDataType::Translucent,
+                    instance_data: translucent_instances,
+                },
+                TypedInstances {
+                    data_type: ChunkDataType::SemiTranslucent,
+                    instance_data: semi_translucent_instances,
+                },
+            ],
+            y_bounds: if min_visible_y <= max_visible_y {
+                [min_visible_y as f32, (max_visible_y + 1) as f32]
+            } else {
+                [0.0, 0.0]
+            },
+            connectivity: ChunkConnectivity::compute(|[x, y, z]| {
+                !self
+                    .blocks
+                    .get_unchecked(x + 1, y, z + 1)
+                    .block_type
+                    .is_translucent()
+            }),
+            translucent_sorted_from: self.camera_eye,
+        }
+    }
+}
+
+// Blocks of a freshly generated chunk. Generation doesn't touch the world, so it can run off the
+// main thread; `WorldState::install_generated_chunk` adds the result to the world.
+pub struct GeneratedChunk {
+    pub chunk_idx: [usize; 2],
+    blocks: Vec3d<Block, vec_extra::XYZ<CHUNK_XZ_SIZE, CHUNK_Y_SIZE, CHUNK_XZ_SIZE>>,
+    // Parts of trees that reach into neighboring chunks, in world coordinates
+    spilled_blocks: Vec<([usize; 3], BlockType)>,
+}
+
+impl GeneratedChunk {
+    pub fn generate([chunk_x, chunk_z]: [usize; 2]) -> Self {
+        let mut generated = Self {
+            chunk_idx: [chunk_x, chunk_z],
+            blocks: Vec3d::new(vec![Block::default(); NUM_BLOCKS_IN_CHUNK]),
+            spilled_blocks: vec![],
+        };
+
+        let elevation_map = map_generation::generate_chunk_elevation_map(
+            [chunk_x, chunk_z],
+            MIN_HEIGHT,
+            MAX_HEIGHT,
+        );
+        let (base_x, base_z) = (chunk_x * CHUNK_XZ_SIZE, chunk_z * CHUNK_XZ_SIZE);
+
+        for (z, x) in iproduct!(0..CHUNK_XZ_SIZE, 0..CHUNK_XZ_SIZE) {
+            let ground_elevation = elevation_map[x][z] as usize;
+            let (world_x, world_z) = (base_x + x, base_z + z);
+            let top_block_type = if ground_elevation < WATER_HEIGHT as usize {
+                BlockType::Sand
+            } else {
+                BlockType::Grass
+            };
+            generated.set_block([world_x, ground_elevation, world_z], top_block_type);
+
+            let min_ground_or_water = core::cmp::min(ground_elevation, WATER_HEIGHT as usize);
+            for y in 0..min_ground_or_water {
+                generated.set_block([world_x, y, world_z], BlockType::Sand);
+            }
+            for y in min_ground_or_water..ground_elevation {
+                generated.set_block([world_x, y, world_z], BlockType::Dirt);
+            }
+            for y in (MIN_HEIGHT as usize)..(WATER_HEIGHT as usize) {
+                if generated.is_empty([world_x, y, world_z]) {
+                    generated.set_block([world_x, y, world_z], BlockType::Water);
+                }
+            }
+
+            if top_block_type == BlockType::Grass {
+                let did_generate_tree =
+                    generated.maybe_generate_tree([world_x, ground_elevation, world_z]);
+                if !did_generate_tree {
+                    generated.maybe_generate_flower([world_x, ground_elevation, world_z]);
+                }
+            }
+        }
+
+        generated.compute_neighbors();
+        generated
+    }
+
+    fn chunk_rel_position(&self, [x, y, z]: [usize; 3]) -> Option<[usize; 3]> {
+        let [chunk_x, chunk_z] = self.chunk_idx;
+        if x / CHUNK_XZ_SIZE == chunk_x && z / CHUNK_XZ_SIZE == chunk_z {
+            Some([x % CHUNK_XZ_SIZE, y, z % CHUNK_XZ_SIZE])
+        } else {
+            None
+        }
+    }
+
+    fn set_block(&mut self, world_pos: [usize; 3], block_type: BlockType) {
+        match self.chunk_rel_position(world_pos) {
+            Some(chunk_rel_pos) => self.blocks[chunk_rel_pos].block_type = block_type,
+            None => self.spilled_blocks.push((world_pos, block_type)),
+        }
+    }
+
+    // Blocks outside of the chunk are only checked once they're added to the world
+    fn is_empty(&self, world_pos: [usize; 3]) -> bool {
+        self.chunk_rel_position(world_pos)
+            .is_none_or(|chunk_rel_pos| self.blocks[chunk_rel_pos].is_empty())
+    }
+
+    fn maybe_generate_tree(&mut self, base_location: [usize; 3]) -> bool {
+        const TREE_CHANCE: f32 = 1.0 / 200.0;
+        if rand::thread_rng().gen::<f32>() > TREE_CHANCE {
+            return false;
+        }
+
+        let mut trunk_base = base_location;
+        trunk_base[1] += 1;
+
+        let [x, y_base, z] = trunk_base;
+        let trunk_top = y_base + 6;
+        for y in y_base..trunk_top {
+            self.set_block([x, y, z], BlockType::Tree);
+        }
+
+        // Minecraft Lollipop Spruce Tree
+        let leaf_slice_diameters = [7, 7, 5, 3];
+        let mut leaf_y = trunk_top - 3;
+        for diam in leaf_slice_diameters {
+            let radius = (diam - 1) / 2;
+            for (leaf_x, leaf_z) in
+                iproduct!(x - radius + 1..x + radius, z - radius + 1..z + radius)
+            {
+                // TODO(aleks): need a "set block if empty" primitive
+                if self.is_empty([leaf_x, leaf_y, leaf_z]) {
+                    self.set_block([leaf_x, leaf_y, leaf_z], BlockType::random_tree_leaf());
+                }
+            }
+            leaf_y += 1;
+        }
+        true
+    }
+
+    fn maybe_generate_flower(&mut self, ground_elevation: [usize; 3]) -> bool {
+        const FLOWER_CHANCE: f32 = 1.0 / 100.0;
+        if rand::thread_rng().gen::<f32>() > FLOWER_CHANCE {
+            return false;
+        }
+
+        let [x, y_base, z] = ground_elevation;
+        self.set_block([x, y_base + 1, z], BlockType::RedFlower);
+        true
+    }
+
+    // Faces on the borders of the chunk depend on the neighboring chunks, see
+    // `WorldState::stitch_chunk_borders`
+    fn compute_neighbors(&mut self) {
+        let last = CHUNK_XZ_SIZE - 1;
+        for (z, y, x) in iproduct!(0..CHUNK_XZ_SIZE, 0..CHUNK_Y_SIZE, 0..CHUNK_XZ_SIZE) {
+            let block_type = self.blocks[[x, y, z]].block_type;
+            let neighbors = [
+                (y < CHUNK_Y_SIZE - 1, [x, y + 1, z], Face::Top),
+                (y > 0, [x, y.wrapping_sub(1), z], Face::Bottom),
+                (x < last, [x + 1, y, z], Face::Left),
+                (x > 0, [x.wrapping_sub(1), y, z], Face::Right),
+                (z < last, [x, y, z + 1], Face::Front),
+                (z > 0, [x, y, z.wrapping_sub(1)], Face::Back),
+            ];
+            for (in_chunk, neighbor_pos, face) in neighbors {
+                if in_chunk {
+                    let neighbor_type = self.blocks[neighbor_pos].block_type;
+                    self.blocks[[x, y, z]]
+                        .neighbors
+                        .set(face, is_face_hidden(block_type, neighbor_type));
+                }
+            }
+        }
+    }
+}
+// Encoding for sending chunk jobs and their results to and from Web Workers, see `jobs::ChunkJob`
+
+impl BlockType {
+    fn from_u8(value: u8) -> Option<Self> {
+        BlockType::ALL.get(value as usize).copied()
+    }
+}
+
+impl Block {
+    fn encode(&self, writer: &mut ByteWriter) {
+        writer.write_u8(self.block_type as u8);
+        writer.write_u8(self.neighbors.bitmap.into_value());
+    }
+
+    fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(Block {
+            block_type: BlockType::from_u8(reader.read_u8()?)?,
+            neighbors: NeighborBitmap {
+                bitmap: Bitmap::from_value(reader.read_u8()?),
+            },
+        })
+    }
+}
+
+fn encode_blocks<DO: DimOrder>(blocks: &Vec3d<Block, DO>, writer: &mut ByteWriter) {
+    for block in blocks.as_slice() {
+        block.encode(writer);
+    }
+}
+
+fn decode_blocks<DO: DimOrder>(reader: &mut ByteReader) -> Option<Vec3d<Block, DO>> {
+    let [x_size, y_size, z_size] = *DO::dims();
+    let blocks = (0..x_size * y_size * z_size)
+        .map(|_| Block::decode(reader))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Vec3d::new(blocks))
+}
+
+fn encode_point(point: Point3<f32>, writer: &mut ByteWriter) {
+    writer.write_f32s([point.x, point.y, point.z]);
+}
+
+fn decode_point(reader: &mut ByteReader) -> Option<Point3<f32>> {
+    let [x, y, z] = reader.read_f32s()?;
+    Some(Point3::new(x, y, z))
+}
+
+impl ChunkDataType {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ChunkDataType::Opaque),
+            1 => Some(ChunkDataType::Translucent),
+            2 => Some(ChunkDataType::SemiTranslucent),
+            3 => Some(ChunkDataType::TranslucentAndSemiTranslucent),
+            _ => None,
+        }
+    }
+}
+
+impl TypedInstances {
+    pub fn encode(&self, writer: &mut ByteWriter) {
+        writer.write_u8(self.data_type as u8);
+        writer.write_bytes(bytemuck::cast_slice(&self.instance_data));
+    }
+
+    pub fn decode(reader: &mut ByteReader) -> Option<Self> {
+        let data_type = ChunkDataType::from_u8(reader.read_u8()?)?;
+        let bytes = reader.read_bytes()?;
+        let instance_size = std::mem::size_of::<InstanceRaw>();
+        if bytes.len() % instance_size != 0 {
+            return None;
+        }
+        Some(TypedInstances {
+            data_type,
+            instance_data: bytes
+                .chunks_exact(instance_size)
+                .map(bytemuck::pod_read_unaligned)
+                .collect(),
+        })
+    }
+}
+
+pub fn encode_typed_instances_vec(typed_instances_vec: &[TypedInstances], writer: &mut ByteWriter) {
+    writer.write_usize(typed_instances_vec.len());
+    for typed_instances in typed_instances_vec {
+        typed_instances.encode(writer);
+    }
+}
+
+pub fn decode_typed_instances_vec(reader: &mut ByteReader) -> Option<Vec<TypedInstances>> {
+    let len = reader.read_usize()?;
+    (0..len).map(|_| TypedInstances::decode(reader)).collect()
+}
+
+impl ChunkData {
+    pub fn encode(&self, writer: &mut ByteWriter) {
+        writer.write_usizes(self.position);
+        writer.write_usizes(self.camera_relative_position);
+        encode_typed_instances_vec(&self.typed_instances_vec, writer);
+        writer.write_f32s(self.y_bounds);
+        self.connectivity.encode(writer);
+        encode_point(self.translucent_sorted_from, writer);
+    }
+
+    pub fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(ChunkData {
+            position: reader.read_usizes()?,
+            camera_relative_position: reader.read_usizes()?,
+            typed_instances_vec: decode_typed_instances_vec(reader)?,
+            y_bounds: reader.read_f32s()?,
+            connectivity: ChunkConnectivity::decode(reader)?,
+            translucent_sorted_from: decode_point(reader)?,
+        })
+    }
+}
+
+impl ChunkSnapshot {
+    pub fn encode(&self, writer: &mut ByteWriter) {
+        writer.write_usizes(self.chunk_idx);
+        encode_blocks(&self.blocks, writer);
+        writer.write_bool(self.highlighted_block.is_some());
+        if let Some(highlighted_block) = self.highlighted_block {
+            writer.write_usizes(highlighted_block);
+        }
+        writer.write_bool(self.greedy_meshing);
+        encode_point(self.camera_eye, writer);
+        writer.write_usizes(self.camera_relative_position);
+    }
+
+    pub fn decode(reader: &mut ByteReader) -> Option<Self> {
+        Some(ChunkSnapshot {
+            chunk_idx: reader.read_usizes()?,
+            blocks: decode_blocks(reader)?,
+            highlighted_block: if reader.read_bool()? {
+                Some(reader.read_usizes()?)
+            } else {
+                None
+            },
+            greedy_meshing: reader.read_bool()?,
+            camera_eye: decode_point(reader)?,
+            camera_relative_position: reader.read_usizes()?,
+        })
+    }
+}
+
+impl GeneratedChunk {
+    pub fn encode(&self, writer: &mut ByteWriter) {
+        writer.write_usizes(self.chunk_idx);
+        encode_blocks(&self.blocks, writer);
+        writer.write_usize(self.spilled_blocks.len());
+        for &(position, block_type) in self.spilled_blocks.iter() {
+            writer.write_usizes(position);
+            writer.write_u8(block_type as u8);
+        }
+    }
+
+    pub fn decode(reader: &mut ByteReader) -> Option<Self> {
+        let chunk_idx = reader.read_usizes()?;
+        let blocks = decode_blocks(reader)?;
+        let num_spilled_blocks = reader.read_usize()?;
+        let spilled_blocks = (0..num_spilled_blocks)
+            .map(|_| {
+                Some((
+                    reader.read_usizes()?,
+                    BlockType::from_u8(reader.read_u8()?)?,
+                ))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(GeneratedChunk {
+            chunk_idx,
+            blocks,
+            spilled_blocks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // A chunk holding a single layer of `block_type` at y = 0, which carries on into the
+    // neighboring chunks
+    fn layer_snapshot(block_type: BlockType) -> ChunkSnapshot {
+        let mut blocks = Vec3d::new(vec![
+            Block::default();
+            SNAPSHOT_XZ_SIZE * CHUNK_Y_SIZE * SNAPSHOT_XZ_SIZE
+        ]);
+        for (z, x) in iproduct!(0..SNAPSHOT_XZ_SIZE, 0..SNAPSHOT_XZ_SIZE) {
+            let mut block = Block {
+                block_type,
+                neighbors: NeighborBitmap::new(),
+            };
+            for face in Face::ALL {
+                let is_hidden = match face {
+                    Face::Top => is_face_hidden(block_type, BlockType::Empty),
+                    Face::Bottom => true,
+                    _ => is_face_hidden(block_type, block_type),
+                };
+                block.neighbors.set(face, is_hidden);
+            }
+            blocks[[x, 0, z]] = block;
+        }
+        ChunkSnapshot {
+            chunk_idx: [1, 1],
+            blocks,
+            highlighted_block: None,
+            greedy_meshing: true,
+            camera_eye: Point3::new(24.0, 10.0, 24.0),
+            camera_relative_position: [0, 0],
+        }
+    }
+
+    fn instance_count(chunk_data: &ChunkData, data_type: ChunkDataType) -> usize {
+        chunk_data
+            .typed_instances_vec
+            .iter()
+            .filter(|typed_instances| typed_instances.data_type == data_type)
+            .map(|typed_instances| typed_instances.instance_data.len())
+            .sum()
+    }
+
+    #[test]
+    fn opaque_layer_meshes_into_one_face() {
+        let chunk_data = layer_snapshot(BlockType::Stone).compute_mesh();
+        assert_eq!(instance_count(&chunk_data, ChunkDataType::Opaque), 1);
+    }
+
+    #[test]
+    fn translucent_faces_stay_unmerged() {
+        let chunk_data = layer_snapshot(BlockType::Water).compute_mesh();
+        assert_eq!(instance_count(&chunk_data, ChunkDataType::Opaque), 0);
+        assert_eq!(
+            instance_count(&chunk_data, ChunkDataType::Translucent),
+            CHUNK_XZ_SIZE * CHUNK_XZ_SIZE
+        );
+    }
+
+    fn top_corners() -> FaceCorners {
+        FaceCorners::new(Face::Top.instance_offset(), Face::Top.rotation())
+    }
+
+    #[test]
+    fn occlusion_level_counts_occluded_neighbors() {
+        assert_eq!(vertex_ambient_occlusion_level(false, false, false), 3);
+        assert_eq!(vertex_ambient_occlusion_level(false, false, true), 2);
+        assert_eq!(vertex_ambient_occlusion_level(true, false, false), 2);
+        assert_eq!(vertex_ambient_occlusion_level(false, true, false), 2);
+        assert_eq!(vertex_ambient_occlusion_level(true, false, true), 1);
+        assert_eq!(vertex_ambient_occlusion_level(false, true, true), 1);
+    }
+
+    #[test]
+    fn occlusion_level_is_darkest_between_two_sides() {
+        // The corner block doesn't matter when both sides are occluded
+        assert_eq!(vertex_ambient_occlusion_level(true, true, false), 0);
+        assert_eq!(vertex_ambient_occlusion_level(true, true, true), 0);
+    }
+
+    // Occluders around each corner of the top face of the block at the origin, on the layer above
+    // it, give the corner each of the four levels
+    #[test]
+    fn top_face_corners_get_every_level() {
+        let face_corners = top_corners();
+        for (corner_idx, &[corner_x, _, corner_z]) in face_corners.corners.iter().enumerate() {
+            // Blocks above the origin's neighbors towards this corner
+            let dx = corner_x * 2 - 1;
+            let dz = corner_z * 2 - 1;
+            let side1 = [dx, 1, 0];
+            let side2 = [0, 1, dz];
+            let diagonal = [dx, 1, dz];
+
+            let cases: [(&[[isize; 3]], u8); 5] = [
+                (&[], 3),
+                (&[diagonal], 2),
+                (&[side1, diagonal], 1),
+                (&[side1, side2], 0),
+                (&[side1, side2, diagonal], 0),
+            ];
+            for (occluders, level) in cases {
+                let occluders = occluders.iter().copied().collect::<HashSet<_>>();
+                let brightness = face_corners
+                    .ambient_occlusion([0, 0, 0], |block_pos| occluders.contains(&block_pos));
+                assert_eq!(
+                    brightness[corner_idx],
+                    ambient_occlusion_brightness(level),
+                    "corner {} with occluders {:?}",
+                    corner_idx,
+                    occluders
+                );
+                // The opposite corner shares none of the blocks. The others share a side.
+                assert_eq!(brightness[3 - corner_idx], 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn occluders_behind_the_face_dont_darken_it() {
+        let brightness = top_corners().ambient_occlusion([0, 0, 0], |[_, y, _]| y <= 0);
+        assert_eq!(brightness, NO_AMBIENT_OCCLUSION);
+    }
+
+    #[test]
+    fn face_corners_follow_texture_coordinates() {
+        let expected = [
+            (Face::Top, [[1, 1, 1], [0, 1, 1], [1, 1, 0], [0, 1, 0]]),
+            (Face::Bottom, [[1, 0, 0], [0, 0, 0], [1, 0, 1], [0, 0, 1]]),
+            (Face::Left, [[1, 1, 1], [1, 1, 0], [1, 0, 1], [1, 0, 0]]),
+            (Face::Right, [[0, 1, 0], [0, 1, 1], [0, 0, 0], [0, 0, 1]]),
+            (Face::Front, [[0, 1, 1], [1, 1, 1], [0, 0, 1], [1, 0, 1]]),
+            (Face::Back, [[1, 1, 0], [0, 1, 0], [1, 0, 0], [0, 0, 0]]),
+        ];
+        for (face, corners) in expected {
+            let face_corners = FaceCorners::new(face.instance_offset(), face.rotation());
+            assert_eq!(face_corners.normal, face.offset(), "{:?}", face);
+            assert_eq!(face_corners.corners, corners, "{:?}", face);
+        }
+    }
+
+    #[test]
+    fn face_corners_lie_on_the_face() {
+        for face in Face::ALL {
+            let face_corners = FaceCorners::new(face.instance_offset(), face.rotation());
+            let normal_axis = face.offset().iter().position(|&n| n != 0).unwrap();
+            let plane = face.offset()[normal_axis].max(0);
+            assert!(face_corners
+                .corners
+                .iter()
+                .all(|corner| corner[normal_axis] == plane));
+
+            // (0, 0) and (1, 1) are opposite corners, and the u and v edges run along different
+            // axes
+            let [c00, c10, c01, c11] = face_corners.corners;
+            let differing_axes = |a: [isize; 3], b: [isize; 3]| {
+                (0..3)
+                    .filter(|&axis| a[axis] != b[axis])
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(differing_axes(c00, c11).len(), 2, "{:?}", face);
+            assert_eq!(differing_axes(c10, c01).len(), 2, "{:?}", face);
+            let u_axis = differing_axes(c00, c10);
+            let v_axis = differing_axes(c00, c01);
+            assert_eq!(u_axis.len(), 1, "{:?}", face);
+            assert_eq!(v_axis.len(), 1, "{:?}", face);
+            assert_ne!(u_axis, v_axis, "{:?}", face);
+        }
+    }
+
+    fn encoded_mesh(snapshot: &ChunkSnapshot) -> Vec<u8> {
+        let mut writer = ByteWriter::new();
+        snapshot.compute_mesh().encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    #[test]
+    fn snapshot_meshes_the_same_after_encoding() {
+        let mut snapshot = layer_snapshot(BlockType::Water);
+        snapshot.highlighted_block = Some([20, 0, 20]);
+        let mut writer = ByteWriter::new();
+        snapshot.encode(&mut writer);
+        let bytes = writer.into_bytes();
+        let mut reader = ByteReader::new(&bytes);
+        let decoded = ChunkSnapshot::decode(&mut reader).unwrap();
+
+        assert!(reader.is_empty());
+        assert_eq!(decoded.highlighted_block, Some([20, 0, 20]));
+        assert_eq!(encoded_mesh(&decoded), encoded_mesh(&snapshot));
+    }
+
+    #[test]
+    fn block_types_decode_from_their_discriminant() {
+        for block_type in BlockType::ALL {
+            assert_eq!(BlockType::from_u8(block_type as u8), Some(block_type));
+        }
+        assert_eq!(BlockType::from_u8(BlockType::ALL.len() as u8), None);
+    }
+}
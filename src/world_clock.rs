@@ -0,0 +1,236 @@
+use crate::color;
+use glam::Vec3;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+// Real time seconds for a full day and night
+pub const DAY_LENGTH_SECS: f64 = 600.0;
+// Mid-morning
+pub const DEFAULT_TIME_OF_DAY: f32 = 0.35;
+// Native builds save the time of day in this file, relative to the working directory. The web
+// build saves it in the page's localStorage instead, see ts/index.ts.
+#[cfg(not(target_arch = "wasm32"))]
+pub const TIME_OF_DAY_SAVE_PATH: &str = "time_of_day.txt";
+// Keeps the sun from passing straight overhead, so shadows always have a direction
+const SUN_TILT: f32 = 0.35;
+
+const DAY_AMBIENT_STRENGTH: f32 = 0.3;
+const NIGHT_AMBIENT_STRENGTH: f32 = 0.08;
+const SUNSET_COLOR: [f32; 3] = [1.0, 0.6, 0.35];
+const MOONLIGHT_COLOR: [f32; 3] = [0.25, 0.3, 0.45];
+
+// Sky colors at the horizon by the sun's height, in sRGB. Heights go from the sun's direction's y
+// component, -1 at midnight to 1 at noon.
+const SKY_GRADIENT: [(f32, [f32; 3]); 4] = [
+    (-0.25, [10.0, 14.0, 35.0]),
+    (0.0, [250.0, 150.0, 90.0]),
+    (0.15, [170.0, 180.0, 230.0]),
+    (0.35, [120.0, 167.0, 255.0]),
+];
+// Same for the top of the sky
+const ZENITH_GRADIENT: [(f32, [f32; 3]); 3] = [
+    (-0.25, [2.0, 3.0, 12.0]),
+    (0.0, [60.0, 70.0, 130.0]),
+    (0.35, [60.0, 110.0, 225.0]),
+];
+
+// Named times that the time of day can be set to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeOfDay {
+    Dawn,
+    Noon,
+    Dusk,
+    Midnight,
+}
+
+impl TimeOfDay {
+    pub const ALL: [TimeOfDay; 4] = [
+        TimeOfDay::Dawn,
+        TimeOfDay::Noon,
+        TimeOfDay::Dusk,
+        TimeOfDay::Midnight,
+    ];
+
+    // Fraction of the day, see `WorldClock::time_of_day`
+    pub fn time_of_day(self) -> f32 {
+        match self {
+            TimeOfDay::Midnight => 0.0,
+            TimeOfDay::Dawn => 0.25,
+            TimeOfDay::Noon => 0.5,
+            TimeOfDay::Dusk => 0.75,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "dawn" => Some(TimeOfDay::Dawn),
+            "noon" => Some(TimeOfDay::Noon),
+            "dusk" => Some(TimeOfDay::Dusk),
+            "midnight" => Some(TimeOfDay::Midnight),
+            _ => None,
+        }
+    }
+
+    // The first of these times coming up after `time_of_day`
+    pub fn next_after(time_of_day: f32) -> Self {
+        TimeOfDay::ALL
+            .into_iter()
+            .filter(|preset| preset.time_of_day() > time_of_day)
+            .min_by(|a, b| a.time_of_day().total_cmp(&b.time_of_day()))
+            .unwrap_or(TimeOfDay::Midnight)
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+// Linear RGB at `sun_height` along a gradient of sRGB colors
+fn sample_sky_gradient(gradient: &[(f32, [f32; 3])], sun_height: f32) -> [f64; 3] {
+    let (first_height, first_color) = gradient[0];
+    let mut srgb = first_color;
+    if sun_height > first_height {
+        srgb = gradient[gradient.len() - 1].1;
+        for window in gradient.windows(2) {
+            let [(height_a, color_a), (height_b, color_b)] = [window[0], window[1]];
+            if sun_height <= height_b {
+                srgb = mix(
+                    color_a,
+                    color_b,
+                    (sun_height - height_a) / (height_b - height_a),
+                );
+                break;
+            }
+        }
+    }
+    srgb.map(|c| color::srgb_to_rgb(c as f64 / 255.0))
+}
+
+pub struct WorldClock {
+    // Fraction of the day in [0, 1). 0 is midnight, 0.5 is noon.
+    time_of_day: f32,
+}
+
+impl WorldClock {
+    pub fn new(time_of_day: f32) -> Self {
+        let mut world_clock = Self { time_of_day: 0.0 };
+        world_clock.set_time_of_day(time_of_day);
+        world_clock
+    }
+
+    pub fn time_of_day(&self) -> f32 {
+        self.time_of_day
+    }
+
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.time_of_day = time_of_day.rem_euclid(1.0);
+    }
+
+    // Returns whether a new hour of the day started, so the time can be saved every now and then
+    pub fn advance(&mut self, dt_secs: f64) -> bool {
+        let hour_before = self.hour();
+        self.set_time_of_day(self.time_of_day + (dt_secs / DAY_LENGTH_SECS) as f32);
+        self.hour() != hour_before
+    }
+
+    fn hour(&self) -> u32 {
+        (self.time_of_day * 24.0) as u32
+    }
+
+    // Points towards the sun. Rises in +x at dawn and sets in -x at dusk.
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.time_of_day - 0.25) * std::f32::consts::TAU;
+        Vec3::new(angle.cos(), angle.sin(), SUN_TILT).normalize()
+    }
+
+    // Points towards whichever of the sun and moon is up. The moon is opposite the sun.
+    pub fn sky_light_direction(&self) -> Vec3 {
+        let sun_direction = self.sun_direction();
+        if sun_direction.y >= 0.0 {
+            sun_direction
+        } else {
+            -sun_direction
+        }
+    }
+
+    // Fades out towards the horizon, so switching between the sun and moon isn't noticeable
+    pub fn sky_light_color(&self) -> Vec3 {
+        let sun_height = self.sun_direction().y;
+        if sun_height >= 0.0 {
+            let color = mix(
+                SUNSET_COLOR,
+                [1.0, 1.0, 1.0],
+                smoothstep(0.0, 0.4, sun_height),
+            );
+            Vec3::from(color) * smoothstep(0.0, 0.25, sun_height)
+        } else {
+            Vec3::from(MOONLIGHT_COLOR) * smoothstep(0.0, 0.25, -sun_height)
+        }
+    }
+
+    // 0 at night to 1 during the day, easing in and out around sunrise and sunset
+    pub fn daylight(&self) -> f32 {
+        smoothstep(-0.2, 0.2, self.sun_direction().y)
+    }
+
+    pub fn ambient_strength(&self) -> f32 {
+        NIGHT_AMBIENT_STRENGTH + (DAY_AMBIENT_STRENGTH - NIGHT_AMBIENT_STRENGTH) * self.daylight()
+    }
+
+    // Linear RGB at the horizon, also used to clear the frame
+    pub fn sky_color(&self) -> [f64; 3] {
+        sample_sky_gradient(&SKY_GRADIENT, self.sun_direction().y)
+    }
+
+    // Linear RGB straight up
+    pub fn zenith_color(&self) -> [f64; 3] {
+        sample_sky_gradient(&ZENITH_GRADIENT, self.sun_direction().y)
+    }
+
+    // Stars come out as the sun goes down
+    pub fn star_visibility(&self) -> f32 {
+        1.0 - smoothstep(-0.25, 0.0, self.sun_direction().y)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_time_of_day(path: &Path, time_of_day: f32) -> std::io::Result<()> {
+    std::fs::write(path, time_of_day.to_string())
+}
+
+// `None` if nothing was saved yet
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_time_of_day(path: &Path) -> Option<f32> {
+    let time_of_day = std::fs::read_to_string(path).ok()?;
+    match time_of_day.trim().parse() {
+        Ok(time_of_day) => Some(time_of_day),
+        Err(_) => {
+            log::warn!("Failed to load time of day: {}", time_of_day);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_time_of_day_loads_back() {
+        let path = std::env::temp_dir().join(format!("time_of_day_{}.txt", std::process::id()));
+        assert_eq!(load_time_of_day(&path), None);
+
+        save_time_of_day(&path, 0.6875).unwrap();
+        assert_eq!(load_time_of_day(&path), Some(0.6875));
+
+        std::fs::write(&path, "dusk").unwrap();
+        assert_eq!(load_time_of_day(&path), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
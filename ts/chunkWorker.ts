@@ -0,0 +1,20 @@
+// Runs chunk jobs for `JobPool` in jobs.rs. Jobs and results are passed as ArrayBuffers, see
+// `run_encoded_chunk_job`. An empty one tells the pool the worker has loaded, and an error message
+// that it couldn't run the job.
+const workerScope = self as any;
+
+import("../pkg/index").then((wasmModule) => {
+  workerScope.onmessage = (event: MessageEvent) => {
+    const result: Uint8Array | undefined = wasmModule.run_encoded_chunk_job(
+      new Uint8Array(event.data)
+    );
+    if (result === undefined) {
+      workerScope.postMessage("Malformed chunk job");
+    } else {
+      workerScope.postMessage(result.buffer, [result.buffer]);
+    }
+  };
+  workerScope.postMessage(new ArrayBuffer(0));
+});
+
+export {};
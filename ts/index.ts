@@ -33,8 +33,6 @@ document.addEventListener("contextmenu", (event: any) => {
   event.preventDefault();
 });
 
-let atlasImage: HTMLImageElement | null = null;
-
 document.addEventListener("DOMContentLoaded", async () => {
   (window as any).feather?.replace();
 
@@ -54,28 +52,39 @@ document.addEventListener("DOMContentLoaded", async () => {
       (joystickDiv as any).style.visibility = "hidden";
     }
   }
-
-  atlasImage = await loadImage('./minecruft_atlas.png');
 });
 
-// Called from Rust code when the user chooses a different block to place
-(window as any).handlePlaceBlockTypeChanged = (blockTypeStr: string) => {
-  if (!atlasImage) return;
+// Called from Rust code when the selected hotbar slot or its count changes. `blockTypeStr` is
+// "Empty" when the slot is empty, items that aren't blocks like tools show no preview.
+(window as any).handlePlaceBlockTypeChanged = async (blockTypeStr: string, count: number) => {
   // console.log("Block type changed to: " + blockTypeStr);
 
-  let atlasIdxByBlockType: { [key: string]: [number, number] } = {
-    "Dirt": [2, 0],
-    "Stone": [2, 3],
-    "Sand": [0, 1],
-    "OakPlank": [2, 4],
-    "Glass": [2, 1],
+  // Same textures as `BlockType::texture_tiles`, see assets/blocks
+  let tileNameByBlockType: { [key: string]: string } = {
+    "Dirt": "dirt",
+    "Grass": "grass_block_side",
+    "Stone": "stone",
+    "Sand": "sand",
+    "OakPlank": "oak_planks",
+    "Glass": "glass",
+    "Tree": "oak_log",
+    "TreeLeaves1": "oak_leaves_1",
+    "TreeLeaves2": "oak_leaves_2",
+    "TreeLeaves3": "oak_leaves_3",
+    "TreeLeaves4": "oak_leaves_2",
+    "RedFlower": "red_flower",
+    "Torch": "torch",
+    "Glowstone": "glowstone",
+    "Lantern": "lantern",
   };
-  if (blockTypeStr in atlasIdxByBlockType) {
-    let blockTypeIdx = atlasIdxByBlockType[blockTypeStr];
-    let blockPreviewCanvas = cropImage(atlasImage, blockTypeIdx[0] * 16, blockTypeIdx[1] * 16, 16, 16);
-    blockPreviewCanvas.id = "block-preview-canvas";
-    document.getElementById("block-preview-canvas").replaceWith(blockPreviewCanvas);
+  let blockPreviewCanvas = document.createElement("canvas");
+  if (blockTypeStr in tileNameByBlockType) {
+    let tileImage = await loadImage(`./blocks/${tileNameByBlockType[blockTypeStr]}.png`);
+    blockPreviewCanvas = cropImage(tileImage, 0, 0, 16, 16);
   }
+  blockPreviewCanvas.id = "block-preview-canvas";
+  document.getElementById("block-preview-canvas").replaceWith(blockPreviewCanvas);
+  document.getElementById("block-preview-count").textContent = count > 1 ? count.toString() : "";
 }
 
 (window as any).updateGameStateLoadProgress = (loadProgress: number) => {
@@ -86,6 +95,41 @@ document.addEventListener("DOMContentLoaded", async () => {
   console.log("Load progress", loadProgress * 100);
 }
 
+// Called from Rust code whenever blocks are collected, placed or the hotbar selection changes
+const INVENTORY_STORAGE_KEY = "minecrust.inventory";
+(window as any).handleInventoryChanged = (inventoryJson: string) => {
+  localStorage.setItem(INVENTORY_STORAGE_KEY, inventoryJson);
+}
+
+// Called from Rust code whenever the character's health changes, shown as hearts of 2 points
+(window as any).handleHealthChanged = (points: number, maxPoints: number) => {
+  const hearts = [];
+  for (let heartPoints = 2; heartPoints <= maxPoints; heartPoints += 2) {
+    if (points >= heartPoints) {
+      hearts.push("♥");
+    } else if (points >= heartPoints - 1) {
+      hearts.push("❥");
+    } else {
+      hearts.push("♡");
+    }
+  }
+  document.getElementById("health").textContent = hearts.join("");
+}
+
+// Called from Rust code every in-game hour and whenever the time of day is set
+const TIME_OF_DAY_STORAGE_KEY = "minecrust.timeOfDay";
+(window as any).handleTimeOfDayChanged = (timeOfDay: number) => {
+  localStorage.setItem(TIME_OF_DAY_STORAGE_KEY, timeOfDay.toString());
+}
+
+// Called from Rust code whenever the render distance changes, including with the [ ] keys, so the
+// slider stays in sync
+(window as any).handleRenderDistanceChanged = (visibleChunkWidth: number) => {
+  (document.getElementById("render-distance") as HTMLInputElement).value =
+    visibleChunkWidth.toString();
+  document.getElementById("render-distance-value").textContent = visibleChunkWidth.toString();
+}
+
 (window as any).handleGameReady = (_: any) => {
   const loadProgressBar = document.getElementById("load-progress") as HTMLProgressElement;
   loadProgressBar.remove();
@@ -235,12 +279,26 @@ function mountJoysticks(wasmModule: any) {
   }, JOYSTICK_MOUNT_DELAY);
 }
 
+// Called from Rust code to start the Web Workers chunks are generated and meshed on
+(window as any).createChunkWorker = () =>
+  new Worker(new URL("./chunkWorker.ts", import.meta.url));
+
 import("../pkg/index").then((wasmModule) => {
   console.log("WASM Loaded");
 
   registerDomButtonEventListeners(wasmModule);
   mountJoysticks(wasmModule);
 
+  // Render distance in chunks, there are no keys to change it on mobile. Bounds match
+  // `MIN_VISIBLE_CHUNK_WIDTH` and `MAX_VISIBLE_CHUNK_WIDTH` in world.rs.
+  const renderDistanceSlider = document.getElementById("render-distance") as HTMLInputElement;
+  renderDistanceSlider.addEventListener("input", () => {
+    document.getElementById("render-distance-value").textContent = renderDistanceSlider.value;
+  });
+  renderDistanceSlider.addEventListener("change", () => {
+    wasmModule.set_render_distance(parseInt(renderDistanceSlider.value));
+  });
+
   const controlsInfoPopup = document.getElementById("controls-info-popup");
   document.addEventListener("pointerlockchange", () => {
     if (!document.pointerLockElement) {
@@ -300,4 +358,47 @@ import("../pkg/index").then((wasmModule) => {
 
   // async
   wasmModule.run(viewportWidth, viewportHeight);
+
+  // Handled once the game has loaded
+  const savedTimeOfDay = localStorage.getItem(TIME_OF_DAY_STORAGE_KEY);
+  if (savedTimeOfDay !== null) {
+    wasmModule.set_time_of_day(parseFloat(savedTimeOfDay));
+  }
+  (window as any).setTimeOfDay = (name: string) => wasmModule.set_time_of_day_by_name(name);
+  // "low", "medium" or "high". The web starts with low shadows unless `?shadows=` says otherwise
+  const shadowQuality = new URLSearchParams(window.location.search).get("shadows");
+  if (shadowQuality !== null) {
+    wasmModule.set_shadow_quality(shadowQuality);
+  }
+  (window as any).setShadowQuality = (name: string) => wasmModule.set_shadow_quality(name);
+  // Logs frame times with and without greedy meshing to the console, see frame_timing.rs
+  if (new URLSearchParams(window.location.search).has("timeFrames")) {
+    wasmModule.time_frames();
+  }
+  (window as any).timeFrames = () => wasmModule.time_frames();
+  const savedInventory = localStorage.getItem(INVENTORY_STORAGE_KEY);
+  if (savedInventory !== null) {
+    wasmModule.set_inventory(savedInventory);
+  }
+
+  // Resource packs are zip archives, see resource_pack.rs. Start with the one in `?resourcePack=`,
+  // or drop one onto the page to switch to it.
+  const loadResourcePack = async (url: string) => {
+    const response = await fetch(url);
+    wasmModule.load_resource_pack(new Uint8Array(await response.arrayBuffer()));
+  };
+  const resourcePackUrl = new URLSearchParams(window.location.search).get("resourcePack");
+  if (resourcePackUrl !== null) {
+    loadResourcePack(resourcePackUrl);
+  }
+  document.addEventListener("dragover", (event) => event.preventDefault());
+  document.addEventListener("drop", async (event) => {
+    event.preventDefault();
+    const file = event.dataTransfer?.files[0];
+    if (file) {
+      wasmModule.load_resource_pack(new Uint8Array(await file.arrayBuffer()));
+    }
+  });
+  (window as any).loadResourcePack = loadResourcePack;
+  (window as any).resetResourcePack = () => wasmModule.reset_resource_pack();
 });
\ No newline at end of file